@@ -0,0 +1,45 @@
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+
+use neovide::benchmarks::{
+    GridDraw, Shaping, FALLBACK_LINES, GRID_COLUMNS, GRID_ROWS, LIGATURE_LINES,
+};
+
+fn shaping(c: &mut Criterion) {
+    let mut group = c.benchmark_group("shape");
+    let runs = [
+        ("code with ligatures", LIGATURE_LINES, true),
+        ("code without ligatures", LIGATURE_LINES, false),
+        ("fallback text", FALLBACK_LINES, true),
+    ];
+    for (name, lines, ligatures) in runs {
+        group.bench_function(format!("{name}, cold cache"), |b| {
+            b.iter_batched_ref(
+                || Shaping::new(lines, ligatures),
+                Shaping::shape,
+                BatchSize::LargeInput,
+            )
+        });
+
+        let mut warm = Shaping::new(lines, ligatures);
+        warm.shape();
+        group.bench_function(format!("{name}, warm cache"), |b| b.iter(|| warm.shape()));
+    }
+    group.finish();
+}
+
+fn grid_draw(c: &mut Criterion) {
+    let mut group = c.benchmark_group("draw a full grid");
+    group.throughput(Throughput::Elements((GRID_COLUMNS * GRID_ROWS) as u64));
+
+    group.bench_function("cold cache", |b| {
+        b.iter_batched_ref(GridDraw::new, GridDraw::draw, BatchSize::LargeInput)
+    });
+
+    let mut warm = GridDraw::new();
+    warm.draw();
+    group.bench_function("warm cache", |b| b.iter(|| warm.draw()));
+    group.finish();
+}
+
+criterion_group!(benches, shaping, grid_draw);
+criterion_main!(benches);
@@ -0,0 +1,88 @@
+//! How long an event sent from another thread takes to reach an idle update loop, like the Neovim
+//! updates sent by the bridge. This needs a display to create the event loop on.
+
+use std::{
+    sync::mpsc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use winit::{
+    application::ApplicationHandler,
+    event::WindowEvent,
+    event_loop::{ActiveEventLoop, EventLoop},
+    platform::pump_events::EventLoopExtPumpEvents,
+    window::WindowId,
+};
+
+use neovide::benchmarks::idle_control_flow;
+
+// Long enough for the loop to be waiting when the event is sent
+const SEND_DELAY: Duration = Duration::from_millis(1);
+
+/// An update loop with nothing to draw, which records when the event sent at the carried time
+/// arrives.
+struct IdleLoop {
+    sleep_while_idle: bool,
+    latency: Option<Duration>,
+}
+
+impl ApplicationHandler<Instant> for IdleLoop {
+    fn resumed(&mut self, _event_loop: &ActiveEventLoop) {}
+
+    fn window_event(&mut self, _event_loop: &ActiveEventLoop, _id: WindowId, _event: WindowEvent) {}
+
+    fn user_event(&mut self, _event_loop: &ActiveEventLoop, sent: Instant) {
+        self.latency = Some(sent.elapsed());
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        event_loop.set_control_flow(idle_control_flow(self.sleep_while_idle));
+    }
+}
+
+fn input_latency(c: &mut Criterion) {
+    let mut event_loop = EventLoop::<Instant>::with_user_event()
+        .build()
+        .expect("Could not create the event loop");
+    let proxy = event_loop.create_proxy();
+    let (request_event, event_requests) = mpsc::channel::<()>();
+    thread::spawn(move || {
+        for () in event_requests {
+            thread::sleep(SEND_DELAY);
+            if proxy.send_event(Instant::now()).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut group = c.benchmark_group("input latency");
+    let runs = [
+        ("waking up at the refresh rate", false),
+        ("sleeping until the next event", true),
+    ];
+    for (name, sleep_while_idle) in runs {
+        group.bench_function(name, |b| {
+            b.iter_custom(|iterations| {
+                let mut idle_loop = IdleLoop {
+                    sleep_while_idle,
+                    latency: None,
+                };
+                let mut total = Duration::ZERO;
+                for _ in 0..iterations {
+                    request_event.send(()).unwrap();
+                    while idle_loop.latency.is_none() {
+                        event_loop.pump_app_events(None, &mut idle_loop);
+                    }
+                    total += idle_loop.latency.take().unwrap();
+                }
+                total
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, input_latency);
+criterion_main!(benches);
@@ -70,6 +70,82 @@ vim.api.nvim_create_user_command("NeovideFocus", function()
     rpcnotify("neovide.focus_window")
 end, {})
 
+vim.api.nvim_create_user_command("NeovideReloadFont", function()
+    rpcnotify("neovide.reload_font", vim.o.guifont)
+end, {})
+
+vim.api.nvim_create_user_command("NeovideCommand", function(command)
+    rpcrequest("neovide.command", unpack(command.fargs))
+end, { nargs = "+" })
+
+-- Neovim doesn't send horizontal scrolling to the UI, so forward it for the smooth scrolling.
+vim.api.nvim_create_autocmd({ "WinScrolled" }, {
+    callback = function()
+        for window, changes in pairs(vim.v.event) do
+            local handle = tonumber(window)
+            if handle and changes.leftcol ~= 0 and vim.api.nvim_win_is_valid(handle) then
+                local text_offset = vim.fn.getwininfo(handle)[1].textoff
+                rpcnotify("neovide.horizontal_scroll", handle, changes.leftcol, text_offset)
+            end
+        end
+    end
+})
+
+-- Keep Neovide up to date with the current directory, which is shown in the window title.
+vim.api.nvim_create_autocmd({ "VimEnter", "DirChanged" }, {
+    nested = true,
+    callback = function()
+        rpcnotify("neovide.cwd_changed", vim.fn.getcwd())
+    end
+})
+
+-- The colors of the NeovideCursor highlight group, used for the cursor instead of the guicursor
+-- highlight when the group is defined. This autocommand runs before the ones of the user config,
+-- which define the group again after the colorscheme has cleared it, so it's only read once they
+-- have run.
+vim.api.nvim_create_autocmd({ "VimEnter", "ColorScheme" }, {
+    callback = function()
+        vim.schedule(function()
+            local highlight = vim.api.nvim_get_hl(0, { name = "NeovideCursor", link = false })
+            rpcnotify("neovide.cursor_highlight", { fg = highlight.fg, bg = highlight.bg })
+        end)
+    end
+})
+
+-- Windows can have a Normal background of their own through 'winhighlight' or a highlight
+-- namespace, which Neovide clears their grids to.
+local window_backgrounds = {}
+local function send_window_backgrounds()
+    for _, window in ipairs(vim.api.nvim_list_wins()) do
+        local name = "Normal"
+        for from, to in vim.wo[window].winhighlight:gmatch("([^:,]+):([^,]+)") do
+            if from == "Normal" then
+                name = to
+            end
+        end
+        local namespace = vim.api.nvim_get_hl_ns and vim.api.nvim_get_hl_ns({ winid = window }) or -1
+        local highlight = vim.api.nvim_get_hl(math.max(namespace, 0), { name = name, link = false })
+        -- Windows without a highlight of their own keep the default background
+        local background = (name ~= "Normal" or namespace > 0) and highlight.bg or nil
+        if window_backgrounds[window] ~= (background or false) then
+            window_backgrounds[window] = background or false
+            rpcnotify("neovide.window_background", window, { bg = background })
+        end
+    end
+end
+vim.api.nvim_create_autocmd({ "VimEnter", "ColorScheme", "WinNew", "WinEnter", "BufWinEnter" }, {
+    callback = send_window_backgrounds
+})
+vim.api.nvim_create_autocmd({ "OptionSet" }, {
+    pattern = "winhighlight",
+    callback = send_window_backgrounds
+})
+vim.api.nvim_create_autocmd({ "WinClosed" }, {
+    callback = function(event)
+        window_backgrounds[tonumber(event.match)] = nil
+    end
+})
+
 vim.api.nvim_exec([[
 function! WatchGlobal(variable, callback)
     call dictwatcheradd(g:, a:variable, a:callback)
@@ -94,6 +170,49 @@ for _,option_setting in ipairs(args.option_settings) do
     })
 end
 
+-- The grid doesn't know which cells are a tab, so tell Neovide how far the one under the cursor
+-- stretches for the cursor to span it.
+local last_tab_span = nil
+local function send_tab_span()
+    if not vim.g.neovide_cursor_span_tabs then
+        -- Send it again when it's turned back on
+        last_tab_span = nil
+        return
+    end
+    local tab_span = {}
+    local mode = vim.fn.mode()
+    -- Only in these modes the cursor is in the buffer, in the others it's on the command line
+    -- or in a terminal, so the current line has nothing to do with it
+    local in_buffer = mode:find("^[nvVsSiR\22\19]") ~= nil
+    local line = vim.api.nvim_get_current_line()
+    local column = vim.fn.col(".")
+    if in_buffer and line:sub(column, column) == "\t" then
+        local first, last = unpack(vim.fn.virtcol(".", 1))
+        -- Neovim puts the cursor on the last cell of a tab, unless it's inserting or the tab
+        -- is drawn with 'listchars'
+        local on_first = mode:find("^[iR]") ~= nil
+            or (vim.wo.list and vim.opt_local.listchars:get().tab ~= nil)
+        local cursor = on_first and first or last
+        tab_span = { cursor - first, last - first + 1 }
+    end
+    local key = table.concat(tab_span, ",")
+    if key ~= last_tab_span then
+        last_tab_span = key
+        rpcnotify("neovide.cursor_tab_span", unpack(tab_span))
+    end
+end
+-- Editing the text before the cursor can move a tab under it, or change how wide it is
+vim.api.nvim_create_autocmd({
+    "CursorMoved", "CursorMovedI", "ModeChanged", "TextChanged", "TextChangedI"
+}, {
+    callback = send_tab_span
+})
+vim.api.nvim_create_autocmd({ "OptionSet" }, {
+    pattern = { "tabstop", "vartabstop", "list", "listchars" },
+    callback = send_tab_span
+})
+vim.fn.WatchGlobal("neovide_cursor_span_tabs", send_tab_span)
+
 -- Ignore initial values of lines and columns because they are set by neovim directly.
 -- See https://github.com/neovide/neovide/issues/2300
 vim.api.nvim_create_autocmd({ "VimEnter" }, {
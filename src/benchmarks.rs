@@ -0,0 +1,175 @@
+//! The setup of the benchmarks in `benches/`, for the text shaping and the grid drawing, the hot
+//! paths of every frame, and for the input latency of the update loop. The shaper, the grid
+//! renderer and the update loop aren't public, so the benchmarks only get to run the measured
+//! part through these.
+
+use std::{
+    hint::black_box,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use skia_safe::{colors, surfaces, Surface};
+use winit::event_loop::ControlFlow;
+
+use crate::{
+    editor::{Colors, Style},
+    renderer::{
+        fonts::{caching_shaper::ShapingFeatures, font_options::CoarseStyle},
+        CachingShaper, GridRenderer, RendererSettings,
+    },
+    settings::SETTINGS,
+    units::GridPos,
+    window::{control_flow, event_deadline, ShouldRender},
+};
+
+/// The size of the grid drawn by [`GridDraw`], in cells.
+pub const GRID_COLUMNS: usize = 200;
+pub const GRID_ROWS: usize = 60;
+
+/// Code with the operators programming fonts turn into ligatures.
+pub const LIGATURE_LINES: &[&str] = &[
+    "fn parse(input: &str) -> Result<Vec<Token>, Error> {",
+    "    if a != b && c >= d || e <= f { return x => y; }",
+    "    let arrows = [|x| x + 1, |y| y * 2] // <!-- --> ::= ===",
+    "}",
+];
+
+/// Scripts and symbols the default font doesn't have, which go through the font fallback.
+pub const FALLBACK_LINES: &[&str] = &[
+    "日本語のテキストと한국어 텍스트",
+    "数学 ∀x ∈ ℝ: λ → ∞ ≠ ∅",
+    "✓ ✗ ★ ☂ ♫ 🎉 🚀 🦀",
+];
+
+/// Shapes some lines in all the bold/italic styles. A new one starts with a cold cache, and it's
+/// warm after the first [`Shaping::shape`].
+pub struct Shaping {
+    shaper: CachingShaper,
+    lines: &'static [&'static str],
+}
+
+impl Shaping {
+    pub fn new(lines: &'static [&'static str], ligatures: bool) -> Self {
+        let mut shaper = CachingShaper::new(1.0);
+        shaper.set_shaping_features(ShapingFeatures {
+            ligatures,
+            ..ShapingFeatures::default()
+        });
+        Self { shaper, lines }
+    }
+
+    pub fn shape(&mut self) {
+        for style in CoarseStyle::permutations() {
+            for line in self.lines {
+                black_box(self.shaper.shape_cached(line.to_string(), style));
+            }
+        }
+    }
+}
+
+/// Draws a full grid of code, split into the runs of cells with the same style like the fragments
+/// of a line. Keywords are bold, comments italic and strings have a background.
+pub struct GridDraw {
+    grid_renderer: GridRenderer,
+    surface: Surface,
+    screen: Vec<Vec<(String, Option<Arc<Style>>)>>,
+}
+
+impl GridDraw {
+    /// The first draw shapes everything, after that the draws come from the shape cache.
+    pub fn new() -> Self {
+        SETTINGS.set(&RendererSettings::default());
+
+        let grid_renderer = GridRenderer::new(1.0);
+        let width = grid_renderer.grid_scale.width() * GRID_COLUMNS as f32;
+        let height = grid_renderer.grid_scale.height() * GRID_ROWS as f32;
+        let surface = surfaces::raster_n32_premul((width.ceil() as i32, height.ceil() as i32))
+            .expect("Could not create the surface");
+
+        Self {
+            grid_renderer,
+            surface,
+            screen: representative_screen(),
+        }
+    }
+
+    pub fn draw(&mut self) {
+        let canvas = self.surface.canvas();
+        canvas.clear(self.grid_renderer.get_default_background());
+        for (row, fragments) in self.screen.iter().enumerate() {
+            let mut column = 0;
+            for (text, style) in fragments {
+                let width = text.chars().count() as i32;
+                let position = GridPos::new(column, row as i32);
+                self.grid_renderer
+                    .draw_background(canvas, position, width, style);
+                self.grid_renderer
+                    .draw_foreground(canvas, text, position, width, style);
+                column += width;
+            }
+        }
+    }
+}
+
+impl Default for GridDraw {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn representative_screen() -> Vec<Vec<(String, Option<Arc<Style>>)>> {
+    let with_foreground = |foreground| Colors::new(Some(foreground), Some(colors::DARK_GREY), None);
+    let keyword = Style {
+        bold: true,
+        ..Style::new(with_foreground(colors::MAGENTA))
+    };
+    let comment = Style {
+        italic: true,
+        ..Style::new(with_foreground(colors::GREY))
+    };
+    let string = Style::new(Colors::new(Some(colors::GREEN), Some(colors::BLACK), None));
+    let styles = [
+        Some(Arc::new(keyword)),
+        None,
+        Some(Arc::new(string)),
+        None,
+        Some(Arc::new(comment)),
+    ];
+
+    let words = LIGATURE_LINES
+        .iter()
+        .chain(FALLBACK_LINES)
+        .flat_map(|line| line.split(' '))
+        .cycle();
+    let mut words = words.zip(styles.iter().cycle());
+
+    (0..GRID_ROWS)
+        .map(|_| {
+            let mut fragments = Vec::new();
+            let mut width = 0;
+            for (word, style) in words.by_ref() {
+                let text = format!("{word} ");
+                width += text.chars().count();
+                if width > GRID_COLUMNS {
+                    break;
+                }
+                fragments.push((text, style.clone()));
+            }
+            fragments
+        })
+        .collect()
+}
+
+/// How the update loop waits for the next event when nothing is drawn or animated. When
+/// `sleep_while_idle` is false it wakes up at the refresh rate, like it used to poll for changes
+/// before it slept until the next event.
+pub fn idle_control_flow(sleep_while_idle: bool) -> ControlFlow {
+    let frame_deadline = Instant::now() + Duration::from_secs_f32(1.0 / 60.0);
+    control_flow(event_deadline(
+        &ShouldRender::Wait,
+        sleep_while_idle,
+        frame_deadline,
+        None,
+    ))
+}
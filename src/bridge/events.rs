@@ -141,6 +141,10 @@ pub enum RedrawEvent {
     SetTitle {
         title: String,
     },
+    /// The `iconstring` option, the title of the window while it's minimized
+    SetIcon {
+        icon: String,
+    },
     ModeInfoSet {
         cursor_modes: Vec<CursorMode>,
     },
@@ -155,6 +159,8 @@ pub enum RedrawEvent {
     MouseOff,
     BusyStart,
     BusyStop,
+    Bell,
+    VisualBell,
     Flush,
     Resize {
         grid: u64,
@@ -173,6 +179,8 @@ pub enum RedrawEvent {
         row: u64,
         column_start: u64,
         cells: Vec<GridLineCell>,
+        // Added in Neovim 0.10, the line continues on the next row
+        wrap: bool,
     },
     Clear {
         grid: u64,
@@ -230,15 +238,11 @@ pub enum RedrawEvent {
     },
     WindowViewport {
         grid: u64,
-        #[allow(unused)]
+        window: u64,
         top_line: f64,
-        #[allow(unused)]
         bottom_line: f64,
-        #[allow(unused)]
         current_line: f64,
-        #[allow(unused)]
         current_column: f64,
-        #[allow(unused)]
         line_count: Option<f64>,
         scroll_delta: Option<f64>,
     },
@@ -305,9 +309,48 @@ pub enum RedrawEvent {
         entries: Vec<(MessageKind, StyledContent)>,
     },
     Suspend,
+    /// Not a Neovim redraw event. Neovim doesn't tell the UI about horizontal scrolling, so the
+    /// Neovide init.lua sends it from the WinScrolled autocommand instead.
+    WindowHorizontalScroll {
+        window: u64,
+        columns: i64,
+        text_offset: u64,
+    },
+    /// Sent by the Neovide init.lua when the current directory of Neovim changes.
+    CwdChanged {
+        cwd: String,
+    },
+    /// Sent by the Neovide init.lua with the colors of the `NeovideCursor` highlight group, when
+    /// the group is defined.
+    CursorHighlight {
+        colors: Option<Colors>,
+    },
+    /// Sent by the Neovide init.lua with the background of the `Normal` highlight a window uses,
+    /// or nothing when that's the default one.
+    WindowBackground {
+        window: u64,
+        background: Option<Color4f>,
+    },
+    /// Not a Neovim redraw event either, but the `copy_visible_text` command, which needs the
+    /// grid contents of the editor.
+    CopyVisibleText,
+    /// Sent by plugins with `neovide.set_image`, to draw an image over cells of a window.
+    ImageSet {
+        window: u64,
+        id: u64,
+        row: i64,
+        column: i64,
+        width: u64,
+        height: u64,
+        data: Vec<u8>,
+    },
+    ImageRemove {
+        window: u64,
+        id: u64,
+    },
 }
 
-fn unpack_color(packed_color: u64) -> Color4f {
+pub fn unpack_color(packed_color: u64) -> Color4f {
     let packed_color = packed_color as u32;
     let r = ((packed_color & 0x00ff_0000) >> 16) as f32;
     let g = ((packed_color & 0xff00) >> 8) as f32;
@@ -391,6 +434,17 @@ fn parse_bool(bool_value: Value) -> Result<bool> {
     bool_value.try_into().map_err(ParseError::Bool)
 }
 
+/// Window handles are sent as a msgpack extension wrapping the handle number.
+fn parse_window_handle(window_value: Value) -> Result<u64> {
+    match &window_value {
+        Value::Ext(_, data) => rmpv::decode::read_value(&mut data.as_slice())
+            .ok()
+            .and_then(|handle| handle.as_u64())
+            .ok_or(ParseError::U64(window_value)),
+        _ => parse_u64(window_value),
+    }
+}
+
 fn parse_set_title(set_title_arguments: Vec<Value>) -> Result<RedrawEvent> {
     let [title] = extract_values(set_title_arguments)?;
 
@@ -399,18 +453,31 @@ fn parse_set_title(set_title_arguments: Vec<Value>) -> Result<RedrawEvent> {
     })
 }
 
+fn parse_set_icon(set_icon_arguments: Vec<Value>) -> Result<RedrawEvent> {
+    let [icon] = extract_values(set_icon_arguments)?;
+
+    Ok(RedrawEvent::SetIcon {
+        icon: parse_string(icon)?,
+    })
+}
+
 fn parse_mode_info_set(mode_info_set_arguments: Vec<Value>) -> Result<RedrawEvent> {
     let [_cursor_style_enabled, mode_info] = extract_values(mode_info_set_arguments)?;
 
     let mode_info_values = parse_array(mode_info)?;
     let mut cursor_modes = Vec::with_capacity(mode_info_values.len());
+    let mut mode_names = Vec::with_capacity(mode_info_values.len());
 
     for mode_info_value in mode_info_values {
         let info_map = parse_map(mode_info_value)?;
         let mut mode_info = CursorMode::default();
+        let mut mode_name = None;
 
         for (name, value) in info_map {
             match parse_string(name)?.as_str() {
+                "name" => {
+                    mode_name = Some(parse_string(value)?);
+                }
                 "cursor_shape" => {
                     mode_info.shape = CursorShape::from_type_name(&parse_string(value)?);
                 }
@@ -434,6 +501,19 @@ fn parse_mode_info_set(mode_info_set_arguments: Vec<Value>) -> Result<RedrawEven
         }
 
         cursor_modes.push(mode_info);
+        mode_names.push(mode_name);
+    }
+
+    // Terminal mode is insert-like, so when guicursor doesn't give it a shape of its own, use the
+    // insert mode shape instead of keeping whatever shape the previous mode had.
+    let mode_index = |name: &str| mode_names.iter().position(|n| n.as_deref() == Some(name));
+    if let (Some(terminal), Some(insert)) = (mode_index("terminal"), mode_index("insert")) {
+        if cursor_modes[terminal].shape.is_none() {
+            cursor_modes[terminal].shape = cursor_modes[insert].shape.clone();
+            cursor_modes[terminal].cell_percentage = cursor_modes[terminal]
+                .cell_percentage
+                .or(cursor_modes[insert].cell_percentage);
+        }
     }
 
     Ok(RedrawEvent::ModeInfoSet { cursor_modes })
@@ -593,7 +673,8 @@ fn parse_grid_line_cell(grid_line_cell: Value) -> Result<GridLineCell> {
 }
 
 fn parse_grid_line(grid_line_arguments: Vec<Value>) -> Result<RedrawEvent> {
-    let [grid_id, row, column_start, cells] = extract_values(grid_line_arguments)?;
+    let ([grid_id, row, column_start, cells], [wrap]) =
+        extract_values_with_optional(grid_line_arguments)?;
 
     Ok(RedrawEvent::GridLine {
         grid: parse_u64(grid_id)?,
@@ -603,6 +684,7 @@ fn parse_grid_line(grid_line_arguments: Vec<Value>) -> Result<RedrawEvent> {
             .into_iter()
             .map(parse_grid_line_cell)
             .collect::<Result<Vec<GridLineCell>>>()?,
+        wrap: wrap.map(parse_bool).transpose()?.unwrap_or(false),
     })
 }
 
@@ -728,12 +810,13 @@ fn parse_msg_set_pos(msg_set_pos_arguments: Vec<Value>) -> Result<RedrawEvent> {
 
 fn parse_win_viewport(win_viewport_arguments: Vec<Value>) -> Result<RedrawEvent> {
     let (
-        [grid, _window, top_line, bottom_line, current_line, current_column],
+        [grid, window, top_line, bottom_line, current_line, current_column],
         [line_count, scroll_delta],
     ) = extract_values_with_optional(win_viewport_arguments)?;
 
     Ok(RedrawEvent::WindowViewport {
         grid: parse_u64(grid)?,
+        window: parse_window_handle(window)?,
         top_line: parse_f64(top_line)?,
         bottom_line: parse_f64(bottom_line)?,
         current_line: parse_f64(current_line)?,
@@ -887,7 +970,7 @@ pub fn parse_redraw_event(event_value: Value) -> Result<Vec<RedrawEvent>> {
         let event_parameters_copy = event_parameters.clone();
         let possible_parsed_event = match event_name.as_str() {
             "set_title" => Some(parse_set_title(event_parameters)),
-            "set_icon" => None, // Ignore set icon for now
+            "set_icon" => Some(parse_set_icon(event_parameters)),
             "mode_info_set" => Some(parse_mode_info_set(event_parameters)),
             "option_set" => Some(parse_option_set(event_parameters)),
             "mode_change" => Some(parse_mode_change(event_parameters)),
@@ -895,6 +978,8 @@ pub fn parse_redraw_event(event_value: Value) -> Result<Vec<RedrawEvent>> {
             "mouse_off" => Some(Ok(RedrawEvent::MouseOff)),
             "busy_start" => Some(Ok(RedrawEvent::BusyStart)),
             "busy_stop" => Some(Ok(RedrawEvent::BusyStop)),
+            "bell" => Some(Ok(RedrawEvent::Bell)),
+            "visual_bell" => Some(Ok(RedrawEvent::VisualBell)),
             "flush" => Some(Ok(RedrawEvent::Flush)),
             "grid_resize" => Some(parse_grid_resize(event_parameters)),
             "default_colors_set" => Some(parse_default_colors(event_parameters)),
@@ -944,3 +1029,201 @@ pub fn parse_redraw_event(event_value: Value) -> Result<Vec<RedrawEvent>> {
 
     Ok(parsed_events)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mode_info(entries: &[(&str, Option<&str>, Option<u64>)]) -> Value {
+        Value::Array(
+            entries
+                .iter()
+                .map(|(name, shape, cell_percentage)| {
+                    let mut map = vec![(Value::from("name"), Value::from(*name))];
+                    if let Some(shape) = shape {
+                        map.push((Value::from("cursor_shape"), Value::from(*shape)));
+                    }
+                    if let Some(cell_percentage) = cell_percentage {
+                        map.push((
+                            Value::from("cell_percentage"),
+                            Value::from(*cell_percentage),
+                        ));
+                    }
+                    Value::Map(map)
+                })
+                .collect(),
+        )
+    }
+
+    fn parse_cursor_modes(mode_info: Value) -> Vec<CursorMode> {
+        match parse_mode_info_set(vec![Value::from(true), mode_info]).unwrap() {
+            RedrawEvent::ModeInfoSet { cursor_modes } => cursor_modes,
+            event => panic!("Unexpected event {event:?}"),
+        }
+    }
+
+    #[test]
+    fn set_icon_is_parsed() {
+        let event = Value::Array(vec![
+            Value::from("set_icon"),
+            Value::Array(vec![Value::from("nvim")]),
+        ]);
+        let events = parse_redraw_event(event).unwrap();
+        assert!(matches!(&events[..], [RedrawEvent::SetIcon { icon }] if icon == "nvim"));
+
+        // Malformed arguments are an error instead of a panic
+        let event = Value::Array(vec![Value::from("set_icon"), Value::Array(vec![])]);
+        assert!(parse_redraw_event(event).is_err());
+    }
+
+    #[test]
+    fn terminal_mode_uses_its_own_cursor_shape() {
+        let cursor_modes = parse_cursor_modes(mode_info(&[
+            ("normal", Some("block"), Some(100)),
+            ("insert", Some("vertical"), Some(25)),
+            ("terminal", Some("horizontal"), Some(20)),
+        ]));
+
+        assert_eq!(cursor_modes[2].shape, Some(CursorShape::Horizontal));
+        assert_eq!(cursor_modes[2].cell_percentage, Some(0.2));
+    }
+
+    #[test]
+    fn terminal_mode_without_shape_falls_back_to_insert_shape() {
+        let cursor_modes = parse_cursor_modes(mode_info(&[
+            ("normal", Some("block"), Some(100)),
+            ("insert", Some("vertical"), Some(25)),
+            ("terminal", None, None),
+        ]));
+
+        assert_eq!(cursor_modes[0].shape, Some(CursorShape::Block));
+        assert_eq!(cursor_modes[2].shape, Some(CursorShape::Vertical));
+        assert_eq!(cursor_modes[2].cell_percentage, Some(0.25));
+    }
+
+    #[test]
+    fn replace_mode_keeps_its_cursor_shape() {
+        let cursor_modes = parse_cursor_modes(mode_info(&[
+            ("normal", Some("block"), Some(100)),
+            ("insert", Some("vertical"), Some(25)),
+            ("replace", Some("horizontal"), Some(20)),
+        ]));
+
+        assert_eq!(cursor_modes[2].shape, Some(CursorShape::Horizontal));
+        assert_eq!(cursor_modes[2].cell_percentage, Some(0.2));
+        match parse_mode_change(vec![Value::from("replace"), Value::from(2)]).unwrap() {
+            RedrawEvent::ModeChange { mode, mode_index } => {
+                assert_eq!(mode, EditorMode::Replace);
+                assert_eq!(mode_index, 2);
+            }
+            event => panic!("Unexpected event {event:?}"),
+        }
+    }
+
+    #[test]
+    fn highlight_special_color_is_separate_from_foreground() {
+        let attributes = Value::Map(vec![
+            (Value::from("foreground"), Value::from(0xff0000)),
+            (Value::from("special"), Value::from(0x00ff00)),
+            (Value::from("undercurl"), Value::from(true)),
+        ]);
+        let event = parse_hl_attr_define(vec![
+            Value::from(1),
+            attributes,
+            Value::Map(vec![]),
+            Value::Array(vec![]),
+        ])
+        .unwrap();
+
+        let (id, style) = match event {
+            RedrawEvent::HighlightAttributesDefine { id, style } => (id, style),
+            event => panic!("Unexpected event {event:?}"),
+        };
+        let default_colors = Colors::new(
+            Some(unpack_color(0xffffff)),
+            Some(unpack_color(0x000000)),
+            Some(unpack_color(0x0000ff)),
+        );
+
+        assert_eq!(id, 1);
+        assert_eq!(style.underline, Some(UnderlineStyle::UnderCurl));
+        assert_eq!(style.foreground(&default_colors), unpack_color(0xff0000));
+        assert_eq!(style.special(&default_colors), unpack_color(0x00ff00));
+    }
+
+    #[test]
+    fn grid_line_keeps_astral_plane_characters() {
+        let cells = Value::Array(vec![
+            Value::Array(vec![Value::from("a"), Value::from(1)]),
+            Value::Array(vec![Value::from("😀")]),
+            Value::Array(vec![Value::from("")]),
+            Value::Array(vec![Value::from("𠜎"), Value::from(2), Value::from(2)]),
+        ]);
+        let event =
+            parse_grid_line(vec![Value::from(1), Value::from(0), Value::from(0), cells]).unwrap();
+
+        let cells = match event {
+            RedrawEvent::GridLine { cells, .. } => cells,
+            event => panic!("Unexpected event {event:?}"),
+        };
+        let texts: Vec<&str> = cells.iter().map(|cell| cell.text.as_str()).collect();
+        assert_eq!(texts, vec!["a", "😀", "", "𠜎"]);
+        assert_eq!(cells[3].highlight_id, Some(2));
+        assert_eq!(cells[3].repeat, Some(2));
+    }
+
+    #[test]
+    fn grid_line_reads_the_optional_wrap_flag() {
+        let cells = || Value::Array(vec![Value::Array(vec![Value::from("a")])]);
+        let wrapped = parse_grid_line(vec![
+            Value::from(1),
+            Value::from(0),
+            Value::from(0),
+            cells(),
+            Value::from(true),
+        ])
+        .unwrap();
+        assert!(matches!(wrapped, RedrawEvent::GridLine { wrap: true, .. }));
+
+        let old_neovim = parse_grid_line(vec![
+            Value::from(1),
+            Value::from(0),
+            Value::from(0),
+            cells(),
+        ])
+        .unwrap();
+        assert!(matches!(
+            old_neovim,
+            RedrawEvent::GridLine { wrap: false, .. }
+        ));
+    }
+
+    #[test]
+    fn win_viewport_reads_the_window_handle() {
+        let mut handle = Vec::new();
+        rmpv::encode::write_value(&mut handle, &Value::from(1001)).unwrap();
+        let event = parse_win_viewport(vec![
+            Value::from(2),
+            Value::Ext(1, handle),
+            Value::from(0),
+            Value::from(10),
+            Value::from(3),
+            Value::from(4),
+        ])
+        .unwrap();
+
+        match event {
+            RedrawEvent::WindowViewport {
+                grid,
+                window,
+                scroll_delta,
+                ..
+            } => {
+                assert_eq!(grid, 2);
+                assert_eq!(window, 1001);
+                assert_eq!(scroll_delta, None);
+            }
+            event => panic!("Unexpected event {event:?}"),
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use rmpv::Value;
+
+use crate::window::WindowCommand;
+
+type GuiCommandHandler = fn(&[Value]) -> Result<GuiCommand, String>;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum GuiCommand {
+    Window(WindowCommand),
+    /// Copies the text of the current window to the clipboard. This is handled by the editor,
+    /// since that's where the grid contents are.
+    CopyVisibleText,
+    /// Flips `g:neovide_font_ligatures`, so that the variable stays in sync with what's drawn.
+    ToggleLigatures,
+    /// Selects the next cursor vfx mode, built-in or registered, to preview them.
+    CycleVfxMode,
+    /// Saves the current frame as a PNG. The path is resolved by Neovim first, so that it's
+    /// relative to its working directory.
+    Screenshot(String),
+    /// Pastes the system clipboard with `nvim_paste`, so that Neovim doesn't auto-indent it.
+    PasteClipboard,
+}
+
+impl From<WindowCommand> for GuiCommand {
+    fn from(command: WindowCommand) -> Self {
+        GuiCommand::Window(command)
+    }
+}
+
+/// The GUI side actions that can be run from Neovim by name, with
+/// `rpcrequest(g:neovide_channel_id, "neovide.command", name, args...)`.
+const GUI_COMMANDS: &[(&str, GuiCommandHandler)] = &[
+    ("toggle_fullscreen", |_| {
+        Ok(WindowCommand::ToggleFullscreen.into())
+    }),
+    ("zoom_in", |_| Ok(WindowCommand::Zoom(1).into())),
+    ("zoom_out", |_| Ok(WindowCommand::Zoom(-1).into())),
+    ("zoom_reset", |_| Ok(WindowCommand::ResetZoom.into())),
+    ("focus_window", |_| Ok(WindowCommand::FocusWindow.into())),
+    ("minimize", |_| Ok(WindowCommand::Minimize.into())),
+    ("reload_font", |arguments| {
+        let guifont = match arguments.first() {
+            None => "",
+            Some(value) => value
+                .as_str()
+                .ok_or("reload_font expects the guifont as a string")?,
+        };
+        Ok(WindowCommand::ReloadFont(guifont.to_string()).into())
+    }),
+    ("diagnostics", |arguments| {
+        let path = match arguments.first() {
+            None => None,
+            Some(value) => Some(
+                value
+                    .as_str()
+                    .ok_or("diagnostics expects the path of the file as a string")?
+                    .to_string(),
+            ),
+        };
+        Ok(WindowCommand::ShowDiagnostics(path).into())
+    }),
+    ("copy_visible_text", |_| Ok(GuiCommand::CopyVisibleText)),
+    ("toggle_ligatures", |_| Ok(GuiCommand::ToggleLigatures)),
+    ("cycle_vfx_mode", |_| Ok(GuiCommand::CycleVfxMode)),
+    ("screenshot", |arguments| {
+        let path = arguments
+            .first()
+            .and_then(|value| value.as_str())
+            .ok_or("screenshot expects the path of the PNG file as a string")?;
+        Ok(GuiCommand::Screenshot(path.to_string()))
+    }),
+    ("paste", |_| Ok(GuiCommand::PasteClipboard)),
+];
+
+pub fn dispatch_gui_command(name: &str, arguments: &[Value]) -> Result<GuiCommand, String> {
+    let (_, handler) = GUI_COMMANDS
+        .iter()
+        .find(|(command_name, _)| *command_name == name)
+        .ok_or_else(|| format!("Unknown Neovide command: {name}"))?;
+    handler(arguments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands_are_dispatched_by_name() {
+        assert_eq!(
+            dispatch_gui_command("zoom_in", &[]),
+            Ok(WindowCommand::Zoom(1).into())
+        );
+        assert_eq!(
+            dispatch_gui_command("zoom_reset", &[]),
+            Ok(WindowCommand::ResetZoom.into())
+        );
+        assert_eq!(
+            dispatch_gui_command("toggle_fullscreen", &[]),
+            Ok(WindowCommand::ToggleFullscreen.into())
+        );
+        assert_eq!(
+            dispatch_gui_command("reload_font", &[Value::from("Fira Code:h14")]),
+            Ok(WindowCommand::ReloadFont("Fira Code:h14".to_string()).into())
+        );
+        assert_eq!(
+            dispatch_gui_command("diagnostics", &[Value::from("neovide.txt")]),
+            Ok(WindowCommand::ShowDiagnostics(Some("neovide.txt".to_string())).into())
+        );
+        assert_eq!(
+            dispatch_gui_command("copy_visible_text", &[]),
+            Ok(GuiCommand::CopyVisibleText)
+        );
+        assert_eq!(
+            dispatch_gui_command("toggle_ligatures", &[]),
+            Ok(GuiCommand::ToggleLigatures)
+        );
+        assert_eq!(
+            dispatch_gui_command("cycle_vfx_mode", &[]),
+            Ok(GuiCommand::CycleVfxMode)
+        );
+        assert_eq!(
+            dispatch_gui_command("screenshot", &[Value::from("~/neovide.png")]),
+            Ok(GuiCommand::Screenshot("~/neovide.png".to_string()))
+        );
+        assert_eq!(
+            dispatch_gui_command("paste", &[]),
+            Ok(GuiCommand::PasteClipboard)
+        );
+    }
+
+    #[test]
+    fn invalid_commands_return_errors() {
+        assert!(dispatch_gui_command("print_window", &[]).is_err());
+        assert!(dispatch_gui_command("reload_font", &[Value::from(14)]).is_err());
+        assert!(dispatch_gui_command("screenshot", &[]).is_err());
+    }
+
+    #[test]
+    fn command_names_are_unique() {
+        let mut names: Vec<_> = GUI_COMMANDS.iter().map(|(name, _)| *name).collect();
+        names.sort();
+        names.dedup();
+        assert_eq!(names.len(), GUI_COMMANDS.len());
+    }
+}
@@ -1,18 +1,27 @@
 use async_trait::async_trait;
-use log::trace;
+use log::{trace, warn};
 use nvim_rs::{Handler, Neovim};
 use rmpv::Value;
 use std::sync::Arc;
-use std::sync::Mutex;
+use std::sync::{Mutex, MutexGuard, PoisonError};
 use tokio::sync::mpsc::UnboundedSender;
 use winit::event_loop::EventLoopProxy;
 
 use crate::{
     bridge::clipboard::{get_clipboard_contents, set_clipboard_contents},
-    bridge::{events::parse_redraw_event, NeovimWriter, RedrawEvent},
+    bridge::{
+        events::{parse_redraw_event, unpack_color},
+        gui_commands::{dispatch_gui_command, GuiCommand},
+        send_ui, NeovimWriter, RedrawEvent, SerialCommand,
+    },
+    clipboard,
+    editor::Colors,
     error_handling::ResultPanicExplanation,
+    renderer::cursor_renderer::{cursor_vfx::VFX_REGISTRY, CursorSettings},
     running_tracker::*,
     settings::SETTINGS,
+    units::GridPos,
+    utils::decode_base64,
     window::{UserEvent, WindowCommand},
     LoggingSender,
 };
@@ -31,6 +40,16 @@ impl NeovimHandler {
             sender: LoggingSender::attach(sender, "neovim_handler"),
         }
     }
+
+    fn proxy(&self) -> MutexGuard<EventLoopProxy<UserEvent>> {
+        lock_unpoisoned(&self.proxy)
+    }
+}
+
+/// A handler that panicked while sending an event doesn't leave the proxy in a broken state, so
+/// the poisoning is ignored instead of taking down every later notification too.
+fn lock_unpoisoned<T>(mutex: &Mutex<T>) -> MutexGuard<T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
 }
 
 #[async_trait]
@@ -62,6 +81,68 @@ impl Handler for NeovimHandler {
             }
             "neovide.set_clipboard" => set_clipboard_contents(&arguments[0], &arguments[1])
                 .map_err(|_| Value::from("cannot set clipboard contents")),
+            "neovide.command" => {
+                let name = arguments
+                    .first()
+                    .and_then(|value| value.as_str())
+                    .unwrap_or_default();
+                let command_arguments = arguments.get(1..).unwrap_or_default();
+                match dispatch_gui_command(name, command_arguments)? {
+                    GuiCommand::Window(command) => {
+                        let _ = self.proxy().send_event(command.into());
+                    }
+                    GuiCommand::CopyVisibleText => {
+                        let _ = self.sender.send(RedrawEvent::CopyVisibleText);
+                    }
+                    // The change is picked up by the variable watcher like any other setting
+                    GuiCommand::ToggleLigatures => {
+                        neovim
+                            .command("let g:neovide_font_ligatures = !g:neovide_font_ligatures")
+                            .await
+                            .map_err(|_| Value::from("cannot toggle ligatures"))?;
+                    }
+                    GuiCommand::CycleVfxMode => {
+                        let current = SETTINGS.get::<CursorSettings>().vfx_mode;
+                        let mode = Value::from(VFX_REGISTRY.next_mode(&current));
+                        let message = match mode.as_str() {
+                            Some("") | None => "Cursor VFX disabled".to_string(),
+                            Some(name) => format!("Cursor VFX: {name}"),
+                        };
+                        neovim
+                            .set_var("neovide_cursor_vfx_mode", mode)
+                            .await
+                            .map_err(|_| Value::from("cannot set the cursor vfx mode"))?;
+                        neovim
+                            .echo(
+                                vec![Value::Array(vec![Value::from(message)])],
+                                false,
+                                vec![],
+                            )
+                            .await
+                            .map_err(|_| Value::from("cannot show the cursor vfx mode"))?;
+                    }
+                    GuiCommand::Screenshot(path) => {
+                        let path = neovim
+                            .call_function(
+                                "fnamemodify",
+                                vec![Value::from(path), Value::from(":p")],
+                            )
+                            .await
+                            .map_err(|_| Value::from("cannot resolve the screenshot path"))?;
+                        let path = path.as_str().unwrap_or_default().to_string();
+                        let _ = self
+                            .proxy()
+                            .send_event(WindowCommand::Screenshot(path).into());
+                    }
+                    // Neovim handles the paste once it has the response to this request
+                    GuiCommand::PasteClipboard => {
+                        let text = clipboard::get_contents("+")
+                            .map_err(|_| Value::from("cannot get clipboard contents"))?;
+                        send_ui(SerialCommand::Paste(text));
+                    }
+                }
+                Ok(Value::Nil)
+            }
             "neovide.quit" => {
                 let error_code = arguments[0]
                     .as_i64()
@@ -93,36 +174,269 @@ impl Handler for NeovimHandler {
                 }
             }
             "setting_changed" => {
-                SETTINGS
-                    .handle_setting_changed_notification(arguments, &self.proxy.lock().unwrap());
+                SETTINGS.handle_setting_changed_notification(arguments, &self.proxy());
             }
             "option_changed" => {
-                SETTINGS.handle_option_changed_notification(arguments, &self.proxy.lock().unwrap());
+                SETTINGS.handle_option_changed_notification(arguments, &self.proxy());
             }
             #[cfg(windows)]
             "neovide.register_right_click" => {
                 let _ = self
-                    .proxy
-                    .lock()
-                    .unwrap()
+                    .proxy()
                     .send_event(WindowCommand::RegisterRightClick.into());
             }
             #[cfg(windows)]
             "neovide.unregister_right_click" => {
                 let _ = self
-                    .proxy
-                    .lock()
-                    .unwrap()
+                    .proxy()
                     .send_event(WindowCommand::UnregisterRightClick.into());
             }
-            "neovide.focus_window" => {
+            "neovide.reload_font" => {
+                let guifont = arguments
+                    .first()
+                    .and_then(|value| value.as_str())
+                    .unwrap_or_default()
+                    .to_string();
+                let _ = self
+                    .proxy()
+                    .send_event(WindowCommand::ReloadFont(guifont).into());
+            }
+            "neovide.set_secondary_cursors" => {
+                let positions = arguments
+                    .first()
+                    .and_then(|value| value.as_array())
+                    .map(|positions| parse_secondary_cursors(positions.as_slice()))
+                    .unwrap_or_default();
                 let _ = self
-                    .proxy
-                    .lock()
-                    .unwrap()
-                    .send_event(WindowCommand::FocusWindow.into());
+                    .proxy()
+                    .send_event(WindowCommand::SetSecondaryCursors(positions).into());
+            }
+            "neovide.cursor_tab_span" => {
+                let tab_span = parse_cursor_tab_span(&arguments);
+                let _ = self
+                    .proxy()
+                    .send_event(WindowCommand::SetCursorTabSpan(tab_span).into());
+            }
+            "neovide.focus_window" => {
+                let _ = self.proxy().send_event(WindowCommand::FocusWindow.into());
+            }
+            "neovide.set_image" => match parse_set_image(&arguments) {
+                Some(event) => {
+                    let _ = self.sender.send(event);
+                }
+                None => warn!("Invalid arguments for neovide.set_image"),
+            },
+            "neovide.remove_image" => {
+                if let [window, id] = arguments.as_slice() {
+                    if let (Some(window), Some(id)) = (window.as_u64(), id.as_u64()) {
+                        let _ = self.sender.send(RedrawEvent::ImageRemove { window, id });
+                    }
+                }
+            }
+            "neovide.cwd_changed" => {
+                if let Some(event) = parse_cwd_changed(&arguments) {
+                    let _ = self.sender.send(event);
+                }
+            }
+            "neovide.cursor_highlight" => {
+                let _ = self.sender.send(parse_cursor_highlight(&arguments));
+            }
+            "neovide.window_background" => {
+                if let Some(event) = parse_window_background(&arguments) {
+                    let _ = self.sender.send(event);
+                }
+            }
+            "neovide.horizontal_scroll" => {
+                if let Some(event) = parse_horizontal_scroll(&arguments) {
+                    let _ = self.sender.send(event);
+                }
             }
             _ => {}
         }
     }
 }
+
+/// The cells of the tab before the cursor cell and the width of the tab, or nothing when the
+/// cursor isn't on a tab.
+fn parse_cursor_tab_span(arguments: &[Value]) -> Option<(u64, u64)> {
+    match arguments {
+        [before, width] => Some((before.as_u64()?, width.as_u64()?)),
+        _ => None,
+    }
+}
+
+/// Parses a list of `[row, col]` pairs, skipping any malformed entries.
+fn parse_secondary_cursors(positions: &[Value]) -> Vec<GridPos<u64>> {
+    positions
+        .iter()
+        .filter_map(|position| match position.as_array()?.as_slice() {
+            [row, col] => Some(GridPos::new(col.as_u64()?, row.as_u64()?)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Parses the `window, columns, text_offset` arguments sent by the WinScrolled autocommand.
+fn parse_horizontal_scroll(arguments: &[Value]) -> Option<RedrawEvent> {
+    match arguments {
+        [window, columns, text_offset] => Some(RedrawEvent::WindowHorizontalScroll {
+            window: window.as_u64()?,
+            columns: columns.as_i64()?,
+            text_offset: text_offset.as_u64()?,
+        }),
+        _ => None,
+    }
+}
+
+/// Parses the `fg` and `bg` of the `NeovideCursor` highlight group, as returned by `nvim_get_hl`.
+/// The group isn't defined unless it has one of them.
+fn parse_cursor_highlight(arguments: &[Value]) -> RedrawEvent {
+    let color = |name| {
+        arguments
+            .first()?
+            .as_map()?
+            .iter()
+            .find(|(key, _)| key.as_str() == Some(name))?
+            .1
+            .as_u64()
+            .map(unpack_color)
+    };
+    let (foreground, background) = (color("fg"), color("bg"));
+    let colors = (foreground.is_some() || background.is_some())
+        .then(|| Colors::new(foreground, background, None));
+    RedrawEvent::CursorHighlight { colors }
+}
+
+/// Parses the `window, { bg = ... }` arguments of `neovide.window_background`.
+fn parse_window_background(arguments: &[Value]) -> Option<RedrawEvent> {
+    let [window, highlight] = arguments else {
+        return None;
+    };
+    let background = highlight
+        .as_map()
+        .and_then(|highlight| highlight.iter().find(|(key, _)| key.as_str() == Some("bg")))
+        .and_then(|(_, background)| background.as_u64())
+        .map(unpack_color);
+    Some(RedrawEvent::WindowBackground {
+        window: window.as_u64()?,
+        background,
+    })
+}
+
+fn parse_cwd_changed(arguments: &[Value]) -> Option<RedrawEvent> {
+    let cwd = arguments.first()?.as_str()?;
+    Some(RedrawEvent::CwdChanged {
+        cwd: cwd.to_string(),
+    })
+}
+
+/// Parses the `window, id, row, column, width, height, data` arguments of `neovide.set_image`,
+/// with the encoded image as base64.
+fn parse_set_image(arguments: &[Value]) -> Option<RedrawEvent> {
+    match arguments {
+        [window, id, row, column, width, height, data] => Some(RedrawEvent::ImageSet {
+            window: window.as_u64()?,
+            id: id.as_u64()?,
+            row: row.as_i64()?,
+            column: column.as_i64()?,
+            width: width.as_u64()?,
+            height: height.as_u64()?,
+            data: decode_base64(data.as_str()?)?,
+        }),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn poisoned_lock_is_still_usable() {
+        let mutex = Arc::new(Mutex::new(1));
+        let poisoner = mutex.clone();
+        let _ = std::thread::spawn(move || {
+            let _guard = poisoner.lock().unwrap();
+            panic!("poisoning the lock");
+        })
+        .join();
+        assert!(mutex.is_poisoned());
+
+        *lock_unpoisoned(&mutex) += 1;
+        assert_eq!(*lock_unpoisoned(&mutex), 2);
+    }
+
+    #[test]
+    fn secondary_cursors_are_parsed_as_row_col_pairs() {
+        let positions = vec![
+            Value::from(vec![Value::from(2), Value::from(5)]),
+            Value::from(vec![Value::from(3)]),
+            Value::from("invalid"),
+            Value::from(vec![Value::from(0), Value::from(1)]),
+        ];
+
+        assert_eq!(
+            parse_secondary_cursors(&positions),
+            vec![GridPos::new(5, 2), GridPos::new(1, 0)]
+        );
+    }
+
+    #[test]
+    fn cursor_highlight_colors_are_parsed() {
+        let highlight = Value::Map(vec![(Value::from("bg"), Value::from(0xff0000))]);
+        let RedrawEvent::CursorHighlight { colors } = parse_cursor_highlight(&[highlight]) else {
+            panic!("expected a cursor highlight");
+        };
+        let colors = colors.unwrap();
+        assert_eq!(colors.background, Some(unpack_color(0xff0000)));
+        assert_eq!(colors.foreground, None);
+
+        // An undefined group comes as an empty table
+        let undefined = parse_cursor_highlight(&[Value::Array(vec![])]);
+        assert!(matches!(
+            undefined,
+            RedrawEvent::CursorHighlight { colors: None }
+        ));
+    }
+
+    #[test]
+    fn window_backgrounds_are_parsed() {
+        let highlight = Value::Map(vec![(Value::from("bg"), Value::from(0x00ff00))]);
+        assert!(matches!(
+            parse_window_background(&[Value::from(1000), highlight]),
+            Some(RedrawEvent::WindowBackground { window: 1000, background: Some(background) })
+                if background == unpack_color(0x00ff00)
+        ));
+        assert!(matches!(
+            parse_window_background(&[Value::from(1001), Value::Array(vec![])]),
+            Some(RedrawEvent::WindowBackground {
+                window: 1001,
+                background: None
+            })
+        ));
+    }
+
+    #[test]
+    fn cursor_tab_spans_are_parsed() {
+        assert_eq!(
+            parse_cursor_tab_span(&[Value::from(3), Value::from(4)]),
+            Some((3, 4))
+        );
+        assert_eq!(parse_cursor_tab_span(&[]), None);
+        assert_eq!(
+            parse_cursor_tab_span(&[Value::from("3"), Value::from(4)]),
+            None
+        );
+    }
+
+    #[test]
+    fn cwd_changes_are_parsed() {
+        let event = parse_cwd_changed(&[Value::from("/home/user/project")]);
+        assert!(matches!(
+            event,
+            Some(RedrawEvent::CwdChanged { cwd }) if cwd == "/home/user/project"
+        ));
+        assert!(parse_cwd_changed(&[]).is_none());
+        assert!(parse_cwd_changed(&[Value::from(1)]).is_none());
+    }
+}
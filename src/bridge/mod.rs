@@ -2,6 +2,7 @@ mod api_info;
 mod clipboard;
 mod command;
 mod events;
+mod gui_commands;
 mod handler;
 pub mod session;
 mod setup;
@@ -12,7 +13,7 @@ use itertools::Itertools;
 use log::info;
 use nvim_rs::{error::CallError, Neovim, UiAttachOptions, Value};
 use rmpv::Utf8String;
-use std::{io::Error, ops::Add, time::Duration};
+use std::{future::Future, io::Error, ops::Add, time::Duration};
 use tokio::{
     runtime::{Builder, Runtime},
     select,
@@ -27,11 +28,14 @@ use crate::{
 pub use handler::NeovimHandler;
 use session::{NeovimInstance, NeovimSession};
 use setup::{get_api_information, setup_neovide_specific_state};
+use ui_commands::NEOVIM_UNRESPONSIVE;
 
 pub use command::create_nvim_command;
 pub use events::*;
 pub use session::NeovimWriter;
 pub use ui_commands::{send_ui, start_ui_command_handler, ParallelCommand, SerialCommand};
+#[cfg(test)]
+pub use ui_commands::{take_sent_ui_commands, UiCommand};
 
 const NEOVIM_REQUIRED_VERSION: &str = "0.10.0";
 
@@ -138,6 +142,10 @@ async fn run(session: NeovimSession, proxy: EventLoopProxy<UserEvent>) {
         // data.
         select! {
             _ = &mut session.io_handle => {}
+            _ = NEOVIM_UNRESPONSIVE.notified() => {
+                log::info!("Stopping the unresponsive Neovim process");
+                process.kill().await.ok();
+            }
             _ = process.wait() => {
                 log::info!("The Neovim process quit before the IO stream, waiting two seconds");
                 if timeout(Duration::from_millis(2000), session.io_handle)
@@ -149,7 +157,10 @@ async fn run(session: NeovimSession, proxy: EventLoopProxy<UserEvent>) {
             }
         };
     } else {
-        session.io_handle.await.ok();
+        select! {
+            _ = session.io_handle => {}
+            _ = NEOVIM_UNRESPONSIVE.notified() => {}
+        }
     }
     log::info!("Neovim has quit");
     proxy.send_event(UserEvent::NeovimExited).ok();
@@ -172,4 +183,15 @@ impl NeovimRuntime {
         self.runtime.spawn(run(session, event_loop_proxy));
         Ok(())
     }
+
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.runtime.block_on(future)
+    }
+
+    pub fn spawn<F>(&self, future: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.runtime.spawn(future);
+    }
 }
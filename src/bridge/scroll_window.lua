@@ -0,0 +1,15 @@
+-- Scrolls the window dragged by its scrollbar, keeping the cursor in the visible lines the same
+-- way scrolling with the mouse wheel does
+local window, top_line = ...
+
+if not vim.api.nvim_win_is_valid(window) then
+    return
+end
+
+vim.api.nvim_win_call(window, function()
+    local last_line = vim.fn.line("$")
+    top_line = math.min(top_line, last_line)
+    local bottom_line = math.min(top_line + vim.api.nvim_win_get_height(0) - 1, last_line)
+    local cursor_line = math.min(math.max(vim.fn.line("."), top_line), bottom_line)
+    vim.fn.winrestview({ topline = top_line, lnum = cursor_line })
+end)
@@ -1,20 +1,49 @@
-use std::sync::OnceLock;
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        OnceLock,
+    },
+    time::Duration,
+};
 
 use log::trace;
 
 use anyhow::{Context, Result};
 use nvim_rs::{call_args, error::CallError, rpc::model::IntoVal, Neovim, Value};
 use strum::AsRefStr;
-use tokio::sync::mpsc::unbounded_channel;
+use tokio::{
+    sync::{
+        mpsc::{unbounded_channel, UnboundedReceiver},
+        Notify,
+    },
+    time::timeout,
+};
 
 use super::{show_error_message, SETTINGS};
 use crate::{
     bridge::NeovimWriter,
     cmd_line::CmdLineSettings,
     profiling::{tracy_dynamic_zone, tracy_fiber_enter, tracy_fiber_leave},
+    running_tracker::RUNNING_TRACKER,
     LoggingSender,
 };
 
+// How long Neovim has to answer before it's considered stuck when quitting
+const QUIT_RESPONSE_TIMEOUT: Duration = Duration::from_secs(3);
+
+// Notified when the user insists on quitting while Neovim is stuck, the bridge then stops the
+// Neovim process and shuts Neovide down like when Neovim quits by itself
+pub static NEOVIM_UNRESPONSIVE: Notify = Notify::const_new();
+
+// Set when a quit request found Neovim stuck, so that closing the window again forces the quit
+static QUIT_WHILE_UNRESPONSIVE: AtomicBool = AtomicBool::new(false);
+
+// The most keyboard input that's joined into a single input call, in bytes
+const MAX_COALESCED_INPUT: usize = 4096;
+
+// The most text sent by a single paste call, larger pastes are streamed in chunks of this size
+const MAX_PASTE_CHUNK: usize = 64 * 1024;
+
 // Serial commands are any commands which must complete before the next value is sent. This
 // includes keyboard and mouse input which would cause problems if sent out of order.
 //
@@ -41,6 +70,13 @@ pub enum SerialCommand {
         position: (u32, u32),
         modifier_string: String,
     },
+    /// Scrolls the Neovim window so that the one based `top_line` is at the top
+    ScrollWindow {
+        window: u64,
+        top_line: u64,
+    },
+    /// Pastes the text at the cursor, as if it was pasted in a terminal
+    Paste(String),
 }
 
 impl SerialCommand {
@@ -107,6 +143,15 @@ impl SerialCommand {
                 )
                 .await
                 .context("Mouse Drag Failed"),
+            SerialCommand::ScrollWindow { window, top_line } => nvim
+                .exec_lua(
+                    include_str!("scroll_window.lua"),
+                    vec![Value::from(window), Value::from(top_line)],
+                )
+                .await
+                .map(|_| ())
+                .context("Scroll window failed"),
+            SerialCommand::Paste(text) => paste(nvim, &text).await.context("Paste failed"),
         };
 
         if let Err(error) = result {
@@ -125,6 +170,7 @@ pub enum ParallelCommand {
     DisplayAvailableFonts(Vec<String>),
     SetBackground(String),
     ShowError { lines: Vec<String> },
+    ShowDiagnostics(String, Option<String>),
 }
 
 async fn display_available_fonts(
@@ -172,6 +218,32 @@ async fn display_available_fonts(
     Ok(())
 }
 
+/// Echoes the report so that it stays in `:messages`, or writes it to the file when there's a
+/// path.
+async fn show_diagnostics(
+    nvim: &Neovim<NeovimWriter>,
+    report: String,
+    path: Option<String>,
+) -> Result<(), Box<CallError>> {
+    let message = match path {
+        Some(path) => {
+            let path = nvim
+                .call_function("expand", vec![Value::from(path)])
+                .await?;
+            let lines = report.lines().map(Value::from).collect::<Vec<_>>();
+            nvim.call_function("writefile", vec![Value::from(lines), path.clone()])
+                .await?;
+            format!(
+                "Wrote the Neovide diagnostics to {}",
+                path.as_str().unwrap_or_default()
+            )
+        }
+        None => report,
+    };
+    nvim.echo(vec![Value::from(vec![Value::from(message)])], true, vec![])
+        .await
+}
+
 impl ParallelCommand {
     async fn execute(self, nvim: &Neovim<NeovimWriter>) {
         // Don't panic here unless there's absolutely no chance of continuing the program, Instead
@@ -180,6 +252,25 @@ impl ParallelCommand {
         // being sent
         let result = match self {
             ParallelCommand::Quit => {
+                // nvim_get_mode is answered even while Neovim is waiting for input, so if it
+                // doesn't respond, Neovim is busy or stuck. The quit is still sent, since a busy
+                // Neovim handles it once it's done, and it may have unsaved buffers to ask about.
+                // Only a second close while it's still stuck forces the exit.
+                let responsive = timeout(QUIT_RESPONSE_TIMEOUT, nvim.get_mode())
+                    .await
+                    .is_ok();
+                if responsive {
+                    QUIT_WHILE_UNRESPONSIVE.store(false, Ordering::Relaxed);
+                } else if QUIT_WHILE_UNRESPONSIVE.swap(true, Ordering::Relaxed) {
+                    log::error!("Neovim is still not responding, forcing Neovide to quit");
+                    RUNNING_TRACKER.quit_with_code(1, "Neovim is not responding");
+                    NEOVIM_UNRESPONSIVE.notify_one();
+                    return;
+                } else {
+                    log::warn!(
+                        "Neovim is not responding, close the window again to force the quit"
+                    );
+                }
                 // Ignore all errors, since neovim exits immediately before the response is sent.
                 // We could an RPC notify instead of request, but nvim-rs does currently not support it.
                 let _ = nvim
@@ -227,6 +318,9 @@ impl ParallelCommand {
             ParallelCommand::DisplayAvailableFonts(fonts) => display_available_fonts(nvim, fonts)
                 .await
                 .context("DisplayAvailableFonts failed"),
+            ParallelCommand::ShowDiagnostics(report, path) => show_diagnostics(nvim, report, path)
+                .await
+                .context("ShowDiagnostics failed"),
 
             ParallelCommand::ShowError { lines } => {
                 // nvim.err_write(&message).await.ok();
@@ -272,6 +366,29 @@ impl AsRef<str> for UiCommand {
     }
 }
 
+/// Joins the keyboard input that's already queued behind `command` into a single input, so that a
+/// burst of keys doesn't wait for a round trip to Neovim per key when typing faster than it
+/// answers. Returns the command that ended the run too, which has to be executed after it.
+fn coalesce_keyboard_input(
+    command: SerialCommand,
+    serial_rx: &mut UnboundedReceiver<SerialCommand>,
+) -> (SerialCommand, Option<SerialCommand>) {
+    let SerialCommand::Keyboard(mut keys) = command else {
+        return (command, None);
+    };
+    while let Ok(next) = serial_rx.try_recv() {
+        match next {
+            SerialCommand::Keyboard(next_keys)
+                if keys.len() + next_keys.len() <= MAX_COALESCED_INPUT =>
+            {
+                keys.push_str(&next_keys);
+            }
+            next => return (SerialCommand::Keyboard(keys), Some(next)),
+        }
+    }
+    (SerialCommand::Keyboard(keys), None)
+}
+
 static UI_COMMAND_CHANNEL: OnceLock<LoggingSender<UiCommand>> = OnceLock::new();
 
 pub fn start_ui_command_handler(nvim: Neovim<NeovimWriter>) {
@@ -304,12 +421,22 @@ pub fn start_ui_command_handler(nvim: Neovim<NeovimWriter>) {
 
     tokio::spawn(async move {
         tracy_fiber_enter!("Serial command");
+        let mut pending = None;
         loop {
-            tracy_fiber_leave();
-            let res = serial_rx.recv().await;
-            tracy_fiber_enter!("Serial command");
+            let res = match pending.take() {
+                Some(serial_command) => Some(serial_command),
+                None => {
+                    tracy_fiber_leave();
+                    let res = serial_rx.recv().await;
+                    tracy_fiber_enter!("Serial command");
+                    res
+                }
+            };
             match res {
                 Some(serial_command) => {
+                    let (serial_command, next) =
+                        coalesce_keyboard_input(serial_command, &mut serial_rx);
+                    pending = next;
                     tracy_dynamic_zone!(serial_command.as_ref());
                     tracy_fiber_leave();
                     serial_command.execute(&nvim).await;
@@ -322,6 +449,46 @@ pub fn start_ui_command_handler(nvim: Neovim<NeovimWriter>) {
     });
 }
 
+/// Splits the text into the chunks of a streamed paste with their phase, which is -1 when it fits in
+/// a single call. The chunks end after a newline when there's one, so that Neovim gets whole lines.
+fn paste_chunks(mut text: &str) -> Vec<(&str, i64)> {
+    let mut chunks = vec![];
+    while text.len() > MAX_PASTE_CHUNK {
+        let mut end = MAX_PASTE_CHUNK;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        let end = text[..end].rfind('\n').map_or(end, |newline| newline + 1);
+        chunks.push(&text[..end]);
+        text = &text[end..];
+    }
+    chunks.push(text);
+
+    let last = chunks.len() - 1;
+    chunks
+        .into_iter()
+        .enumerate()
+        .map(|(index, chunk)| match index {
+            _ if last == 0 => (chunk, -1),
+            0 => (chunk, 1),
+            _ if index == last => (chunk, 3),
+            _ => (chunk, 2),
+        })
+        .collect()
+}
+
+/// Pastes with `nvim_paste`, like a terminal with bracketed paste, so that Neovim inserts the text
+/// as is, without the auto-indent and mappings of typed text.
+async fn paste(nvim: &Neovim<NeovimWriter>, text: &str) -> Result<(), Box<CallError>> {
+    for (chunk, phase) in paste_chunks(text) {
+        // False means that the user cancelled the paste, and the rest must not be sent
+        if !nvim.paste(chunk, false, phase).await? {
+            break;
+        }
+    }
+    Ok(())
+}
+
 pub fn send_ui<T>(command: T)
 where
     T: Into<UiCommand>,
@@ -332,3 +499,114 @@ where
         .expect("The UI command channel has not been initialized")
         .send(command);
 }
+
+/// Takes the UI commands sent since the last call. The first call starts capturing them instead
+/// of sending them to Neovim, so the tests using this have to be serial.
+#[cfg(test)]
+pub fn take_sent_ui_commands() -> Vec<UiCommand> {
+    use parking_lot::Mutex;
+
+    lazy_static! {
+        static ref SENT_UI_COMMANDS: Mutex<UnboundedReceiver<UiCommand>> = {
+            let (sender, receiver) = unbounded_channel();
+            UI_COMMAND_CHANNEL
+                .set(LoggingSender::attach(sender, "UIComand"))
+                .expect("The UI command channel is already created");
+            Mutex::new(receiver)
+        };
+    }
+
+    let mut sent_ui_commands = SENT_UI_COMMANDS.lock();
+    std::iter::from_fn(|| sent_ui_commands.try_recv().ok()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn drain(mut serial_rx: UnboundedReceiver<SerialCommand>) -> Vec<SerialCommand> {
+        let mut executed = vec![];
+        let mut pending = None;
+        while let Some(command) = pending.take().or_else(|| serial_rx.try_recv().ok()) {
+            let (command, next) = coalesce_keyboard_input(command, &mut serial_rx);
+            executed.push(command);
+            pending = next;
+        }
+        executed
+    }
+
+    fn keys(command: &SerialCommand) -> Option<&str> {
+        match command {
+            SerialCommand::Keyboard(keys) => Some(keys),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn rapid_input_reaches_neovim_in_order() {
+        let (serial_tx, serial_rx) = unbounded_channel();
+        for key in ["a", "b", "<C-x>"] {
+            serial_tx.send(SerialCommand::Keyboard(key.into())).unwrap();
+        }
+        serial_tx
+            .send(SerialCommand::Paste("pasted".into()))
+            .unwrap();
+        for key in ["c", "<Esc>"] {
+            serial_tx.send(SerialCommand::Keyboard(key.into())).unwrap();
+        }
+
+        let executed = drain(serial_rx);
+        assert_eq!(executed.len(), 3);
+        assert_eq!(keys(&executed[0]), Some("ab<C-x>"));
+        assert!(matches!(&executed[1], SerialCommand::Paste(text) if text == "pasted"));
+        assert_eq!(keys(&executed[2]), Some("c<Esc>"));
+    }
+
+    #[test]
+    fn large_pastes_are_streamed_in_whole_lines() {
+        assert_eq!(paste_chunks("one\ntwo\n"), vec![("one\ntwo\n", -1)]);
+
+        let line = format!("{}\n", "x".repeat(999));
+        let text = line.repeat(200);
+        let chunks = paste_chunks(&text);
+        assert_eq!(chunks.len(), 4);
+        assert_eq!(
+            chunks.iter().map(|(_, phase)| *phase).collect::<Vec<_>>(),
+            vec![1, 2, 2, 3]
+        );
+        assert!(chunks
+            .iter()
+            .all(|(chunk, _)| chunk.len() <= MAX_PASTE_CHUNK));
+        assert!(chunks.iter().all(|(chunk, _)| chunk.ends_with('\n')));
+        assert_eq!(
+            chunks.iter().map(|(chunk, _)| *chunk).collect::<String>(),
+            text
+        );
+
+        // Without newlines the chunks still end between characters
+        let text = "é".repeat(MAX_PASTE_CHUNK);
+        let chunks = paste_chunks(&text);
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(
+            chunks.iter().map(|(chunk, _)| *chunk).collect::<String>(),
+            text
+        );
+    }
+
+    #[test]
+    fn coalesced_input_is_limited() {
+        let (serial_tx, serial_rx) = unbounded_channel();
+        let key = "x".repeat(MAX_COALESCED_INPUT / 2 + 1);
+        for _ in 0..3 {
+            serial_tx
+                .send(SerialCommand::Keyboard(key.clone()))
+                .unwrap();
+        }
+
+        let executed = drain(serial_rx);
+        assert_eq!(executed.len(), 3);
+        assert!(executed
+            .iter()
+            .all(|command| keys(command) == Some(key.as_str())));
+    }
+}
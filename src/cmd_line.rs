@@ -1,6 +1,6 @@
-use std::{iter, mem};
+use std::iter;
 
-use crate::{dimensions::Dimensions, frame::Frame, settings::*};
+use crate::{dimensions::Dimensions, frame::Frame, renderer::GraphicsBackend, settings::*};
 
 use anyhow::Result;
 use clap::{
@@ -15,6 +15,11 @@ pub const SRGB_DEFAULT: &str = "1";
 #[cfg(not(target_os = "windows"))]
 pub const SRGB_DEFAULT: &str = "0";
 
+#[cfg(target_os = "macos")]
+pub const SINGLE_INSTANCE_DEFAULT: &str = "1";
+#[cfg(not(target_os = "macos"))]
+pub const SINGLE_INSTANCE_DEFAULT: &str = "0";
+
 fn get_styles() -> Styles {
     styling::Styles::styled()
         .header(styling::AnsiColor::Green.on_default() | styling::Effects::BOLD)
@@ -75,6 +80,16 @@ pub struct CmdLineSettings {
     #[arg(long = "no-fork", action = ArgAction::SetTrue, value_parser = FalseyValueParser::new())]
     _no_fork: bool,
 
+    /// Open the files in an already running Neovide instead of starting a new one. Default on
+    /// macOS.
+    #[arg(long = "single-instance", env = "NEOVIDE_SINGLE_INSTANCE", action = ArgAction::SetTrue, default_value = SINGLE_INSTANCE_DEFAULT, value_parser = FalseyValueParser::new())]
+    pub single_instance: bool,
+
+    /// Always start a new Neovide, even when one is already running. Default on Linux and
+    /// Windows.
+    #[arg(long = "no-single-instance", action = ArgAction::SetTrue, value_parser = FalseyValueParser::new())]
+    _no_single_instance: bool,
+
     /// Render every frame, takes more power and CPU time but possibly helps with frame timing
     /// issues
     #[arg(long = "no-idle", env = "NEOVIDE_IDLE", action = ArgAction::SetFalse, value_parser = FalseyValueParser::new())]
@@ -137,6 +152,20 @@ pub struct CmdLineSettings {
     #[command(flatten)]
     pub geometry: GeometryArgs,
 
+    /// The index of the monitor to open the window on, starting from 0. Falls back to the primary
+    /// monitor when it isn't connected
+    #[arg(long, env = "NEOVIDE_MONITOR")]
+    pub monitor: Option<usize>,
+
+    /// Which graphics backend to render with, falls back to an available one when it isn't
+    #[arg(long, env = "NEOVIDE_BACKEND", default_value_t)]
+    pub backend: GraphicsBackend,
+
+    /// Render on the CPU with a software rasterizer instead of the GPU. Slower, but doesn't depend
+    /// on a working graphics driver
+    #[arg(long = "software-render", env = "NEOVIDE_SOFTWARE_RENDER", action = ArgAction::SetTrue, value_parser = FalseyValueParser::new())]
+    pub software_render: bool,
+
     /// Force opengl on Windows
     #[cfg(target_os = "windows")]
     #[arg(long = "opengl", env = "NEOVIDE_OPENGL", action = ArgAction::SetTrue, value_parser = FalseyValueParser::new())]
@@ -201,6 +230,10 @@ pub fn handle_command_line_arguments(args: Vec<String>) -> Result<()> {
         cmdline.fork = false;
     }
 
+    if cmdline._no_single_instance {
+        cmdline.single_instance = false;
+    }
+
     if cmdline._no_srgb {
         cmdline.srgb = false;
     }
@@ -213,10 +246,7 @@ pub fn handle_command_line_arguments(args: Vec<String>) -> Result<()> {
         .tabs
         .then(|| "-p".to_string())
         .into_iter()
-        .chain(handle_wslpaths(
-            mem::take(&mut cmdline.files_to_open),
-            cmdline.wsl,
-        ))
+        .chain(handle_wslpaths(cmdline.files_to_open.clone(), cmdline.wsl))
         .chain(cmdline.neovim_args)
         .collect();
 
@@ -391,6 +421,15 @@ mod tests {
         assert_eq!(SETTINGS.get::<CmdLineSettings>().frame, Frame::None);
     }
 
+    #[test]
+    fn test_monitor_environment_variable() {
+        let args: Vec<String> = ["neovide"].iter().map(|s| s.to_string()).collect();
+
+        let _env = ScopedEnv::set("NEOVIDE_MONITOR", "1");
+        handle_command_line_arguments(args).expect("Could not parse arguments");
+        assert_eq!(SETTINGS.get::<CmdLineSettings>().monitor, Some(1));
+    }
+
     #[test]
     fn test_neovim_bin_arg() {
         let args: Vec<String> = ["neovide", "--neovim-bin", "foo"]
@@ -44,9 +44,13 @@ pub struct Cursor {
     pub blinkon: Option<u64>,
     pub blinkoff: Option<u64>,
     pub style: Option<Arc<Style>>,
+    /// The colors of the `NeovideCursor` highlight group, which take precedence over the style
+    pub highlight: Option<Colors>,
     pub enabled: bool,
-    pub double_width: bool,
+    pub cell_width: u64,
     pub grid_cell: GridCell,
+    /// Counts the flushes that changed the line under the cursor
+    pub content_revision: u64,
 }
 
 impl Cursor {
@@ -56,30 +60,79 @@ impl Cursor {
             parent_window_id: 0,
             shape: CursorShape::Block,
             style: None,
+            highlight: None,
             cell_percentage: None,
             blinkwait: None,
             blinkon: None,
             blinkoff: None,
             enabled: true,
-            double_width: false,
+            cell_width: 1,
             grid_cell: (" ".to_string(), None),
+            content_revision: 0,
         }
     }
 
+    fn colors(&self) -> impl Iterator<Item = &Colors> {
+        self.highlight
+            .iter()
+            .chain(self.style.as_ref().map(|s| &s.colors))
+    }
+
+    fn highlight_foreground(&self) -> Option<Color4f> {
+        self.colors().find_map(|colors| colors.foreground)
+    }
+
     pub fn foreground(&self, default_colors: &Colors) -> Color4f {
-        self.style
-            .as_ref()
-            .and_then(|s| s.colors.foreground)
+        self.highlight_foreground()
             .unwrap_or_else(|| default_colors.background.unwrap())
     }
 
     pub fn background(&self, default_colors: &Colors) -> Color4f {
-        self.style
-            .as_ref()
-            .and_then(|s| s.colors.background)
+        self.colors()
+            .find_map(|colors| colors.background)
             .unwrap_or_else(|| default_colors.foreground.unwrap())
     }
 
+    /// The foreground and background the cell under the cursor is drawn with, after the reverse
+    /// attribute. The background of a blended cell is mixed with what shows through it.
+    pub fn cell_colors(&self, default_colors: &Colors) -> (Color4f, Color4f) {
+        match &self.grid_cell.1 {
+            Some(style) => (
+                style.foreground(default_colors),
+                blended_background(
+                    style.background(default_colors),
+                    style.blend,
+                    default_colors.background.unwrap(),
+                ),
+            ),
+            None => (
+                default_colors.foreground.unwrap(),
+                default_colors.background.unwrap(),
+            ),
+        }
+    }
+
+    /// The color of the character drawn over the cursor. Without a foreground in the cursor
+    /// highlight, a cell with the reverse attribute has its colors swapped, so the character is
+    /// drawn in the background the cell is drawn with. Unless that's the cursor color itself,
+    /// which would hide the character. Other cells keep the usual cursor foreground.
+    pub fn character_foreground(&self, default_colors: &Colors) -> Color4f {
+        if let Some(foreground) = self.highlight_foreground() {
+            return foreground;
+        }
+        let reversed = self.grid_cell.1.as_ref().is_some_and(|style| style.reverse);
+        if !reversed {
+            return self.foreground(default_colors);
+        }
+
+        let (cell_foreground, cell_background) = self.cell_colors(default_colors);
+        if cell_background == self.background(default_colors) {
+            cell_foreground
+        } else {
+            cell_background
+        }
+    }
+
     pub fn alpha(&self) -> u8 {
         return self
             .style
@@ -113,6 +166,19 @@ impl Cursor {
     }
 }
 
+/// A cell of a floating window with `winblend` or `pumblend` lets what's beneath it show through
+/// its background. That's usually the default background of the grid under the window.
+fn blended_background(background: Color4f, blend: u8, beneath: Color4f) -> Color4f {
+    let t = blend.min(100) as f32 / 100.0;
+    let mix = |from: f32, to: f32| from + (to - from) * t;
+    Color4f::new(
+        mix(background.r, beneath.r),
+        mix(background.g, beneath.g),
+        mix(background.b, beneath.b),
+        mix(background.a, beneath.a),
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -195,6 +261,103 @@ mod tests {
         );
     }
 
+    #[test]
+    fn cursor_highlight_takes_precedence_over_the_style() {
+        let mut cursor = Cursor::new();
+        cursor.style = Some(Arc::new(Style::new(COLORS)));
+        let highlight_background = Color4f::new(0.4, 0.1, 0.1, 1.0);
+        cursor.highlight = Some(Colors::new(None, Some(highlight_background), None));
+
+        assert_eq!(cursor.background(&DEFAULT_COLORS), highlight_background);
+        // The group doesn't set a foreground, so that still comes from the style
+        assert_eq!(
+            cursor.foreground(&DEFAULT_COLORS),
+            COLORS.foreground.unwrap()
+        );
+
+        cursor.highlight = None;
+        assert_eq!(
+            cursor.background(&DEFAULT_COLORS),
+            COLORS.background.unwrap()
+        );
+    }
+
+    #[test]
+    fn blended_cells_show_the_background_beneath() {
+        let mut cursor = Cursor::new();
+        let default_colors = Colors::new(
+            Some(Color4f::new(1.0, 1.0, 1.0, 1.0)),
+            Some(Color4f::new(0.0, 0.0, 0.0, 1.0)),
+            None,
+        );
+        let mut blended = Style::new(Colors::new(
+            None,
+            Some(Color4f::new(0.0, 0.0, 1.0, 1.0)),
+            None,
+        ));
+        blended.blend = 50;
+        cursor.grid_cell = ("a".to_string(), Some(Arc::new(blended.clone())));
+
+        let expected = Color4f::new(0.0, 0.0, 0.5, 1.0);
+        assert_eq!(cursor.cell_colors(&default_colors).1, expected);
+
+        // The character of a reversed cell is cut out of the cursor in the color the cell
+        // actually has on screen
+        blended.reverse = true;
+        cursor.grid_cell = ("a".to_string(), Some(Arc::new(blended)));
+        assert_eq!(
+            cursor.character_foreground(&default_colors),
+            Color4f::new(0.5, 0.5, 0.5, 1.0)
+        );
+    }
+
+    #[test]
+    fn character_foreground_follows_a_reversed_cell() {
+        let mut cursor = Cursor::new();
+        assert_eq!(
+            cursor.character_foreground(&DEFAULT_COLORS),
+            DEFAULT_COLORS.background.unwrap()
+        );
+
+        // The cell is drawn in its foreground color, so that's where the character is cut out
+        let mut reversed = Style::new(COLORS);
+        reversed.reverse = true;
+        cursor.grid_cell = ("a".to_string(), Some(Arc::new(reversed)));
+        assert_eq!(
+            cursor.character_foreground(&DEFAULT_COLORS),
+            COLORS.foreground.unwrap()
+        );
+
+        // A reversed cell in the default colors has the same background as the cursor
+        let mut reversed = Style::new(NONE_COLORS);
+        reversed.reverse = true;
+        cursor.grid_cell = ("a".to_string(), Some(Arc::new(reversed)));
+        assert_eq!(
+            cursor.character_foreground(&DEFAULT_COLORS),
+            DEFAULT_COLORS.background.unwrap()
+        );
+
+        cursor.style = Some(Arc::new(Style::new(COLORS)));
+        assert_eq!(
+            cursor.character_foreground(&DEFAULT_COLORS),
+            COLORS.foreground.unwrap()
+        );
+    }
+
+    #[test]
+    fn character_foreground_ignores_highlighted_cells_that_are_not_reversed() {
+        let mut cursor = Cursor::new();
+        cursor.grid_cell = ("a".to_string(), Some(Arc::new(Style::new(COLORS))));
+        assert_eq!(
+            cursor.character_foreground(&DEFAULT_COLORS),
+            cursor.foreground(&DEFAULT_COLORS)
+        );
+        assert_eq!(
+            cursor.character_foreground(&DEFAULT_COLORS),
+            DEFAULT_COLORS.background.unwrap()
+        );
+    }
+
     #[test]
     fn test_change_mode() {
         let cursor_mode = CursorMode {
@@ -0,0 +1,106 @@
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+/// A value that's written in the back buffer while Neovim sends a frame, while readers only ever
+/// see the front buffer, which holds the last flushed frame. Readers take a snapshot of the front
+/// buffer, so they don't hold the lock while they use it.
+///
+/// The back buffer is cloned on every swap that has changes, so the value should be cheap to
+/// clone, like a `CharacterGrid` whose lines are shared until they are written.
+pub struct DoubleBuffer<T: Clone> {
+    back: T,
+    front: FrontBuffer<T>,
+    changed: bool,
+}
+
+/// A handle to read the front buffer, which can be shared with other threads.
+pub struct FrontBuffer<T>(Arc<RwLock<Arc<T>>>);
+
+impl<T> FrontBuffer<T> {
+    pub fn snapshot(&self) -> Arc<T> {
+        self.0.read().clone()
+    }
+}
+
+impl<T> Clone for FrontBuffer<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T: Clone> DoubleBuffer<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            front: FrontBuffer(Arc::new(RwLock::new(Arc::new(value.clone())))),
+            back: value,
+            changed: false,
+        }
+    }
+
+    pub fn back(&self) -> &T {
+        &self.back
+    }
+
+    pub fn back_mut(&mut self) -> &mut T {
+        self.changed = true;
+        &mut self.back
+    }
+
+    pub fn front(&self) -> &FrontBuffer<T> {
+        &self.front
+    }
+
+    /// Shows the back buffer to the readers. The back buffer keeps its contents, since Neovim
+    /// only sends what changed for the next frame.
+    pub fn swap(&mut self) {
+        if !self.changed {
+            return;
+        }
+        self.changed = false;
+        // Copy before taking the lock, so that readers never wait for it
+        let frame = Arc::new(self.back.clone());
+        *self.front.0.write() = frame;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn readers_only_see_flushed_frames() {
+        let mut buffer = DoubleBuffer::new(vec![0; 8]);
+        buffer.back_mut()[0] = 1;
+        assert_eq!(*buffer.front().snapshot(), vec![0; 8]);
+
+        buffer.swap();
+        assert_eq!(buffer.front().snapshot()[0], 1);
+        // The back buffer keeps the frame for the next changes
+        assert_eq!(buffer.back()[0], 1);
+    }
+
+    #[test]
+    fn reading_during_a_write_sees_a_consistent_frame() {
+        let mut buffer = DoubleBuffer::new(vec![0u32; 256]);
+        let front = buffer.front().clone();
+        let reader = thread::spawn(move || {
+            for _ in 0..1000 {
+                let frame = front.snapshot();
+                // Every frame is written with a single value, so a mix means a partial frame
+                assert!(frame.iter().all(|cell| *cell == frame[0]), "{frame:?}");
+            }
+        });
+
+        for value in 1..1000 {
+            for cell in buffer.back_mut().iter_mut() {
+                *cell = value;
+            }
+            buffer.swap();
+        }
+        reader.join().unwrap();
+        assert_eq!(buffer.front().snapshot()[0], 999);
+    }
+}
@@ -4,6 +4,9 @@ use crate::{editor::DrawCommand, renderer::WindowDrawCommand, window::UserEvent}
 
 use winit::event_loop::EventLoopProxy;
 
+/// Changes to the grids are queued here while Neovim sends them, and only handed to the renderer
+/// as a whole on flush, so it never draws a half updated frame and never has to lock the editor
+/// state.
 pub struct DrawCommandBatcher {
     batch: RefCell<Vec<DrawCommand>>,
 }
@@ -20,6 +23,10 @@ impl DrawCommandBatcher {
     }
 
     pub fn send_batch(&self, proxy: &EventLoopProxy<UserEvent>) {
+        proxy.send_event(self.take_batch().into()).ok();
+    }
+
+    fn take_batch(&self) -> Vec<DrawCommand> {
         let mut batch: Vec<DrawCommand> = self.batch.borrow_mut().split_off(0);
         // Order the draw command batches such that window draw commands are handled first
         // by grid id, and then by the draw command such that they are positioned first.
@@ -34,6 +41,54 @@ impl DrawCommandBatcher {
             }
             _ => 200,
         });
-        proxy.send_event(batch.into()).ok();
+        batch
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::editor::WindowType;
+
+    fn window_command(grid_id: u64, command: WindowDrawCommand) -> DrawCommand {
+        DrawCommand::Window { grid_id, command }
+    }
+
+    fn position(grid_id: u64) -> DrawCommand {
+        window_command(
+            grid_id,
+            WindowDrawCommand::Position {
+                grid_position: (0.0, 0.0),
+                grid_size: (10, 10),
+                anchor_info: None,
+                window_type: WindowType::Editor,
+            },
+        )
+    }
+
+    #[test]
+    fn frame_being_written_is_not_part_of_the_taken_batch() {
+        let batcher = DrawCommandBatcher::new();
+        batcher.queue(window_command(2, WindowDrawCommand::Clear));
+        batcher.queue(DrawCommand::UIReady);
+        batcher.queue(position(2));
+
+        let frame = batcher.take_batch();
+        // Commands queued for the next frame don't show up in the one that was already taken
+        batcher.queue(window_command(2, WindowDrawCommand::Hide));
+        assert_eq!(
+            frame,
+            vec![
+                position(2),
+                window_command(2, WindowDrawCommand::Clear),
+                DrawCommand::UIReady,
+            ]
+        );
+
+        assert_eq!(
+            batcher.take_batch(),
+            vec![window_command(2, WindowDrawCommand::Hide)]
+        );
+        assert!(batcher.take_batch().is_empty());
     }
 }
@@ -1,6 +1,8 @@
 use std::sync::Arc;
 
-use crate::{editor::style::Style, utils::RingBuffer};
+use log::warn;
+
+use crate::{editor::style::Style, settings::MAX_DRAWN_GRID_SIZE, utils::RingBuffer};
 
 pub type GridCell = (String, Option<Arc<Style>>);
 
@@ -14,38 +16,60 @@ macro_rules! default_cell {
 #[derive(Clone)]
 struct GridLine {
     characters: Vec<GridCell>,
+    /// The text continues on the next line, because it was wrapped by Neovim
+    wrapped: bool,
 }
 
 impl GridLine {
     pub fn new(length: usize) -> GridLine {
         GridLine {
             characters: vec![default_cell!(); length],
+            wrapped: false,
         }
     }
 }
 
+#[derive(Clone)]
 pub struct CharacterGrid {
     pub width: usize,
     pub height: usize,
 
     /// The CharacterGrid a ring buffer which improves the performance of scrolling.
-    lines: RingBuffer<GridLine>,
+    /// The lines are shared between the clones of the grid, and only copied when they are
+    /// changed, so that cloning the grid on every flush doesn't copy every cell.
+    lines: RingBuffer<Arc<GridLine>>,
+}
+
+/// Neovim is asked for a grid that fits the window, but it can still send a bigger one, like with
+/// `:set columns=100000`. The cells past the cap are dropped, instead of allocating all of them.
+fn capped_size((width, height): (usize, usize)) -> (usize, usize) {
+    let max_width = MAX_DRAWN_GRID_SIZE.width as usize;
+    let max_height = MAX_DRAWN_GRID_SIZE.height as usize;
+    if width > max_width || height > max_height {
+        warn!("Grid of {width}x{height} is larger than the maximum of {max_width}x{max_height}");
+    }
+    (width.min(max_width), height.min(max_height))
 }
 
 impl CharacterGrid {
-    pub fn new((width, height): (usize, usize)) -> CharacterGrid {
+    pub fn new(size: (usize, usize)) -> CharacterGrid {
+        let (width, height) = capped_size(size);
         CharacterGrid {
             width,
             height,
-            lines: RingBuffer::new(height, GridLine::new(width)),
+            lines: RingBuffer::new(height, Arc::new(GridLine::new(width))),
         }
     }
 
-    pub fn resize(&mut self, (width, height): (usize, usize)) {
-        self.lines.resize(height, GridLine::new(width));
+    pub fn resize(&mut self, size: (usize, usize)) {
+        let (width, height) = capped_size(size);
+        self.lines.resize(height, Arc::new(GridLine::new(width)));
 
         for line in &mut self.lines {
+            let line = Arc::make_mut(line);
             line.characters.resize(width, default_cell!());
+            // The lines are rewrapped for the new width, and sent again by Neovim
+            line.wrapped = false;
         }
 
         self.width = width;
@@ -53,7 +77,10 @@ impl CharacterGrid {
     }
 
     pub fn clear(&mut self) {
-        self.set_all_characters(default_cell!());
+        let empty_line = Arc::new(GridLine::new(self.width));
+        for line in &mut self.lines {
+            *line = empty_line.clone();
+        }
     }
 
     pub fn get_cell(&self, x: usize, y: usize) -> Option<&GridCell> {
@@ -61,17 +88,27 @@ impl CharacterGrid {
     }
 
     pub fn get_cell_mut(&mut self, x: usize, y: usize) -> Option<&mut GridCell> {
-        self.lines[y].characters.get_mut(x)
+        Arc::make_mut(&mut self.lines[y]).characters.get_mut(x)
     }
 
     pub fn set_all_characters(&mut self, value: GridCell) {
         for line in &mut self.lines {
-            for ch in &mut line.characters {
+            for ch in &mut Arc::make_mut(line).characters {
                 *ch = value.clone()
             }
         }
     }
 
+    pub fn set_line_wrapped(&mut self, row_index: usize, wrapped: bool) {
+        if row_index < self.height && self.lines[row_index].wrapped != wrapped {
+            Arc::make_mut(&mut self.lines[row_index]).wrapped = wrapped;
+        }
+    }
+
+    pub fn is_line_wrapped(&self, row_index: usize) -> bool {
+        row_index < self.height && self.lines[row_index].wrapped
+    }
+
     pub fn row(&self, row_index: usize) -> Option<&[GridCell]> {
         if row_index < self.height {
             Some(&self.lines[row_index].characters[..])
@@ -80,6 +117,39 @@ impl CharacterGrid {
         }
     }
 
+    /// Whether the row has the same contents as in the other grid. Rows that weren't changed
+    /// since the grid was cloned are still shared, and don't need to be compared cell by cell.
+    pub fn row_eq(&self, other: &CharacterGrid, row_index: usize) -> bool {
+        match (row_index < self.height, row_index < other.height) {
+            (true, true) => {
+                let (line, other_line) = (&self.lines[row_index], &other.lines[row_index]);
+                Arc::ptr_eq(line, other_line) || line.characters == other_line.characters
+            }
+            (in_self, in_other) => !in_self && !in_other,
+        }
+    }
+
+    /// The text of the grid, with the trailing whitespace of each line removed. Lines wrapped by
+    /// Neovim are joined back together.
+    pub fn text(&self) -> String {
+        let mut text = String::new();
+        for row_index in 0..self.height {
+            let row: String = self.lines[row_index]
+                .characters
+                .iter()
+                .map(|(character, _)| character.as_str())
+                .collect();
+            if self.is_line_wrapped(row_index) {
+                text.push_str(&row);
+            } else {
+                text.push_str(row.trim_end());
+                text.push('\n');
+            }
+        }
+        text.truncate(text.trim_end_matches('\n').len());
+        text
+    }
+
     /// Scroll the region defined by top, bottom, left, and right by rows and columns.
     /// More details found here: https://neovim.io/doc/user/ui.html#ui-linegrid
     /// Returns true if it's a pure up/down scroll
@@ -92,6 +162,8 @@ impl CharacterGrid {
         rows: isize,
         cols: isize,
     ) -> bool {
+        // The region can go past a capped grid
+        let (bottom, right) = (bottom.min(self.height), right.min(self.width));
         if top == 0 && bottom == self.height && left == 0 && right == self.width && cols == 0 {
             // Pure up/down scrolling is optimized, and furthermore does not destroy the region
             // that has been scrolled out
@@ -105,20 +177,27 @@ impl CharacterGrid {
             top_to_bottom = (top as isize + rows) as usize..bottom;
             &mut top_to_bottom
         } else {
-            bottom_to_top = (top..(bottom as isize + rows) as usize).rev();
+            bottom_to_top = (top..(bottom as isize + rows).max(top as isize) as usize).rev();
             &mut bottom_to_top
         };
 
+        // Only whole lines take their wrapping along
+        let full_lines = left == 0 && right == self.width && cols == 0;
         for y in y_iter {
             let dest_y = y as isize - rows;
             let mut cols_left;
             let mut cols_right;
             if dest_y >= 0 && dest_y < self.height as isize {
+                if full_lines {
+                    let wrapped = self.is_line_wrapped(y);
+                    self.set_line_wrapped(dest_y as usize, wrapped);
+                }
+
                 let x_iter: &mut dyn Iterator<Item = usize> = if cols > 0 {
                     cols_left = (left as isize + cols) as usize..right;
                     &mut cols_left
                 } else {
-                    cols_right = (left..(right as isize + cols) as usize).rev();
+                    cols_right = (left..(right as isize + cols).max(left as isize) as usize).rev();
                     &mut cols_right
                 };
 
@@ -229,7 +308,7 @@ mod tests {
         let context = Context::new();
         let mut character_grid = CharacterGrid::new(context.size);
 
-        character_grid.lines[context.y].characters[context.x] = (
+        Arc::make_mut(&mut character_grid.lines[context.y]).characters[context.x] = (
             "foo".to_string(),
             Some(Arc::new(Style::new(context.none_colors.clone()))),
         );
@@ -250,7 +329,7 @@ mod tests {
         let context = Context::new();
         let mut character_grid = CharacterGrid::new(context.size);
 
-        character_grid.lines[context.y].characters[context.x] = (
+        Arc::make_mut(&mut character_grid.lines[context.y]).characters[context.x] = (
             "foo".to_string(),
             Some(Arc::new(Style::new(context.none_colors.clone()))),
         );
@@ -305,6 +384,27 @@ mod tests {
         assert_all_cells_equal_to(&context, &character_grid, &default_cell!());
     }
 
+    #[test]
+    fn huge_grids_are_capped() {
+        let size = (1_000_000, 1_000_000);
+        let mut character_grid = CharacterGrid::new(size);
+        let max_size = (
+            MAX_DRAWN_GRID_SIZE.width as usize,
+            MAX_DRAWN_GRID_SIZE.height as usize,
+        );
+        assert_eq!((character_grid.width, character_grid.height), max_size);
+        assert_eq!(character_grid.lines.len(), max_size.1);
+
+        character_grid.resize((10, 10));
+        character_grid.resize(size);
+        assert_eq!((character_grid.width, character_grid.height), max_size);
+        assert!(character_grid.row(max_size.1).is_none());
+
+        // Cells and scrolls past the cap are ignored
+        assert!(character_grid.get_cell_mut(max_size.0, 0).is_none());
+        character_grid.scroll_region(0, size.1, 1, size.0, -(max_size.1 as isize + 10), 0);
+    }
+
     #[test]
     fn resize_clears_and_resizes_grid() {
         let context = Context::new();
@@ -473,4 +573,51 @@ mod tests {
         set_grid_line_to_chars(&mut grid, 2, "3");
         set_grid_line_to_chars(&mut grid, 3, "4");
     }
+
+    #[test]
+    fn wrapping_moves_with_scrolled_lines() {
+        let mut grid = create_initialized_grid(["1", "2", "3", "4"].as_ref());
+        grid.set_line_wrapped(2, true);
+
+        grid.scroll_region(0, 3, 0, 1, 1, 0);
+        assert!(grid.is_line_wrapped(1));
+        assert!(!grid.is_line_wrapped(0));
+
+        grid.clear();
+        assert!(!grid.is_line_wrapped(1));
+        // Out of bounds lines are never wrapped
+        assert!(!grid.is_line_wrapped(4));
+    }
+
+    #[test]
+    fn text_strips_trailing_whitespace_and_joins_wrapped_lines() {
+        fn set_row(grid: &mut CharacterGrid, row: usize, text: &str) {
+            for (column, character) in text.chars().enumerate() {
+                *grid.get_cell_mut(column, row).unwrap() = (character.to_string(), None);
+            }
+        }
+
+        let mut grid = CharacterGrid::new((4, 4));
+        set_row(&mut grid, 0, "ab  ");
+        set_row(&mut grid, 1, "long");
+        set_row(&mut grid, 2, "er\t ");
+        grid.set_line_wrapped(1, true);
+
+        assert_eq!(grid.text(), "ab\nlonger");
+    }
+
+    #[test]
+    fn clones_only_copy_the_changed_lines() {
+        let mut grid = create_initialized_grid(["ab", "cd", "ef"].as_ref());
+        let flushed = grid.clone();
+        *grid.get_cell_mut(0, 1).unwrap() = ("x".to_string(), None);
+
+        assert!(Arc::ptr_eq(&grid.lines[0], &flushed.lines[0]));
+        assert!(!Arc::ptr_eq(&grid.lines[1], &flushed.lines[1]));
+        assert_eq!(flushed.get_cell(0, 1).unwrap().0, "c");
+
+        assert!(grid.row_eq(&flushed, 0));
+        assert!(!grid.row_eq(&flushed, 1));
+        assert!(grid.row_eq(&flushed, 3));
+    }
 }
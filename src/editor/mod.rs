@@ -1,10 +1,17 @@
 mod cursor;
+mod double_buffer;
 mod draw_command_batcher;
 mod grid;
 mod style;
 mod window;
 
-use std::{collections::HashMap, rc::Rc, sync::Arc, thread};
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+    rc::Rc,
+    sync::Arc,
+    thread,
+};
 use tokio::sync::mpsc::unbounded_channel;
 
 use log::{error, trace, warn};
@@ -19,8 +26,9 @@ use skia_safe::Color4f;
 
 use crate::{
     bridge::{GuiOption, NeovimHandler, RedrawEvent, WindowAnchor},
+    clipboard,
     profiling::{tracy_named_frame, tracy_zone},
-    renderer::{DrawCommand, WindowDrawCommand},
+    renderer::{DrawCommand, ImagePlacement, WindowDrawCommand},
     window::{UserEvent, WindowCommand},
 };
 
@@ -92,8 +100,18 @@ pub struct Editor {
     pub draw_command_batcher: Rc<DrawCommandBatcher>,
     pub current_mode_index: Option<u64>,
     pub ui_ready: bool,
+    pub mouse_enabled: bool,
+    /// The grid of each Neovim window handle, learned from the viewport events
+    window_grids: HashMap<u64, u64>,
+    /// The background of the windows that don't use the default `Normal` highlight, by handle
+    window_backgrounds: HashMap<u64, Color4f>,
+    /// The title set by Neovim, empty unless `title` is set
+    title: String,
+    /// The current directory of Neovim
+    cwd: Option<String>,
     event_loop_proxy: EventLoopProxy<UserEvent>,
     composition_order: u64,
+    changed_lines: LineChanges,
 }
 
 impl Editor {
@@ -106,21 +124,46 @@ impl Editor {
             draw_command_batcher: Rc::new(DrawCommandBatcher::new()),
             current_mode_index: None,
             ui_ready: false,
+            mouse_enabled: true,
+            window_grids: HashMap::new(),
+            window_backgrounds: HashMap::new(),
+            title: String::new(),
+            cwd: None,
             event_loop_proxy,
             composition_order: 0,
+            changed_lines: LineChanges::default(),
         }
     }
 
     pub fn handle_redraw_event(&mut self, event: RedrawEvent) {
         match event {
-            RedrawEvent::SetTitle { mut title } => {
+            RedrawEvent::SetTitle { title } => {
                 tracy_zone!("EditorSetTitle");
-                if title.is_empty() {
-                    title = "Neovide".to_string()
-                }
-                let _ = self
-                    .event_loop_proxy
-                    .send_event(WindowCommand::TitleChanged(title).into());
+                self.title = title;
+                self.send_title();
+            }
+            RedrawEvent::SetIcon { icon } => {
+                // Winit has no way to set the icon name of a window on any platform, only its
+                // icon image
+                trace!("Icon name ignored: {icon}");
+            }
+            RedrawEvent::CursorHighlight { colors } => {
+                tracy_zone!("EditorCursorHighlight");
+                self.cursor.highlight = colors;
+                self.send_cursor_info();
+            }
+            RedrawEvent::WindowBackground { window, background } => {
+                tracy_zone!("EditorWindowBackground");
+                match background {
+                    Some(background) => self.window_backgrounds.insert(window, background),
+                    None => self.window_backgrounds.remove(&window),
+                };
+                self.queue_window_command(window, WindowDrawCommand::Background(background));
+            }
+            RedrawEvent::CwdChanged { cwd } => {
+                tracy_zone!("EditorCwdChanged");
+                self.cwd = Some(cwd);
+                self.send_title();
             }
             RedrawEvent::ModeInfoSet { cursor_modes } => {
                 tracy_zone!("EditorModeInfoSet");
@@ -148,15 +191,11 @@ impl Editor {
             }
             RedrawEvent::MouseOn => {
                 tracy_zone!("EditorMouseOn");
-                let _ = self
-                    .event_loop_proxy
-                    .send_event(WindowCommand::SetMouseEnabled(true).into());
+                self.set_mouse_enabled(true);
             }
             RedrawEvent::MouseOff => {
                 tracy_zone!("EditorMouseOff");
-                let _ = self
-                    .event_loop_proxy
-                    .send_event(WindowCommand::SetMouseEnabled(false).into());
+                self.set_mouse_enabled(false);
             }
             RedrawEvent::BusyStart => {
                 tracy_zone!("EditorBusyStart");
@@ -168,11 +207,27 @@ impl Editor {
                 trace!("Cursor on");
                 self.cursor.enabled = true;
             }
+            RedrawEvent::Bell => {
+                tracy_zone!("EditorBell");
+                let _ = self
+                    .event_loop_proxy
+                    .send_event(WindowCommand::Bell { visual: false }.into());
+            }
+            RedrawEvent::VisualBell => {
+                tracy_zone!("EditorVisualBell");
+                let _ = self
+                    .event_loop_proxy
+                    .send_event(WindowCommand::Bell { visual: true }.into());
+            }
             RedrawEvent::Flush => {
                 tracy_zone!("EditorFlush");
                 trace!("Image flushed");
                 tracy_named_frame!("neovim draw command flush");
+                self.mark_cursor_line_changes();
                 self.send_cursor_info();
+                for window in self.windows.values_mut() {
+                    window.flush_grid();
+                }
                 {
                     trace!("send_batch");
                     self.draw_command_batcher.send_batch(&self.event_loop_proxy);
@@ -192,8 +247,9 @@ impl Editor {
 
                 self.draw_command_batcher
                     .queue(DrawCommand::DefaultStyleChanged(Style::new(colors)));
+                // The redrawn grid is sent together with the rest of the frame on flush, so the
+                // renderer doesn't show it in a half updated state
                 self.redraw_screen();
-                self.draw_command_batcher.send_batch(&self.event_loop_proxy);
             }
             RedrawEvent::HighlightAttributesDefine { id, style } => {
                 tracy_zone!("EditorHighlightAttributesDefine");
@@ -220,14 +276,16 @@ impl Editor {
                 row,
                 column_start,
                 cells,
+                wrap,
             } => {
                 tracy_zone!("EditorGridLine");
                 self.set_ui_ready();
                 let defined_styles = &self.defined_styles;
                 let window = self.windows.get_mut(&grid);
                 if let Some(window) = window {
-                    window.draw_grid_line(row, column_start, cells, defined_styles);
+                    window.draw_grid_line(row, column_start, cells, wrap, defined_styles);
                 }
+                self.changed_lines.insert(grid, row);
             }
             RedrawEvent::Clear { grid } => {
                 tracy_zone!("EditorClear");
@@ -254,6 +312,7 @@ impl Editor {
                 if let Some(window) = window {
                     window.scroll_region(top, bottom, left, right, rows, columns);
                 }
+                self.changed_lines.insert_rows(grid, top..bottom);
             }
             RedrawEvent::WindowPosition {
                 grid,
@@ -311,16 +370,58 @@ impl Editor {
             }
             RedrawEvent::WindowViewport {
                 grid,
-                // Don't send viewport events if they don't have a scroll delta
-                scroll_delta: Some(scroll_delta),
-                ..
+                window,
+                top_line,
+                bottom_line,
+                current_line,
+                current_column,
+                line_count,
+                scroll_delta,
             } => {
                 tracy_zone!("EditorWindowViewport");
-                self.set_ui_ready();
-                self.draw_command_batcher.queue(DrawCommand::Window {
-                    grid_id: grid,
-                    command: WindowDrawCommand::Viewport { scroll_delta },
-                });
+                if self.window_grids.insert(window, grid) != Some(grid) {
+                    // The background can be known before the grid of the window is
+                    if let Some(&background) = self.window_backgrounds.get(&window) {
+                        self.queue_window_command(
+                            window,
+                            WindowDrawCommand::Background(Some(background)),
+                        );
+                    }
+                }
+                if let Some(grid_window) = self.windows.get_mut(&grid) {
+                    grid_window.set_viewport(
+                        window,
+                        Viewport {
+                            top_line,
+                            bottom_line,
+                            current_line,
+                            current_column,
+                            line_count,
+                        },
+                    );
+                }
+                // Don't send viewport events if they don't have a scroll delta
+                if let Some(scroll_delta) = scroll_delta {
+                    self.set_ui_ready();
+                    self.draw_command_batcher.queue(DrawCommand::Window {
+                        grid_id: grid,
+                        command: WindowDrawCommand::Viewport { scroll_delta },
+                    });
+                }
+            }
+            RedrawEvent::WindowHorizontalScroll {
+                window,
+                columns,
+                text_offset,
+            } => {
+                tracy_zone!("EditorWindowHorizontalScroll");
+                self.queue_window_command(
+                    window,
+                    WindowDrawCommand::HorizontalScroll {
+                        columns,
+                        text_offset,
+                    },
+                );
             }
             RedrawEvent::WindowViewportMargins {
                 grid,
@@ -340,6 +441,33 @@ impl Editor {
                     },
                 });
             }
+            RedrawEvent::ImageSet {
+                window,
+                id,
+                row,
+                column,
+                width,
+                height,
+                data,
+            } => {
+                tracy_zone!("EditorImageSet");
+                let placement = ImagePlacement {
+                    id,
+                    row,
+                    column,
+                    width,
+                    height,
+                };
+                self.queue_window_command(window, WindowDrawCommand::SetImage { placement, data });
+            }
+            RedrawEvent::ImageRemove { window, id } => {
+                tracy_zone!("EditorImageRemove");
+                self.queue_window_command(window, WindowDrawCommand::RemoveImage { id });
+            }
+            RedrawEvent::CopyVisibleText => {
+                tracy_zone!("EditorCopyVisibleText");
+                self.copy_visible_text();
+            }
             // Interpreting suspend as a window minimize request
             RedrawEvent::Suspend => {
                 let _ = self
@@ -350,7 +478,41 @@ impl Editor {
         };
     }
 
+    fn send_title(&self) {
+        let home = dirs::home_dir();
+        let title = window_title(&self.title, self.cwd.as_deref(), home.as_deref());
+        let _ = self
+            .event_loop_proxy
+            .send_event(WindowCommand::TitleChanged(title).into());
+    }
+
+    /// Queues a draw command for the grid of a Neovim window handle, which is only known with
+    /// multigrid.
+    fn queue_window_command(&self, window: u64, command: WindowDrawCommand) {
+        if let Some(&grid) = self.window_grids.get(&window) {
+            self.draw_command_batcher.queue(DrawCommand::Window {
+                grid_id: grid,
+                command,
+            });
+        }
+    }
+
+    /// Copies the text of the window with the cursor, which is the whole screen without multigrid.
+    fn copy_visible_text(&self) {
+        let Some(window) = self.windows.get(&self.cursor.parent_window_id) else {
+            return;
+        };
+        if let Err(error) = clipboard::set_contents(window.get_text(), "+") {
+            warn!("Could not copy the visible text: {error}");
+        }
+    }
+
     fn close_window(&mut self, grid: u64) {
+        let window_grids = &self.window_grids;
+        self.window_backgrounds
+            .retain(|window, _| window_grids.get(window) != Some(&grid));
+        self.window_grids
+            .retain(|_, window_grid| *window_grid != grid);
         if let Some(window) = self.windows.remove(&grid) {
             window.close();
             self.draw_command_batcher
@@ -581,15 +743,25 @@ impl Editor {
         self.cursor.grid_position = (grid_left, grid_top);
     }
 
+    /// An edit under a cursor that stays in place changes the cursor too, so that the blinking
+    /// starts over and the cursor is visible right after typing.
+    fn mark_cursor_line_changes(&mut self) {
+        let (_, row) = self.cursor.grid_position;
+        let grid = self.cursor.parent_window_id;
+        if self.changed_lines.take_changed(&self.windows, grid, row) {
+            self.cursor.content_revision = self.cursor.content_revision.wrapping_add(1);
+        }
+    }
+
     fn send_cursor_info(&mut self) {
         tracy_zone!("send_cursor_info");
         let (grid_left, grid_top) = self.cursor.grid_position;
         if let Some(window) = self.windows.get(&self.cursor.parent_window_id) {
-            let (character, style, double_width) = window.get_cursor_grid_cell(grid_left, grid_top);
+            let (character, style, cell_width) = window.get_cursor_grid_cell(grid_left, grid_top);
             self.cursor.grid_cell = (character, style);
-            self.cursor.double_width = double_width;
+            self.cursor.cell_width = cell_width;
         } else {
-            self.cursor.double_width = false;
+            self.cursor.cell_width = 1;
             self.cursor.grid_cell = (" ".to_string(), None);
         }
         self.draw_command_batcher
@@ -612,6 +784,12 @@ impl Editor {
                     self.redraw_screen();
                 }
             }
+            GuiOption::GuiFontWide(guifontwide) => {
+                self.draw_command_batcher
+                    .queue(DrawCommand::WideFontChanged(guifontwide));
+
+                self.redraw_screen();
+            }
             GuiOption::LineSpace(linespace) => {
                 self.draw_command_batcher
                     .queue(DrawCommand::LineSpaceChanged(linespace as f32));
@@ -628,6 +806,15 @@ impl Editor {
         }
     }
 
+    fn set_mouse_enabled(&mut self, mouse_enabled: bool) {
+        if self.mouse_enabled != mouse_enabled {
+            self.mouse_enabled = mouse_enabled;
+            let _ = self
+                .event_loop_proxy
+                .send_event(WindowCommand::SetMouseEnabled(mouse_enabled).into());
+        }
+    }
+
     fn set_ui_ready(&mut self) {
         if !self.ui_ready {
             self.ui_ready = true;
@@ -666,3 +853,118 @@ fn window_theme_for_background(background_color: Option<Color4f>) -> Option<Them
         _ => Some(Theme::Dark),
     }
 }
+
+/// The grid and row of the lines drawn since the last flush. Neovim often draws a line again with
+/// the same content, like the whole screen after a scroll, so only the lines that don't match the
+/// last flushed frame count as changed.
+#[derive(Default)]
+struct LineChanges {
+    lines: HashSet<(u64, u64)>,
+}
+
+impl LineChanges {
+    fn insert(&mut self, grid: u64, row: u64) {
+        self.lines.insert((grid, row));
+    }
+
+    fn insert_rows(&mut self, grid: u64, rows: std::ops::Range<u64>) {
+        self.lines.extend(rows.map(|row| (grid, row)));
+    }
+
+    /// Whether the line changed since the last flush, which has to be asked before the grids are
+    /// flushed. It starts over for the next flush.
+    fn take_changed(&mut self, windows: &HashMap<u64, Window>, grid: u64, row: u64) -> bool {
+        let drawn = self.lines.contains(&(grid, row));
+        self.lines.clear();
+        drawn
+            && windows
+                .get(&grid)
+                .is_some_and(|window| window.row_changed_since_flush(row))
+    }
+}
+
+/// The title set by Neovim, or the current directory when Neovim doesn't set one, with the home
+/// directory shortened to `~`.
+fn window_title(title: &str, cwd: Option<&str>, home: Option<&Path>) -> String {
+    if !title.is_empty() {
+        return title.to_string();
+    }
+    let Some(cwd) = cwd else {
+        return "Neovide".to_string();
+    };
+
+    let cwd_path = Path::new(cwd);
+    let cwd = match home.and_then(|home| cwd_path.strip_prefix(home).ok()) {
+        Some(relative) if relative.as_os_str().is_empty() => "~".to_string(),
+        Some(relative) => format!("~/{}", relative.display()),
+        None => cwd.to_string(),
+    };
+    format!("{cwd} - Neovide")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bridge::GridLineCell;
+
+    fn cells(text: &str) -> Vec<GridLineCell> {
+        text.chars()
+            .map(|character| GridLineCell {
+                text: character.to_string(),
+                highlight_id: None,
+                repeat: None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn only_new_content_on_the_cursor_line_counts_as_changed() {
+        let mut windows = HashMap::new();
+        windows.insert(
+            2,
+            Window::new(
+                2,
+                WindowType::Editor,
+                None,
+                (0.0, 0.0),
+                (4, 2),
+                Rc::new(DrawCommandBatcher::new()),
+            ),
+        );
+        let mut changes = LineChanges::default();
+        // Draws a line and flushes with the cursor on the first row, like the editor does
+        let mut draw_and_flush = |row: u64, text: &str| {
+            let window = windows.get_mut(&2).unwrap();
+            window.draw_grid_line(row, 0, cells(text), false, &HashMap::new());
+            changes.insert(2, row);
+            let changed = changes.take_changed(&windows, 2, 0);
+            windows.get_mut(&2).unwrap().flush_grid();
+            changed
+        };
+
+        assert!(draw_and_flush(0, "ab"));
+        // Neovim redraws lines without changing them, like after a scroll
+        assert!(!draw_and_flush(0, "ab"));
+        assert!(draw_and_flush(0, "ac"));
+        assert!(!draw_and_flush(1, "xy"));
+    }
+
+    #[test]
+    fn title_shows_the_current_directory() {
+        let home = Some(Path::new("/home/user"));
+
+        assert_eq!(window_title("", None, home), "Neovide");
+        assert_eq!(
+            window_title("", Some("/home/user/project"), home),
+            "~/project - Neovide"
+        );
+        assert_eq!(window_title("", Some("/home/user"), home), "~ - Neovide");
+        assert_eq!(window_title("", Some("/tmp"), home), "/tmp - Neovide");
+
+        // Titles set with the `title` option take precedence
+        assert_eq!(
+            window_title("main.rs - NVIM", Some("/home/user/project"), home),
+            "main.rs - NVIM"
+        );
+    }
+}
@@ -5,8 +5,11 @@ use unicode_segmentation::UnicodeSegmentation;
 
 use crate::{
     bridge::GridLineCell,
-    editor::{grid::CharacterGrid, style::Style, AnchorInfo, DrawCommand, DrawCommandBatcher},
-    renderer::{LineFragment, WindowDrawCommand},
+    editor::{
+        double_buffer::DoubleBuffer, grid::CharacterGrid, style::Style, AnchorInfo, DrawCommand,
+        DrawCommandBatcher,
+    },
+    renderer::{LineFragment, ScrollbarPosition, WindowDrawCommand},
 };
 
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -15,13 +18,26 @@ pub enum WindowType {
     Message { scrolled: bool },
 }
 
+/// The part of the buffer shown in a window, as reported by `win_viewport` with multigrid. The
+/// lines are zero based, and the bottom line is exclusive.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Viewport {
+    pub top_line: f64,
+    pub bottom_line: f64,
+    pub current_line: f64,
+    pub current_column: f64,
+    /// Unknown for older Neovim versions
+    pub line_count: Option<f64>,
+}
+
 pub struct Window {
     grid_id: u64,
-    grid: CharacterGrid,
+    grid: DoubleBuffer<CharacterGrid>,
     pub window_type: WindowType,
 
     pub anchor_info: Option<AnchorInfo>,
     grid_position: (f64, f64),
+    viewport: Option<Viewport>,
 
     draw_command_batcher: Rc<DrawCommandBatcher>,
 }
@@ -37,10 +53,14 @@ impl Window {
     ) -> Window {
         let window = Window {
             grid_id,
-            grid: CharacterGrid::new((grid_size.0 as usize, grid_size.1 as usize)),
+            grid: DoubleBuffer::new(CharacterGrid::new((
+                grid_size.0 as usize,
+                grid_size.1 as usize,
+            ))),
             window_type,
             anchor_info,
             grid_position,
+            viewport: None,
             draw_command_batcher,
         };
         window.send_updated_position();
@@ -57,39 +77,79 @@ impl Window {
     fn send_updated_position(&self) {
         self.send_command(WindowDrawCommand::Position {
             grid_position: self.grid_position,
-            grid_size: (self.grid.width as u64, self.grid.height as u64),
+            grid_size: (
+                self.grid.back().width as u64,
+                self.grid.back().height as u64,
+            ),
             anchor_info: self.anchor_info.clone(),
             window_type: self.window_type,
         });
     }
 
+    /// Returns the cell under the cursor, along with the number of columns it covers. Wide
+    /// characters are followed by empty continuation cells, while expanded tabs are just regular
+    /// cells, so the cursor keeps the width of the single cell it's on.
     pub fn get_cursor_grid_cell(
         &self,
         window_left: u64,
         window_top: u64,
-    ) -> (String, Option<Arc<Style>>, bool) {
+    ) -> (String, Option<Arc<Style>>, u64) {
         let grid_cell = self
             .grid
+            .back()
             .get_cell(window_left as usize, window_top as usize)
             .map_or((" ".to_string(), None), |(character, style)| {
                 (character.clone(), style.clone())
             });
 
-        let double_width = self
-            .grid
-            .get_cell(window_left as usize + 1, window_top as usize)
-            .map(|(character, _)| character.is_empty())
-            .unwrap_or_default();
+        let continuation_cells = (window_left as usize + 1..self.grid.back().width)
+            .map_while(|x| self.grid.back().get_cell(x, window_top as usize))
+            .take_while(|(character, _)| character.is_empty())
+            .count();
 
-        (grid_cell.0, grid_cell.1, double_width)
+        (grid_cell.0, grid_cell.1, 1 + continuation_cells as u64)
     }
 
     pub fn get_width(&self) -> u64 {
-        self.grid.width as u64
+        self.grid.back().width as u64
     }
 
     pub fn get_height(&self) -> u64 {
-        self.grid.height as u64
+        self.grid.back().height as u64
+    }
+
+    /// The text of the last flushed frame, so that it's never copied half updated.
+    pub fn get_text(&self) -> String {
+        self.grid.front().snapshot().text()
+    }
+
+    /// Whether the row is different from the last flushed frame.
+    pub fn row_changed_since_flush(&self, row: u64) -> bool {
+        let row = row as usize;
+        !self.grid.back().row_eq(&self.grid.front().snapshot(), row)
+    }
+
+    /// Makes the changes to the grid since the last flush visible to its readers.
+    pub fn flush_grid(&mut self) {
+        self.grid.swap();
+    }
+
+    #[allow(unused)]
+    pub fn viewport(&self) -> Option<&Viewport> {
+        self.viewport.as_ref()
+    }
+
+    /// Stores the viewport of the Neovim window `window` shown in this grid, and updates its
+    /// scrollbar.
+    pub fn set_viewport(&mut self, window: u64, viewport: Viewport) {
+        let scrollbar = viewport.line_count.map(|line_count| ScrollbarPosition {
+            window,
+            top_line: viewport.top_line,
+            bottom_line: viewport.bottom_line,
+            line_count,
+        });
+        self.send_command(WindowDrawCommand::Scrollbar(scrollbar));
+        self.viewport = Some(viewport);
     }
 
     pub fn get_grid_position(&self) -> (f64, f64) {
@@ -103,6 +163,7 @@ impl Window {
         grid_position: (f64, f64),
     ) {
         self.grid
+            .back_mut()
             .resize((grid_size.0 as usize, grid_size.1 as usize));
         self.anchor_info = anchor_info;
         self.grid_position = grid_position;
@@ -110,7 +171,9 @@ impl Window {
     }
 
     pub fn resize(&mut self, new_size: (u64, u64)) {
-        self.grid.resize((new_size.0 as usize, new_size.1 as usize));
+        self.grid
+            .back_mut()
+            .resize((new_size.0 as usize, new_size.1 as usize));
         self.send_updated_position();
     }
 
@@ -129,26 +192,25 @@ impl Window {
             None => previous_style.clone(),
         };
 
-        // Compute text.
-        let mut text = cell.text;
-        if let Some(times) = cell.repeat {
-            // Repeats of zero times should be ignored, they are mostly useful for terminal Neovim
-            // to distinguish between empty lines and lines ending with spaces.
-            if times == 0 {
-                return;
-            }
-            text = text.repeat(times as usize);
+        // Repeats of zero times should be ignored, they are mostly useful for terminal Neovim to
+        // distinguish between empty lines and lines ending with spaces.
+        let times = cell.repeat.unwrap_or(1);
+        if times == 0 {
+            return;
         }
 
-        // Insert the contents of the cell into the grid.
-        if text.is_empty() {
-            if let Some(cell) = self.grid.get_cell_mut(*column_pos, row_index) {
-                *cell = (text, style.clone());
-            }
-            *column_pos += 1;
+        // The repeated cells are filled one by one, joining their text first could merge them into
+        // a single grapheme, like pairs of regional indicators do.
+        let characters = if cell.text.is_empty() {
+            vec![cell.text.as_str()]
         } else {
-            for character in text.graphemes(true) {
-                if let Some(cell) = self.grid.get_cell_mut(*column_pos, row_index) {
+            cell.text.graphemes(true).collect()
+        };
+
+        // Insert the contents of the cell into the grid.
+        for _ in 0..times {
+            for character in &characters {
+                if let Some(cell) = self.grid.back_mut().get_cell_mut(*column_pos, row_index) {
                     *cell = (character.to_string(), style.clone());
                 }
                 *column_pos += 1;
@@ -161,19 +223,30 @@ impl Window {
     // Build a line fragment for the given row starting from current_start up until the next style
     // change or double width character.
     fn build_line_fragment(&self, row_index: usize, start: usize) -> (usize, LineFragment) {
-        let row = self.grid.row(row_index).unwrap();
+        let row = self.grid.back().row(row_index).unwrap();
 
         let (_, style) = &row[start];
 
         let mut text = String::new();
         let mut width = 0;
 
-        for (character, possible_end_style) in row.iter().take(self.grid.width).skip(start) {
+        for (index, (character, possible_end_style)) in row
+            .iter()
+            .enumerate()
+            .take(self.grid.back().width)
+            .skip(start)
+        {
             // Style doesn't match. Draw what we've got.
             if style != possible_end_style {
                 break;
             }
 
+            // Double width characters are drawn on their own, so they can use guifontwide
+            let double_width = row.get(index + 1).is_some_and(|(next, _)| next.is_empty());
+            if double_width && width > 0 {
+                break;
+            }
+
             width += 1;
             // The previous character is double width, so send this as its own draw command.
             if character.is_empty() {
@@ -200,7 +273,7 @@ impl Window {
     fn redraw_line(&self, row: usize) {
         let mut current_start = 0;
         let mut line_fragments = Vec::new();
-        while current_start < self.grid.width {
+        while current_start < self.grid.back().width {
             let (next_start, line_fragment) = self.build_line_fragment(row, current_start);
             current_start = next_start;
             line_fragments.push(line_fragment);
@@ -216,11 +289,13 @@ impl Window {
         row: u64,
         column_start: u64,
         cells: Vec<GridLineCell>,
+        wrap: bool,
         defined_styles: &HashMap<u64, Arc<Style>>,
     ) {
         let mut previous_style = None;
         let row = row as usize;
-        if row < self.grid.height {
+        if row < self.grid.back().height {
+            self.grid.back_mut().set_line_wrapped(row, wrap);
             let mut column_pos = column_start as usize;
             for cell in cells {
                 self.modify_grid(
@@ -236,7 +311,7 @@ impl Window {
             // clipped by the line below. To mitigate that, we redraw the adjacent lines whenever
             // an individual line is redrawn. Unfortunately, some clipping still happens.
             // TODO: figure out how to solve this
-            if row < self.grid.height - 1 {
+            if row < self.grid.back().height - 1 {
                 self.redraw_line(row + 1);
             }
             self.redraw_line(row);
@@ -259,7 +334,7 @@ impl Window {
     ) {
         // Scrolls must move the data and send a WindowDrawCommand to move the rendered texture so
         // that future renders draw correctly
-        let is_pure_updown = self.grid.scroll_region(
+        let is_pure_updown = self.grid.back_mut().scroll_region(
             top as usize,
             bottom as usize,
             left as usize,
@@ -297,7 +372,7 @@ impl Window {
     }
 
     pub fn clear(&mut self) {
-        self.grid.clear();
+        self.grid.back_mut().clear();
         self.send_command(WindowDrawCommand::Clear);
     }
 
@@ -305,7 +380,7 @@ impl Window {
         self.send_command(WindowDrawCommand::Clear);
         // Draw the lines from the bottom up so that underlines don't get overwritten by the line
         // below.
-        for row in (0..self.grid.height).rev() {
+        for row in (0..self.grid.back().height).rev() {
             self.redraw_line(row);
         }
     }
@@ -322,3 +397,251 @@ impl Window {
         self.send_command(WindowDrawCommand::Close);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cell(text: &str) -> GridLineCell {
+        GridLineCell {
+            text: text.to_string(),
+            highlight_id: None,
+            repeat: None,
+        }
+    }
+
+    #[test]
+    fn only_flushed_text_is_copied() {
+        let mut window = Window::new(
+            1,
+            WindowType::Editor,
+            None,
+            (0.0, 0.0),
+            (4, 1),
+            Rc::new(DrawCommandBatcher::new()),
+        );
+
+        window.draw_grid_line(0, 0, vec![cell("a"), cell("b")], false, &HashMap::new());
+        assert_eq!(window.get_text(), "");
+        window.flush_grid();
+        assert_eq!(window.get_text(), "ab");
+    }
+
+    #[test]
+    fn draw_grid_line_stores_astral_plane_characters() {
+        let mut window = Window::new(
+            1,
+            WindowType::Editor,
+            None,
+            (0.0, 0.0),
+            (6, 1),
+            Rc::new(DrawCommandBatcher::new()),
+        );
+
+        window.draw_grid_line(
+            0,
+            0,
+            vec![
+                cell("a"),
+                cell("😀"),
+                cell(""),
+                cell("𝄞"),
+                cell("👍🏽"),
+                cell(""),
+            ],
+            false,
+            &HashMap::new(),
+        );
+
+        assert_eq!(window.get_cursor_grid_cell(0, 0).0, "a");
+        let (character, _, cell_width) = window.get_cursor_grid_cell(1, 0);
+        assert_eq!(character, "😀");
+        assert_eq!(cell_width, 2);
+        let (character, _, cell_width) = window.get_cursor_grid_cell(3, 0);
+        assert_eq!(character, "𝄞");
+        assert_eq!(cell_width, 1);
+        let (character, _, cell_width) = window.get_cursor_grid_cell(4, 0);
+        assert_eq!(character, "👍🏽");
+        assert_eq!(cell_width, 2);
+    }
+
+    #[test]
+    fn repeated_cells_fill_the_whole_run() {
+        let mut window = Window::new(
+            1,
+            WindowType::Editor,
+            None,
+            (0.0, 0.0),
+            (12, 1),
+            Rc::new(DrawCommandBatcher::new()),
+        );
+        let repeated = |text: &str, times| GridLineCell {
+            repeat: Some(times),
+            ..cell(text)
+        };
+
+        window.draw_grid_line(
+            0,
+            0,
+            vec![
+                cell("a"),
+                repeated("-", 5),
+                // Pairs of these would be a single grapheme when joined
+                repeated("🇦", 3),
+                repeated("x", 0),
+                repeated(" ", 3),
+            ],
+            false,
+            &HashMap::new(),
+        );
+
+        let row: Vec<_> = (0..12)
+            .map(|column| window.get_cursor_grid_cell(column, 0).0)
+            .collect();
+        assert_eq!(
+            row,
+            ["a", "-", "-", "-", "-", "-", "🇦", "🇦", "🇦", " ", " ", " "]
+        );
+    }
+
+    #[test]
+    fn viewport_is_stored_per_window() {
+        let mut window = Window::new(
+            2,
+            WindowType::Editor,
+            None,
+            (0.0, 0.0),
+            (80, 20),
+            Rc::new(DrawCommandBatcher::new()),
+        );
+        assert_eq!(window.viewport(), None);
+
+        window.set_viewport(
+            1000,
+            Viewport {
+                top_line: 41.0,
+                bottom_line: 61.0,
+                current_line: 50.0,
+                current_column: 4.0,
+                line_count: Some(300.0),
+            },
+        );
+        assert_eq!(
+            window.viewport().map(|viewport| viewport.top_line),
+            Some(41.0)
+        );
+    }
+
+    #[test]
+    fn cursor_over_an_expanded_tab_covers_a_single_cell() {
+        let mut window = Window::new(
+            1,
+            WindowType::Editor,
+            None,
+            (0.0, 0.0),
+            (7, 1),
+            Rc::new(DrawCommandBatcher::new()),
+        );
+
+        // A tab expanded with `listchars=tab:>-`, followed by a wide character at the end
+        window.draw_grid_line(
+            0,
+            0,
+            vec![
+                cell(">"),
+                cell("-"),
+                cell("-"),
+                cell("-"),
+                cell("a"),
+                cell("好"),
+                cell(""),
+            ],
+            false,
+            &HashMap::new(),
+        );
+
+        for column in 0..5 {
+            assert_eq!(window.get_cursor_grid_cell(column, 0).2, 1);
+        }
+        assert_eq!(window.get_cursor_grid_cell(5, 0).2, 2);
+        // Outside of the grid
+        assert_eq!(window.get_cursor_grid_cell(7, 0).2, 1);
+    }
+
+    #[test]
+    fn double_width_characters_get_their_own_fragment() {
+        let mut window = Window::new(
+            1,
+            WindowType::Editor,
+            None,
+            (0.0, 0.0),
+            (6, 1),
+            Rc::new(DrawCommandBatcher::new()),
+        );
+
+        window.draw_grid_line(
+            0,
+            0,
+            vec![
+                cell("a"),
+                cell("b"),
+                cell("好"),
+                cell(""),
+                cell("c"),
+                cell("d"),
+            ],
+            false,
+            &HashMap::new(),
+        );
+
+        let (next_start, fragment) = window.build_line_fragment(0, 0);
+        assert_eq!((fragment.text.as_str(), fragment.width), ("ab", 2));
+        let (next_start, fragment) = window.build_line_fragment(0, next_start);
+        assert_eq!((fragment.text.as_str(), fragment.width), ("好", 2));
+        let (_, fragment) = window.build_line_fragment(0, next_start);
+        assert_eq!((fragment.text.as_str(), fragment.width), ("cd", 2));
+    }
+
+    #[test]
+    fn wrapped_grid_line_is_marked() {
+        let mut window = Window::new(
+            1,
+            WindowType::Editor,
+            None,
+            (0.0, 0.0),
+            (2, 2),
+            Rc::new(DrawCommandBatcher::new()),
+        );
+
+        window.draw_grid_line(0, 0, vec![cell("a"), cell("b")], true, &HashMap::new());
+        window.draw_grid_line(1, 0, vec![cell("c")], false, &HashMap::new());
+
+        assert!(window.grid.is_line_wrapped(0));
+        assert!(!window.grid.is_line_wrapped(1));
+    }
+
+    #[test]
+    fn cursor_over_a_listchars_glyph_shows_it() {
+        let mut window = Window::new(
+            1,
+            WindowType::Editor,
+            None,
+            (0.0, 0.0),
+            (4, 1),
+            Rc::new(DrawCommandBatcher::new()),
+        );
+
+        // `set list listchars=space:·,tab:→\ ,eol:¬` with a tab between two spaces
+        window.draw_grid_line(
+            0,
+            0,
+            vec![cell("·"), cell("→"), cell(" "), cell("¬")],
+            false,
+            &HashMap::new(),
+        );
+
+        assert_eq!(window.get_cursor_grid_cell(0, 0).0, "·");
+        assert_eq!(window.get_cursor_grid_cell(1, 0).0, "→");
+        assert_eq!(window.get_cursor_grid_cell(3, 0).0, "¬");
+    }
+}
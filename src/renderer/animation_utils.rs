@@ -30,7 +30,6 @@ pub fn ease_in_cubic(t: f32) -> f32 {
     t * t * t
 }
 
-#[allow(dead_code)]
 pub fn ease_out_cubic(t: f32) -> f32 {
     let n = t - 1.0;
     n * n * n + 1.0
@@ -0,0 +1,84 @@
+use core::fmt;
+
+use clap::{builder::PossibleValue, ValueEnum};
+use log::warn;
+use serde::Deserialize;
+
+// The graphics APIs Skia can render with
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum GraphicsBackend {
+    /// Direct3D on Windows and OpenGL everywhere else
+    #[default]
+    Auto,
+    #[serde(rename = "opengl")]
+    OpenGL,
+    #[serde(rename = "d3d")]
+    Direct3D,
+}
+
+impl GraphicsBackend {
+    /// The backend to actually use, given which of them are available on this machine
+    pub fn resolve(self, direct3d_available: bool) -> GraphicsBackend {
+        match self {
+            GraphicsBackend::Auto if direct3d_available => GraphicsBackend::Direct3D,
+            GraphicsBackend::Auto => GraphicsBackend::OpenGL,
+            GraphicsBackend::Direct3D if !direct3d_available => {
+                warn!("Direct3D is not available, falling back to OpenGL");
+                GraphicsBackend::OpenGL
+            }
+            backend => backend,
+        }
+    }
+}
+
+impl From<&'_ GraphicsBackend> for &'static str {
+    fn from(backend: &'_ GraphicsBackend) -> Self {
+        match backend {
+            GraphicsBackend::Auto => "auto",
+            GraphicsBackend::OpenGL => "opengl",
+            GraphicsBackend::Direct3D => "d3d",
+        }
+    }
+}
+
+impl ValueEnum for GraphicsBackend {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[Self::Auto, Self::OpenGL, Self::Direct3D]
+    }
+
+    fn to_possible_value(&self) -> Option<PossibleValue> {
+        Some(PossibleValue::new(<&str>::from(self)))
+    }
+}
+
+impl fmt::Display for GraphicsBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", <&str>::from(self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unavailable_backend_falls_back_to_opengl() {
+        assert_eq!(
+            GraphicsBackend::Auto.resolve(true),
+            GraphicsBackend::Direct3D
+        );
+        assert_eq!(
+            GraphicsBackend::Auto.resolve(false),
+            GraphicsBackend::OpenGL
+        );
+        assert_eq!(
+            GraphicsBackend::Direct3D.resolve(false),
+            GraphicsBackend::OpenGL
+        );
+        assert_eq!(
+            GraphicsBackend::OpenGL.resolve(true),
+            GraphicsBackend::OpenGL
+        );
+    }
+}
@@ -0,0 +1,256 @@
+use std::path::PathBuf;
+
+use log::{error, warn};
+use rmpv::Value;
+use skia_safe::{
+    Canvas, Data, FilterMode, Image, MipmapMode, Paint, Rect, SamplingOptions, TileMode,
+};
+
+use crate::{renderer::RendererSettings, settings::ParseFromValue};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackgroundImageMode {
+    /// Scale the image to cover the whole window, cropping what doesn't fit
+    Fill,
+    /// Scale the image to fit inside the window, keeping the aspect ratio
+    Fit,
+    /// Scale the image to the window size, ignoring the aspect ratio
+    Stretch,
+    /// Repeat the image at its original size
+    Tile,
+    /// Draw the image at its original size in the middle of the window
+    Center,
+}
+
+impl ParseFromValue for BackgroundImageMode {
+    fn parse_from_value(&mut self, value: Value) {
+        if value.is_str() {
+            *self = match value.as_str().unwrap() {
+                "fill" => BackgroundImageMode::Fill,
+                "fit" => BackgroundImageMode::Fit,
+                "stretch" => BackgroundImageMode::Stretch,
+                "tile" => BackgroundImageMode::Tile,
+                "center" => BackgroundImageMode::Center,
+                value => {
+                    error!("Setting BackgroundImageMode expected one of `fill`, `fit`, `stretch`, `tile`, or `center`, but received {:?}", value);
+                    return;
+                }
+            };
+        } else {
+            error!(
+                "Setting BackgroundImageMode expected string, but received {:?}",
+                value
+            );
+        }
+    }
+}
+
+impl From<BackgroundImageMode> for Value {
+    fn from(mode: BackgroundImageMode) -> Self {
+        match mode {
+            BackgroundImageMode::Fill => Value::from("fill"),
+            BackgroundImageMode::Fit => Value::from("fit"),
+            BackgroundImageMode::Stretch => Value::from("stretch"),
+            BackgroundImageMode::Tile => Value::from("tile"),
+            BackgroundImageMode::Center => Value::from("center"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackgroundImageFilter {
+    /// Blend the neighbouring pixels, for photos
+    Linear,
+    /// Use the closest pixel, keeping the edges of pixel art sharp
+    Nearest,
+}
+
+impl ParseFromValue for BackgroundImageFilter {
+    fn parse_from_value(&mut self, value: Value) {
+        if value.is_str() {
+            *self = match value.as_str().unwrap() {
+                "linear" => BackgroundImageFilter::Linear,
+                "nearest" => BackgroundImageFilter::Nearest,
+                value => {
+                    error!("Setting BackgroundImageFilter expected one of `linear` or `nearest`, but received {:?}", value);
+                    return;
+                }
+            };
+        } else {
+            error!(
+                "Setting BackgroundImageFilter expected string, but received {:?}",
+                value
+            );
+        }
+    }
+}
+
+impl From<BackgroundImageFilter> for Value {
+    fn from(filter: BackgroundImageFilter) -> Self {
+        match filter {
+            BackgroundImageFilter::Linear => Value::from("linear"),
+            BackgroundImageFilter::Nearest => Value::from("nearest"),
+        }
+    }
+}
+
+fn sampling_options(filter: BackgroundImageFilter) -> SamplingOptions {
+    let filter_mode = match filter {
+        BackgroundImageFilter::Linear => FilterMode::Linear,
+        BackgroundImageFilter::Nearest => FilterMode::Nearest,
+    };
+    SamplingOptions::new(filter_mode, MipmapMode::None)
+}
+
+/// Where to draw an image of `image_size` on a canvas of `canvas_size`. Tiling is handled by a
+/// shader instead, so it's drawn at the original size from the top left corner.
+pub(super) fn image_rect(
+    mode: BackgroundImageMode,
+    image_size: (f32, f32),
+    canvas_size: (f32, f32),
+) -> Rect {
+    let (image_width, image_height) = image_size;
+    let (canvas_width, canvas_height) = canvas_size;
+
+    let scale = match mode {
+        BackgroundImageMode::Fill => (canvas_width / image_width).max(canvas_height / image_height),
+        BackgroundImageMode::Fit => (canvas_width / image_width).min(canvas_height / image_height),
+        BackgroundImageMode::Stretch => return Rect::from_wh(canvas_width, canvas_height),
+        BackgroundImageMode::Tile => return Rect::from_wh(image_width, image_height),
+        BackgroundImageMode::Center => 1.0,
+    };
+
+    let width = image_width * scale;
+    let height = image_height * scale;
+    Rect::from_xywh(
+        (canvas_width - width) / 2.0,
+        (canvas_height - height) / 2.0,
+        width,
+        height,
+    )
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(relative), Some(home)) => home.join(relative),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// The image drawn behind the grid. Decoding is slow, so the image is only loaded again when the
+/// path changes.
+pub struct BackgroundImage {
+    path: String,
+    image: Option<Image>,
+}
+
+impl BackgroundImage {
+    pub fn new() -> Self {
+        Self {
+            path: String::new(),
+            image: None,
+        }
+    }
+
+    fn update_path(&mut self, path: &str) {
+        if self.path == path {
+            return;
+        }
+        self.path = path.to_string();
+        self.image = None;
+
+        if path.is_empty() {
+            return;
+        }
+
+        let image = std::fs::read(expand_home(path))
+            .map_err(|error| error.to_string())
+            .and_then(|bytes| {
+                Image::from_encoded(Data::new_copy(&bytes))
+                    .ok_or_else(|| "unsupported image format".to_string())
+            });
+        match image {
+            Ok(image) => self.image = Some(image),
+            Err(error) => warn!("Could not load the background image {path:?}: {error}"),
+        }
+    }
+
+    /// Loads the image set in the settings and returns whether there is anything to draw.
+    pub fn update(&mut self, settings: &RendererSettings) -> bool {
+        self.update_path(&settings.background_image);
+        self.image.is_some()
+    }
+
+    pub fn draw(&self, canvas: &Canvas, frame: Rect, settings: &RendererSettings) {
+        let Some(image) = &self.image else {
+            return;
+        };
+
+        let canvas_size = (frame.width(), frame.height());
+        let image_size = (image.width() as f32, image.height() as f32);
+        let sampling = sampling_options(settings.background_image_filter);
+
+        let mut paint = Paint::default();
+        paint.set_alpha_f(settings.background_image_opacity.clamp(0.0, 1.0));
+
+        if settings.background_image_mode == BackgroundImageMode::Tile {
+            paint.set_shader(image.to_shader((TileMode::Repeat, TileMode::Repeat), sampling, None));
+            canvas.draw_rect(frame, &paint);
+        } else {
+            let rect = image_rect(settings.background_image_mode, image_size, canvas_size);
+            canvas.draw_image_rect_with_sampling_options(image, None, rect, sampling, &paint);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn image_rect_scales_per_mode() {
+        let image_size = (200.0, 100.0);
+        let canvas_size = (400.0, 400.0);
+
+        assert_eq!(
+            image_rect(BackgroundImageMode::Fill, image_size, canvas_size),
+            Rect::from_xywh(-200.0, 0.0, 800.0, 400.0)
+        );
+        assert_eq!(
+            image_rect(BackgroundImageMode::Fit, image_size, canvas_size),
+            Rect::from_xywh(0.0, 100.0, 400.0, 200.0)
+        );
+        assert_eq!(
+            image_rect(BackgroundImageMode::Stretch, image_size, canvas_size),
+            Rect::from_wh(400.0, 400.0)
+        );
+        assert_eq!(
+            image_rect(BackgroundImageMode::Center, image_size, canvas_size),
+            Rect::from_xywh(100.0, 150.0, 200.0, 100.0)
+        );
+    }
+
+    #[test]
+    fn sampling_follows_the_filter_setting() {
+        assert_eq!(
+            RendererSettings::default().background_image_filter,
+            BackgroundImageFilter::Linear
+        );
+        let linear = sampling_options(BackgroundImageFilter::Linear);
+        assert_eq!(linear.filter, FilterMode::Linear);
+        let nearest = sampling_options(BackgroundImageFilter::Nearest);
+        assert_eq!(nearest.filter, FilterMode::Nearest);
+        assert_eq!(nearest.mipmap, MipmapMode::None);
+    }
+
+    #[test]
+    fn missing_image_is_not_drawn_and_not_reloaded() {
+        let mut background_image = BackgroundImage::new();
+        background_image.update_path("/this/path/does/not/exist.png");
+        assert!(background_image.image.is_none());
+        assert_eq!(background_image.path, "/this/path/does/not/exist.png");
+
+        background_image.update_path("");
+        assert!(background_image.image.is_none());
+    }
+}
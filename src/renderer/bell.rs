@@ -0,0 +1,84 @@
+use skia_safe::{Canvas, Color, Paint, Rect};
+
+// How strong the flash starts out, before fading
+const FLASH_ALPHA: f32 = 0.25;
+
+/// A flash over the whole window for the visual bell, which fades out linearly.
+pub struct VisualBell {
+    elapsed: Option<f32>,
+}
+
+impl VisualBell {
+    pub fn new() -> Self {
+        Self { elapsed: None }
+    }
+
+    pub fn ring(&mut self) {
+        self.elapsed = Some(0.0);
+    }
+
+    /// Returns whether anything changed. That includes the frame where the flash ends, so that it
+    /// gets cleared from the screen.
+    pub fn animate(&mut self, dt: f32, length: f32) -> bool {
+        let Some(elapsed) = self.elapsed else {
+            return false;
+        };
+        let elapsed = elapsed + dt;
+        self.elapsed = (elapsed < length).then_some(elapsed);
+        true
+    }
+
+    fn strength(&self, length: f32) -> f32 {
+        match self.elapsed {
+            Some(elapsed) if length > 0.0 => (1.0 - elapsed / length).clamp(0.0, 1.0),
+            _ => 0.0,
+        }
+    }
+
+    pub fn draw(&self, canvas: &Canvas, frame: Rect, color: Color, length: f32) {
+        let strength = self.strength(length);
+        if strength <= 0.0 {
+            return;
+        }
+
+        let mut paint = Paint::default();
+        paint.set_color(color).set_alpha_f(FLASH_ALPHA * strength);
+        canvas.draw_rect(frame, &paint);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flash_fades_and_redraws_once_it_ends() {
+        let mut bell = VisualBell::new();
+        assert!(!bell.animate(0.1, 0.2));
+
+        bell.ring();
+        assert_eq!(bell.strength(0.2), 1.0);
+        assert!(bell.animate(0.1, 0.2));
+        assert!((bell.strength(0.2) - 0.5).abs() < 1e-6);
+
+        assert!(bell.animate(0.1, 0.2));
+        assert_eq!(bell.strength(0.2), 0.0);
+        assert!(!bell.animate(0.1, 0.2));
+    }
+
+    #[test]
+    fn flash_is_transformed_with_the_frame() {
+        let mut surface = skia_safe::surfaces::raster_n32_premul((20, 20)).unwrap();
+        let canvas = surface.canvas();
+        canvas.clear(Color::BLACK);
+        // Like a fullscreen transition shrinking the frame
+        canvas.scale((0.5, 0.5));
+        let mut bell = VisualBell::new();
+        bell.ring();
+        bell.draw(canvas, Rect::from_wh(20.0, 20.0), Color::WHITE, 0.2);
+
+        let pixels = surface.peek_pixels().unwrap();
+        assert_ne!(pixels.get_color((5, 5)), Color::BLACK);
+        assert_eq!(pixels.get_color((15, 15)), Color::BLACK);
+    }
+}
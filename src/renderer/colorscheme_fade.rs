@@ -0,0 +1,90 @@
+use skia_safe::{Canvas, Picture};
+
+/// A cross-fade from the screen as it was drawn with the previous colorscheme. The old frame is
+/// drawn over the new one with a decreasing opacity, so whatever changes while it fades out is
+/// still visible underneath.
+pub struct ColorschemeFade {
+    old_frame: Option<Picture>,
+    elapsed: f32,
+}
+
+impl ColorschemeFade {
+    pub fn new() -> Self {
+        Self {
+            old_frame: None,
+            elapsed: 0.0,
+        }
+    }
+
+    pub fn start(&mut self, old_frame: Picture) {
+        self.old_frame = Some(old_frame);
+        self.elapsed = 0.0;
+    }
+
+    /// Returns whether anything changed. That includes the frame where the fade ends, so that the
+    /// old frame gets cleared from the screen.
+    pub fn animate(&mut self, dt: f32, length: f32) -> bool {
+        if self.old_frame.is_none() {
+            return false;
+        }
+        self.elapsed += dt;
+        if self.elapsed >= length {
+            self.old_frame = None;
+        }
+        true
+    }
+
+    fn opacity(&self, length: f32) -> f32 {
+        match self.old_frame {
+            Some(_) if length > 0.0 => (1.0 - self.elapsed / length).clamp(0.0, 1.0),
+            _ => 0.0,
+        }
+    }
+
+    pub fn draw(&self, canvas: &Canvas, length: f32) {
+        let opacity = self.opacity(length);
+        let Some(old_frame) = self.old_frame.as_ref().filter(|_| opacity > 0.0) else {
+            return;
+        };
+
+        canvas.save_layer_alpha_f(None, opacity);
+        canvas.draw_picture(old_frame, None, None);
+        canvas.restore();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use skia_safe::{PictureRecorder, Rect};
+
+    fn empty_frame() -> Picture {
+        let mut recorder = PictureRecorder::new();
+        recorder.begin_recording(Rect::from_wh(10.0, 10.0), None);
+        recorder.finish_recording_as_picture(None).unwrap()
+    }
+
+    #[test]
+    fn old_frame_fades_out_and_redraws_once_it_ends() {
+        let mut fade = ColorschemeFade::new();
+        assert!(!fade.animate(0.1, 0.2));
+
+        fade.start(empty_frame());
+        assert_eq!(fade.opacity(0.2), 1.0);
+        assert!(fade.animate(0.1, 0.2));
+        assert!((fade.opacity(0.2) - 0.5).abs() < 1e-6);
+
+        assert!(fade.animate(0.1, 0.2));
+        assert_eq!(fade.opacity(0.2), 0.0);
+        assert!(!fade.animate(0.1, 0.2));
+    }
+
+    #[test]
+    fn zero_length_disables_the_fade() {
+        let mut fade = ColorschemeFade::new();
+        fade.start(empty_frame());
+        assert_eq!(fade.opacity(0.0), 0.0);
+        assert!(fade.animate(0.1, 0.0));
+        assert!(!fade.animate(0.1, 0.0));
+    }
+}
@@ -14,6 +14,8 @@ pub struct BlinkStatus {
     /// When the cursor should change to the next [`BlinkState`]
     transition_time: Instant,
     current_cursor: Option<Cursor>,
+    /// Blinking can be turned off regardless of the blink times of the cursor
+    enabled: bool,
 }
 
 fn is_static(cursor: &Cursor) -> bool {
@@ -32,6 +34,7 @@ impl BlinkStatus {
             state: BlinkState::Waiting,
             transition_time: Instant::now(),
             current_cursor: None,
+            enabled: true,
         }
     }
 
@@ -48,8 +51,17 @@ impl BlinkStatus {
         Duration::from_millis(delay_ms)
     }
 
-    pub fn update_status(&mut self, new_cursor: &Cursor) -> ShouldRender {
-        let now = Instant::now();
+    pub fn update_status(&mut self, new_cursor: &Cursor, enabled: bool) -> ShouldRender {
+        self.update_status_at(new_cursor, Instant::now(), enabled)
+    }
+
+    fn update_status_at(
+        &mut self,
+        new_cursor: &Cursor,
+        now: Instant,
+        enabled: bool,
+    ) -> ShouldRender {
+        self.enabled = enabled;
         if self.current_cursor.is_none() || new_cursor != self.current_cursor.as_ref().unwrap() {
             self.current_cursor = Some(new_cursor.clone());
             if new_cursor.blinkwait.is_some() && new_cursor.blinkwait != Some(0) {
@@ -62,7 +74,7 @@ impl BlinkStatus {
 
         let current_cursor = self.current_cursor.as_ref().unwrap();
 
-        if is_static(current_cursor) {
+        if !enabled || is_static(current_cursor) {
             self.state = BlinkState::Waiting;
             ShouldRender::Wait
         } else {
@@ -83,6 +95,13 @@ impl BlinkStatus {
         }
     }
 
+    /// When the cursor changes to the next [`BlinkState`], so that the render loop can wake up
+    /// right then, even when that's sooner than the next frame. `None` when it doesn't blink.
+    pub fn next_wake_time(&self) -> Option<Instant> {
+        let cursor = self.current_cursor.as_ref()?;
+        (self.enabled && !is_static(cursor)).then_some(self.transition_time)
+    }
+
     /// Calculate the opacity the cursor should be drawn with when smooth cursor blink is enabled.
     /// `0.0` is fully transparent, `1.0` is fully opaque.
     pub fn opacity(&self) -> f32 {
@@ -108,11 +127,119 @@ impl BlinkStatus {
         }
     }
 
-    /// Whether or not the cursor should be drawn (only applicable when smooth blink is disabled).
-    pub fn should_render(&self) -> bool {
+    /// The opacity the cursor should be drawn with when smooth blink is disabled. It's dimmed to
+    /// `off_opacity` while blinked off, where `0.0` hides it completely.
+    pub fn blink_opacity(&self, off_opacity: f32) -> f32 {
         match self.state {
-            BlinkState::Off => false,
-            BlinkState::On | BlinkState::Waiting => true,
+            BlinkState::Off => off_opacity.clamp(0.0, 1.0),
+            BlinkState::On | BlinkState::Waiting => 1.0,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn next_wake_time_is_the_next_transition() {
+        let cursor = Cursor {
+            blinkwait: Some(100),
+            blinkon: Some(50),
+            blinkoff: Some(30),
+            ..Cursor::new()
+        };
+        let start = Instant::now();
+        let ms = Duration::from_millis;
+        let mut blink_status = BlinkStatus::new();
+
+        assert_eq!(
+            blink_status.update_status_at(&cursor, start, true),
+            ShouldRender::Deadline(start + ms(100))
+        );
+        assert_eq!(blink_status.next_wake_time(), Some(start + ms(100)));
+
+        assert_eq!(
+            blink_status.update_status_at(&cursor, start + ms(100), true),
+            ShouldRender::Immediately
+        );
+        assert_eq!(blink_status.state, BlinkState::On);
+        assert_eq!(blink_status.next_wake_time(), Some(start + ms(150)));
+
+        blink_status.update_status_at(&cursor, start + ms(150), true);
+        assert_eq!(blink_status.state, BlinkState::Off);
+        assert_eq!(blink_status.next_wake_time(), Some(start + ms(180)));
+
+        let static_cursor = Cursor {
+            blinkwait: None,
+            ..cursor
+        };
+        blink_status.update_status_at(&static_cursor, start + ms(200), true);
+        assert_eq!(blink_status.next_wake_time(), None);
+    }
+
+    #[test]
+    fn content_changes_restart_the_blinking() {
+        let cursor = Cursor {
+            blinkwait: Some(100),
+            blinkon: Some(50),
+            blinkoff: Some(30),
+            ..Cursor::new()
+        };
+        let start = Instant::now();
+        let ms = Duration::from_millis;
+        let mut blink_status = BlinkStatus::new();
+        blink_status.update_status_at(&cursor, start, true);
+        blink_status.update_status_at(&cursor, start + ms(100), true);
+        blink_status.update_status_at(&cursor, start + ms(150), true);
+        assert_eq!(blink_status.state, BlinkState::Off);
+
+        // A flush that only changed the line under the cursor, it didn't move
+        let edited = Cursor {
+            content_revision: cursor.content_revision + 1,
+            ..cursor
+        };
+        assert_eq!(
+            blink_status.update_status_at(&edited, start + ms(160), true),
+            ShouldRender::Deadline(start + ms(260))
+        );
+        assert_eq!(blink_status.state, BlinkState::Waiting);
+        assert_eq!(blink_status.blink_opacity(0.0), 1.0);
+    }
+
+    #[test]
+    fn disabled_blinking_keeps_the_cursor_on() {
+        let cursor = Cursor {
+            blinkwait: Some(100),
+            blinkon: Some(50),
+            blinkoff: Some(30),
+            ..Cursor::new()
+        };
+        let start = Instant::now();
+        let mut blink_status = BlinkStatus::new();
+
+        for elapsed in [0, 100, 150, 180, 1000] {
+            let now = start + Duration::from_millis(elapsed);
+            assert_eq!(
+                blink_status.update_status_at(&cursor, now, false),
+                ShouldRender::Wait
+            );
+            assert_eq!(blink_status.blink_opacity(0.0), 1.0);
+            assert!(!blink_status.should_animate());
+        }
+        assert_eq!(blink_status.next_wake_time(), None);
+    }
+
+    #[test]
+    fn blink_off_uses_the_configured_opacity() {
+        let mut blink_status = BlinkStatus::new();
+        assert_eq!(blink_status.blink_opacity(0.3), 1.0);
+
+        blink_status.state = BlinkState::Off;
+        assert_eq!(blink_status.blink_opacity(0.3), 0.3);
+        assert_eq!(blink_status.blink_opacity(0.0), 0.0);
+
+        blink_status.state = BlinkState::On;
+        assert_eq!(blink_status.blink_opacity(0.3), 1.0);
+    }
+}
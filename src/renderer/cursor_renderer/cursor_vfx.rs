@@ -1,4 +1,4 @@
-use log::error;
+use log::{error, warn};
 use nvim_rs::Value;
 use skia_safe::{paint::Style, BlendMode, Canvas, Color, Paint, Rect};
 
@@ -10,6 +10,7 @@ use crate::{
     units::{GridSize, PixelPos, PixelSize, PixelVec},
 };
 
+/// An effect drawn along with the cursor, selected by the `vfx_mode` setting.
 pub trait CursorVfx {
     fn update(
         &mut self,
@@ -45,27 +46,101 @@ pub enum TrailMode {
 
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum VfxMode {
-    Highlight(HighlightMode),
-    Trail(TrailMode),
+    /// An effect from the [`VfxRegistry`], by its name
+    Effect(String),
     Disabled,
 }
 
+pub type VfxConstructor = fn() -> Box<dyn CursorVfx>;
+
+/// The effects compiled into Neovide, by the name that selects them in `vfx_mode`, in the order
+/// they are cycled through. A new effect adds itself here.
+const VFX_MODES: &[(&str, VfxConstructor)] = &[
+    ("sonicboom", || {
+        Box::new(PointHighlight::new(&HighlightMode::SonicBoom))
+    }),
+    ("ripple", || {
+        Box::new(PointHighlight::new(&HighlightMode::Ripple))
+    }),
+    ("wireframe", || {
+        Box::new(PointHighlight::new(&HighlightMode::Wireframe))
+    }),
+    ("railgun", || {
+        Box::new(ParticleTrail::new(&TrailMode::Railgun))
+    }),
+    ("torpedo", || {
+        Box::new(ParticleTrail::new(&TrailMode::Torpedo))
+    }),
+    ("pixiedust", || {
+        Box::new(ParticleTrail::new(&TrailMode::PixieDust))
+    }),
+];
+
+/// The effects that can be selected by name in `vfx_mode`. An empty name selects no effect.
+pub struct VfxRegistry {
+    effects: Vec<(String, VfxConstructor)>,
+}
+
+impl VfxRegistry {
+    fn new(effects: &[(&str, VfxConstructor)]) -> Self {
+        let mut registry = Self {
+            effects: Vec::new(),
+        };
+        for (name, constructor) in effects {
+            if name.is_empty() || registry.constructor(name).is_some() {
+                warn!("The cursor vfx mode {name:?} is already taken, and can't be registered");
+                continue;
+            }
+            registry.effects.push((name.to_string(), *constructor));
+        }
+        registry
+    }
+
+    fn constructor(&self, name: &str) -> Option<VfxConstructor> {
+        self.effects
+            .iter()
+            .find(|(effect, _)| effect == name)
+            .map(|(_, constructor)| *constructor)
+    }
+
+    /// The registered mode with the name.
+    fn mode(&self, name: &str) -> Option<VfxMode> {
+        if name.is_empty() {
+            return Some(VfxMode::Disabled);
+        }
+        self.constructor(name)
+            .map(|_| VfxMode::Effect(name.to_string()))
+    }
+
+    fn create(&self, name: &str) -> Option<Box<dyn CursorVfx>> {
+        self.constructor(name).map(|constructor| constructor())
+    }
+
+    /// The mode after `current` when cycling through all of them in the order they were
+    /// registered, and back to no effect at the end.
+    pub fn next_mode(&self, current: &VfxMode) -> VfxMode {
+        let index = match current {
+            VfxMode::Effect(name) => self.effects.iter().position(|(effect, _)| effect == name),
+            VfxMode::Disabled => None,
+        };
+        let next = index.map_or(0, |index| index + 1);
+        self.effects
+            .get(next)
+            .map_or(VfxMode::Disabled, |(name, _)| VfxMode::Effect(name.clone()))
+    }
+}
+
+lazy_static! {
+    pub static ref VFX_REGISTRY: VfxRegistry = VfxRegistry::new(VFX_MODES);
+}
+
 impl ParseFromValue for VfxMode {
     fn parse_from_value(&mut self, value: Value) {
-        if value.is_str() {
-            *self = match value.as_str().unwrap() {
-                "sonicboom" => VfxMode::Highlight(HighlightMode::SonicBoom),
-                "ripple" => VfxMode::Highlight(HighlightMode::Ripple),
-                "wireframe" => VfxMode::Highlight(HighlightMode::Wireframe),
-                "railgun" => VfxMode::Trail(TrailMode::Railgun),
-                "torpedo" => VfxMode::Trail(TrailMode::Torpedo),
-                "pixiedust" => VfxMode::Trail(TrailMode::PixieDust),
-                "" => VfxMode::Disabled,
-                value => {
-                    error!("Expected a VfxMode name, but received {:?}", value);
-                    return;
-                }
-            };
+        if let Some(name) = value.as_str() {
+            match VFX_REGISTRY.mode(name) {
+                Some(mode) => *self = mode,
+                None => error!("Expected a VfxMode name, but received {:?}", name),
+            }
         } else {
             error!("Expected a VfxMode string, but received {:?}", value);
         }
@@ -75,12 +150,7 @@ impl ParseFromValue for VfxMode {
 impl From<VfxMode> for Value {
     fn from(mode: VfxMode) -> Self {
         match mode {
-            VfxMode::Highlight(HighlightMode::SonicBoom) => Value::from("sonicboom"),
-            VfxMode::Highlight(HighlightMode::Ripple) => Value::from("ripple"),
-            VfxMode::Highlight(HighlightMode::Wireframe) => Value::from("wireframe"),
-            VfxMode::Trail(TrailMode::Railgun) => Value::from("railgun"),
-            VfxMode::Trail(TrailMode::Torpedo) => Value::from("torpedo"),
-            VfxMode::Trail(TrailMode::PixieDust) => Value::from("pixiedust"),
+            VfxMode::Effect(name) => Value::from(name),
             VfxMode::Disabled => Value::from(""),
         }
     }
@@ -88,8 +158,7 @@ impl From<VfxMode> for Value {
 
 pub fn new_cursor_vfx(mode: &VfxMode) -> Option<Box<dyn CursorVfx>> {
     match mode {
-        VfxMode::Highlight(mode) => Some(Box::new(PointHighlight::new(mode))),
-        VfxMode::Trail(mode) => Some(Box::new(ParticleTrail::new(mode))),
+        VfxMode::Effect(name) => VFX_REGISTRY.create(name),
         VfxMode::Disabled => None,
     }
 }
@@ -143,7 +212,7 @@ impl CursorVfx for PointHighlight {
         paint.set_blend_mode(BlendMode::SrcOver);
 
         let colors = &grid_renderer.default_style.colors;
-        let base_color: Color = cursor.background(colors).to_color();
+        let base_color: Color = grid_renderer.display_color(cursor.background(colors));
         let alpha = ease(ease_in_quad, settings.vfx_opacity, 0.0, self.t) as u8;
         let color = Color::from_argb(alpha, base_color.r(), base_color.g(), base_color.b());
 
@@ -345,7 +414,7 @@ impl CursorVfx for ParticleTrail {
         }
 
         let colors = &grid_renderer.default_style.colors;
-        let base_color: Color = cursor.background(colors).to_color();
+        let base_color: Color = grid_renderer.display_color(cursor.background(colors));
 
         paint.set_blend_mode(BlendMode::SrcOver);
 
@@ -448,3 +517,97 @@ fn rotate_vec(v: PixelVec<f32>, rot: f32) -> PixelVec<f32> {
 
     PixelVec::new(v.x * cos - v.y * sin, v.x * sin + v.y * cos)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct StillVfx;
+
+    impl CursorVfx for StillVfx {
+        fn update(
+            &mut self,
+            _settings: &CursorSettings,
+            _current_cursor_destination: PixelPos<f32>,
+            _cursor_dimensions: PixelSize<f32>,
+            _immediate_movement: bool,
+            _dt: f32,
+        ) -> bool {
+            true
+        }
+        fn restart(&mut self, _position: PixelPos<f32>) {}
+        fn render(
+            &self,
+            _settings: &CursorSettings,
+            _canvas: &Canvas,
+            _grid_renderer: &mut GridRenderer,
+            _cursor: &Cursor,
+        ) {
+        }
+    }
+
+    fn still_vfx() -> Box<dyn CursorVfx> {
+        Box::new(StillVfx)
+    }
+
+    #[test]
+    fn registered_modes_can_be_selected_by_name() {
+        let registry = VfxRegistry::new(&[]);
+        assert_eq!(registry.mode("still"), None);
+        assert_eq!(registry.mode(""), Some(VfxMode::Disabled));
+
+        let registry = VfxRegistry::new(&[("still", still_vfx)]);
+        let mode = registry.mode("still").unwrap();
+        assert_eq!(mode, VfxMode::Effect("still".to_string()));
+        assert_eq!(Value::from(mode), Value::from("still"));
+
+        let mut vfx = registry.create("still").unwrap();
+        assert!(vfx.update(
+            &CursorSettings::default(),
+            PixelPos::origin(),
+            PixelSize::new(1.0, 1.0),
+            false,
+            0.1,
+        ));
+
+        // The first effect with a name keeps it
+        let registry = VfxRegistry::new(&[("still", still_vfx), ("still", || unreachable!())]);
+        assert_eq!(registry.effects.len(), 1);
+        assert!(registry.create("still").is_some());
+    }
+
+    #[test]
+    fn built_in_modes_are_parsed_and_created_from_the_registry() {
+        for (name, _) in VFX_MODES {
+            let mut mode = VfxMode::Disabled;
+            mode.parse_from_value(Value::from(*name));
+            assert_eq!(Value::from(mode.clone()), Value::from(*name));
+            assert!(new_cursor_vfx(&mode).is_some());
+        }
+
+        let mut mode = VfxMode::Effect("railgun".to_string());
+        mode.parse_from_value(Value::from(""));
+        assert_eq!(mode, VfxMode::Disabled);
+        assert!(new_cursor_vfx(&mode).is_none());
+    }
+
+    #[test]
+    fn cycling_goes_through_the_modes_in_the_registered_order() {
+        let registry = VfxRegistry::new(&[("zap", still_vfx), ("blink", still_vfx)]);
+        let effect = |name: &str| VfxMode::Effect(name.to_string());
+        assert_eq!(registry.next_mode(&VfxMode::Disabled), effect("zap"));
+        assert_eq!(registry.next_mode(&effect("zap")), effect("blink"));
+        assert_eq!(registry.next_mode(&effect("blink")), VfxMode::Disabled);
+        // An unknown mode starts over
+        assert_eq!(registry.next_mode(&effect("gone")), effect("zap"));
+
+        assert_eq!(
+            VFX_REGISTRY.next_mode(&VfxMode::Disabled),
+            effect("sonicboom")
+        );
+        assert_eq!(
+            VFX_REGISTRY.next_mode(&effect("pixiedust")),
+            VfxMode::Disabled
+        );
+    }
+}
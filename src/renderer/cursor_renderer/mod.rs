@@ -1,24 +1,38 @@
 mod blink;
-mod cursor_vfx;
+pub mod cursor_vfx;
 
-use std::collections::HashMap;
+use std::{collections::HashMap, time::Instant};
 
-use skia_safe::{op, Canvas, Paint, Path};
+use skia_safe::{op, paint::Style, Canvas, Color, Color4f, Paint, Path, Rect, TextBlob};
 use winit::event::WindowEvent;
 
 use crate::{
     bridge::EditorMode,
-    editor::{Cursor, CursorShape},
+    editor::{Colors, Cursor, CursorShape},
     profiling::{tracy_plot, tracy_zone},
-    renderer::{animation_utils::*, GridRenderer, RenderedWindow},
+    renderer::{animation_utils::*, parse_color_setting, GridRenderer, RenderedWindow},
     settings::{ParseFromValue, SETTINGS},
-    units::{to_skia_point, GridPos, GridScale, PixelPos, PixelSize, PixelVec},
-    window::ShouldRender,
+    units::{
+        to_skia_point, to_skia_rect, GridPos, GridScale, PixelPos, PixelRect, PixelSize, PixelVec,
+    },
+    window::{is_motion_reduced, ShouldRender},
 };
 
 use blink::*;
 
 const DEFAULT_CELL_PERCENTAGE: f32 = 1.0 / 8.0;
+const SECONDARY_CURSOR_OPACITY: f32 = 0.5;
+// The slowest a trailing corner can move relative to the animation speed, which guarantees that
+// every corner eventually reaches its destination
+const MIN_CORNER_SPEED: f32 = 0.05;
+// How close to its destination a corner snapped to whole pixels comes to rest
+const RESTING_DISTANCE: f32 = 0.1;
+
+// Below this difference in luminance, the cursor is hard to see over the cell under it
+const MIN_CURSOR_CONTRAST: f32 = 0.2;
+
+// The missing glyph box is inset by this fraction of the cell, like a tofu glyph
+const MISSING_GLYPH_INSET: f32 = 1.0 / 8.0;
 
 const STANDARD_CORNERS: &[(f32, f32); 4] = &[(-0.5, -0.5), (0.5, -0.5), (0.5, 0.5), (-0.5, 0.5)];
 
@@ -27,13 +41,33 @@ const STANDARD_CORNERS: &[(f32, f32); 4] = &[(-0.5, -0.5), (0.5, -0.5), (0.5, 0.
 #[derive(Clone)]
 pub struct CursorSettings {
     antialiasing: bool,
+    crisp_resting_edges: bool,
+    text_antialiasing: bool,
     animation_length: f32,
+    max_animation_distance: f32,
+    smart_animation: bool,
+    smart_min_distance: f32,
+    smart_max_distance: f32,
     distance_length_adjust: bool,
     animate_in_insert_mode: bool,
     animate_command_line: bool,
+    instant_modes: String,
     trail_size: f32,
+    trail_intensity: f32,
     unfocused_outline_width: f32,
+    outline_width: f32,
+    blink: bool,
     smooth_blink: bool,
+    blink_off_opacity: f32,
+    multiple_cursors: bool,
+    replace_color: String,
+    draw_character: bool,
+    unclipped_character: bool,
+    keep_character_color: bool,
+    force_contrast: bool,
+    min_bar_width: f32,
+    span_tabs: bool,
+    snap_to_pixels: bool,
 
     vfx_mode: cursor_vfx::VfxMode,
     vfx_opacity: f32,
@@ -48,13 +82,33 @@ impl Default for CursorSettings {
     fn default() -> Self {
         CursorSettings {
             antialiasing: true,
+            crisp_resting_edges: false,
+            text_antialiasing: true,
             animation_length: 0.06,
+            max_animation_distance: 0.0,
+            smart_animation: false,
+            smart_min_distance: 2.0,
+            smart_max_distance: 20.0,
             distance_length_adjust: true,
             animate_in_insert_mode: true,
             animate_command_line: true,
+            instant_modes: String::new(),
             trail_size: 0.7,
+            trail_intensity: 1.0,
             unfocused_outline_width: 1.0 / 8.0,
+            outline_width: 0.0,
+            blink: true,
             smooth_blink: false,
+            blink_off_opacity: 0.0,
+            multiple_cursors: false,
+            replace_color: "".to_string(),
+            draw_character: true,
+            unclipped_character: false,
+            keep_character_color: false,
+            force_contrast: false,
+            min_bar_width: 1.0,
+            span_tabs: false,
+            snap_to_pixels: false,
             vfx_mode: cursor_vfx::VfxMode::Disabled,
             vfx_opacity: 200.0,
             vfx_particle_lifetime: 1.2,
@@ -88,6 +142,39 @@ impl Corner {
         }
     }
 
+    /// Where the corner rests on the cell region. It's taken from the edges of the region instead
+    /// of being offset from the center, so that a resting cursor covers exactly the same pixels as
+    /// the background under it.
+    fn resting_position(&self, cell_region: PixelRect<f32>) -> PixelPos<f32> {
+        let edge = |min: f32, max: f32, t: f32| match t {
+            0.0 => min,
+            1.0 => max,
+            _ => min + (max - min) * t,
+        };
+        PixelPos::new(
+            edge(
+                cell_region.min.x,
+                cell_region.max.x,
+                self.relative_position.x + 0.5,
+            ),
+            edge(
+                cell_region.min.y,
+                cell_region.max.y,
+                self.relative_position.y + 0.5,
+            ),
+        )
+    }
+
+    /// Puts the corner where it comes to rest, rounded to whole pixels with `snap_to_pixels` so
+    /// that the resting cursor has crisp edges.
+    fn settle(&mut self, settings: &CursorSettings, position: PixelPos<f32>) {
+        self.current_position = if settings.snap_to_pixels {
+            position.round()
+        } else {
+            position
+        };
+    }
+
     pub fn update(
         &mut self,
         settings: &CursorSettings,
@@ -122,7 +209,7 @@ impl Corner {
 
         if immediate_movement {
             self.t = 1.0;
-            self.current_position = corner_destination;
+            self.settle(settings, corner_destination);
             return true;
         }
 
@@ -142,12 +229,16 @@ impl Corner {
             // We are at destination, move t out of 0-1 range to stop the animation
             self.t = 2.0;
         } else {
-            let corner_dt = dt
-                * lerp(
-                    1.0,
-                    (1.0 - settings.trail_size).clamp(0.0, 1.0),
-                    -direction_alignment,
-                );
+            let mut motion_scale = lerp(
+                1.0,
+                (1.0 - settings.trail_size).clamp(0.0, 1.0),
+                -direction_alignment,
+            );
+            // Make the corners facing away from the destination lag even further behind
+            if motion_scale < 1.0 {
+                motion_scale = motion_scale.powf(settings.trail_intensity.max(0.0));
+            }
+            let corner_dt = dt * motion_scale.max(MIN_CORNER_SPEED);
             self.t =
                 (self.t + corner_dt / (settings.animation_length * self.length_multiplier)).min(1.0)
         }
@@ -158,37 +249,173 @@ impl Corner {
             corner_destination,
             self.t,
         );
+        // The tail of the easing moves by fractions of a pixel, end it there instead
+        if settings.snap_to_pixels
+            && (corner_destination - self.current_position).length() < RESTING_DISTANCE
+        {
+            self.t = 1.0;
+            self.settle(settings, corner_destination);
+        }
 
         true
     }
 }
 
+/// How the smart animation mode moves the cursor to a new destination.
+#[derive(Debug, PartialEq)]
+enum SmartAnimation {
+    /// Short moves jump, so the cursor stays crisp while editing locally
+    Snap,
+    Smear,
+    /// Long moves only smear over the last part, the corners first jump ahead by this offset
+    Capped(PixelVec<f32>),
+}
+
+/// Chooses the animation for a move over `distance`, with the thresholds measured in cells.
+fn smart_animation(
+    settings: &CursorSettings,
+    distance: PixelVec<f32>,
+    cursor_dimensions: PixelSize<f32>,
+) -> SmartAnimation {
+    let cell_distance = PixelVec::new(
+        distance.x / cursor_dimensions.width,
+        distance.y / cursor_dimensions.height,
+    )
+    .length();
+
+    if cell_distance < settings.smart_min_distance {
+        SmartAnimation::Snap
+    } else if cell_distance > settings.smart_max_distance {
+        let skipped = 1.0 - settings.smart_max_distance.max(0.0) / cell_distance;
+        SmartAnimation::Capped(distance * skipped)
+    } else {
+        SmartAnimation::Smear
+    }
+}
+
 pub struct CursorRenderer {
     pub corners: Vec<Corner>,
     cursor: Cursor,
     destination: PixelPos<f32>,
+    /// The background region of the cell under the cursor, when the window isn't scrolling
+    cell_region: Option<PixelRect<f32>>,
+    has_destination: bool,
+    secondary_cursors: Vec<GridPos<u64>>,
+    secondary_destinations: Vec<PixelPos<f32>>,
+    /// The expanded tab under the cursor, as the cells before the cursor cell and its width
+    tab_span: Option<(u64, u64)>,
     blink_status: BlinkStatus,
-    previous_cursor_shape: Option<CursorShape>,
+    previous_cursor_shape: Option<(CursorShape, f32, f32)>,
     previous_editor_mode: EditorMode,
+    current_editor_mode: EditorMode,
     cursor_vfx: Option<Box<dyn cursor_vfx::CursorVfx>>,
     previous_vfx_mode: cursor_vfx::VfxMode,
     window_has_focus: bool,
 }
 
+/// What's drawn for the character under the cursor.
+enum CursorGlyph<'a> {
+    Text(&'a [TextBlob]),
+    /// The character couldn't be shaped, so a box is drawn instead of leaving the cell empty
+    Missing,
+    Blank,
+}
+
+fn cursor_glyph(blank: bool, blobs: &[TextBlob]) -> CursorGlyph {
+    match (blank, blobs.is_empty()) {
+        (_, false) => CursorGlyph::Text(blobs),
+        (false, true) => CursorGlyph::Missing,
+        (true, true) => CursorGlyph::Blank,
+    }
+}
+
+/// A block cursor shows the character in reverse, so it's always clipped to the cursor. The thin
+/// bar and underline cursors can draw the whole character instead, so it stays readable.
+fn clips_character(settings: &CursorSettings, shape: &CursorShape) -> bool {
+    *shape == CursorShape::Block || !settings.unclipped_character
+}
+
+/// The color of the character drawn on the cursor. An unclipped character, and any character with
+/// `keep_character_color`, keeps the color of its cell instead of the cursor foreground.
+fn character_color(
+    settings: &CursorSettings,
+    clip: bool,
+    cursor: &Cursor,
+    default_colors: &Colors,
+    cursor_foreground: Color4f,
+) -> Color4f {
+    if !clip || settings.keep_character_color {
+        cursor.cell_colors(default_colors).0
+    } else {
+        cursor_foreground
+    }
+}
+
+/// The part of the cell covered by a bar or underline cursor, which is never thinner than
+/// `min_bar_width` pixels, so that small percentages don't end up in a sub-pixel bar.
+fn bar_percentage(cell_percentage: f32, cell_length: f32, min_bar_width: f32) -> f32 {
+    if cell_length <= 0.0 {
+        return cell_percentage;
+    }
+    cell_percentage.max(min_bar_width / cell_length).min(1.0)
+}
+
+/// The paints for the cursor block and for the character drawn on top of it. They are separate,
+/// so that the block can have pixel-crisp edges with smooth text, or the other way around. The
+/// glyphs themselves only follow the antialiasing of the text paint when they are shaped with
+/// `shape_aliased`, since their edging comes from the font.
+fn cursor_paints(settings: &CursorSettings, axis_aligned: bool) -> (Paint, Paint) {
+    // Optionally only the smeared quad is antialiased, and a resting block is drawn with the same
+    // crisp edges as the cell backgrounds
+    let crisp = settings.crisp_resting_edges && axis_aligned;
+    let mut block_paint = Paint::new(skia_safe::colors::WHITE, None);
+    block_paint.set_anti_alias(settings.antialiasing && !crisp);
+
+    let mut text_paint = Paint::new(skia_safe::colors::WHITE, None);
+    text_paint.set_anti_alias(settings.text_antialiasing);
+
+    (block_paint, text_paint)
+}
+
+fn missing_glyph_rect(position: PixelPos<f32>, cell_size: PixelSize<f32>) -> Rect {
+    let inset = cell_size.width * MISSING_GLYPH_INSET;
+    Rect::from_xywh(position.x, position.y, cell_size.width, cell_size.height)
+        .with_inset((inset, inset))
+}
+
+/// Outlines the box shown in place of a character that couldn't be shaped.
+fn draw_missing_glyph(
+    canvas: &Canvas,
+    paint: &Paint,
+    position: PixelPos<f32>,
+    cell_size: PixelSize<f32>,
+) {
+    let mut paint = paint.clone();
+    paint.set_style(Style::Stroke);
+    paint.set_stroke_width(1.0);
+    canvas.draw_rect(missing_glyph_rect(position, cell_size), &paint);
+}
+
 impl CursorRenderer {
     pub fn new() -> CursorRenderer {
         let mut renderer = CursorRenderer {
             corners: vec![Corner::new(); 4],
             cursor: Cursor::new(),
             destination: (0.0, 0.0).into(),
+            cell_region: None,
+            has_destination: false,
+            secondary_cursors: Vec::new(),
+            secondary_destinations: Vec::new(),
+            tab_span: None,
             blink_status: BlinkStatus::new(),
             previous_cursor_shape: None,
             previous_editor_mode: EditorMode::Normal,
+            current_editor_mode: EditorMode::Normal,
             cursor_vfx: None,
             previous_vfx_mode: cursor_vfx::VfxMode::Disabled,
             window_has_focus: true,
         };
-        renderer.set_cursor_shape(&CursorShape::Block, DEFAULT_CELL_PERCENTAGE);
+        renderer.set_cursor_shape(&CursorShape::Block, DEFAULT_CELL_PERCENTAGE, 0.0);
         renderer
     }
 
@@ -202,7 +429,34 @@ impl CursorRenderer {
         self.cursor = new_cursor;
     }
 
-    fn set_cursor_shape(&mut self, cursor_shape: &CursorShape, cell_percentage: f32) {
+    /// Sets the grid positions of the secondary cursors, relative to the window containing the
+    /// primary cursor. An empty list removes them.
+    pub fn set_secondary_cursors(&mut self, positions: Vec<GridPos<u64>>) {
+        self.secondary_cursors = positions;
+    }
+
+    /// Neovim puts the cursor on a single cell of an expanded tab, this is the whole tab.
+    pub fn set_tab_span(&mut self, tab_span: Option<(u64, u64)>) {
+        self.tab_span = tab_span;
+    }
+
+    fn covered_cells(&self, settings: &CursorSettings) -> (GridPos<u64>, u64) {
+        covered_cells(
+            settings.span_tabs && self.cursor.shape == CursorShape::Block,
+            self.cursor.grid_position.into(),
+            self.cursor.cell_width,
+            self.tab_span,
+        )
+    }
+
+    /// Sets the corners relative to the center of the cell. `bar_offset` moves the horizontal bar
+    /// up from the bottom of the cell, as a fraction of its height.
+    fn set_cursor_shape(
+        &mut self,
+        cursor_shape: &CursorShape,
+        cell_percentage: f32,
+        bar_offset: f32,
+    ) {
         self.corners = self
             .corners
             .clone()
@@ -221,7 +475,8 @@ impl CursorRenderer {
                         // so that the horizontal bar is at the bottom of the character space
                         // instead of the top.
                         CursorShape::Horizontal => {
-                            (x, -((-y + 0.5) * cell_percentage - 0.5)).into()
+                            let y = -((-y + 0.5) * cell_percentage - 0.5) - bar_offset;
+                            (x, y.max(-0.5)).into()
                         }
                     },
                     t: 0.0,
@@ -237,98 +492,159 @@ impl CursorRenderer {
         grid_scale: GridScale,
         windows: &HashMap<u64, RenderedWindow>,
     ) {
-        let cursor_grid_position = GridPos::<u64>::from(self.cursor.grid_position)
-            .try_cast()
-            .unwrap();
-        if let Some(window) = windows.get(&self.cursor.parent_window_id) {
-            let mut grid = cursor_grid_position + window.grid_current_position.to_vector();
-            grid.y -= window.scroll_animation.position;
-
-            let top_border = window.viewport_margins.top as f32;
-            let bottom_border = window.viewport_margins.bottom as f32;
-
-            // Prevent the cursor from targeting a position outside its current window. Since only
-            // the vertical direction is effected by scrolling, we only have to clamp the vertical
-            // grid position.
-            grid.y = grid.y.max(window.grid_current_position.y + top_border).min(
-                window.grid_current_position.y + window.grid_size.height as f32
-                    - 1.0
-                    - bottom_border,
-            );
-
-            self.destination = grid * grid_scale;
-        } else {
-            self.destination = cursor_grid_position * grid_scale;
+        let settings = SETTINGS.get::<CursorSettings>();
+        let (position, width) = self.covered_cells(&settings);
+        let window = windows.get(&self.cursor.parent_window_id);
+        // The position is relative to the grid, which might not have been positioned yet. Stay put
+        // until it is, instead of jumping to the same cell of the screen.
+        if let Some(window) = window {
+            self.destination = grid_destination(position, window, grid_scale);
         }
+        // Only a block covers the whole cell, bars are narrower than its background
+        self.cell_region = window
+            .filter(|window| {
+                self.cursor.shape == CursorShape::Block
+                    && window.scroll_animation.position == 0.0
+                    && window.horizontal_scroll_animation.position == 0.0
+            })
+            .map(|window| window.cell_region(grid_scale, position, width))
+            // The destination is clamped to the window, and isn't on the cell then
+            .filter(|region| (region.min - self.destination).length() < 0.5);
+        self.secondary_destinations = self
+            .secondary_cursors
+            .iter()
+            .filter_map(|position| Some(grid_destination(*position, window?, grid_scale)))
+            .collect();
     }
 
     pub fn prepare_frame(&mut self) -> ShouldRender {
-        self.blink_status.update_status(&self.cursor)
+        let blink = SETTINGS.get::<CursorSettings>().blink && !is_motion_reduced();
+        self.blink_status.update_status(&self.cursor, blink)
+    }
+
+    pub fn next_wake_time(&self) -> Option<Instant> {
+        self.blink_status.next_wake_time()
     }
 
     pub fn draw(&mut self, grid_renderer: &mut GridRenderer, canvas: &Canvas) {
         tracy_zone!("cursor_draw");
+        if self.is_busy() {
+            return;
+        }
+
         let settings = SETTINGS.get::<CursorSettings>();
-        let render = self.blink_status.should_render() || settings.smooth_blink;
-        let opacity = match settings.smooth_blink {
-            true => self.blink_status.opacity(),
-            false => 1.0,
-        };
+        let opacity = self.opacity(&settings);
         let alpha = self.cursor.alpha() as f32;
 
-        let mut paint = Paint::new(skia_safe::colors::WHITE, None);
-        paint.set_anti_alias(settings.antialiasing);
+        let (mut block_paint, mut text_paint) = cursor_paints(&settings, self.is_axis_aligned());
 
-        let character = self.cursor.grid_cell.0.clone();
+        // Secondary cursors don't blink, so they are drawn even when the primary one is hidden
+        if settings.multiple_cursors {
+            self.draw_secondary_cursors(grid_renderer, canvas, &block_paint);
+        }
 
-        if !(self.cursor.enabled && render) {
+        if !self.is_visible(&settings) {
             return;
         }
         // Draw Background
-        let background_color = self
-            .cursor
-            .background(&grid_renderer.default_style.colors)
-            .to_color()
+        let default_colors = &grid_renderer.default_style.colors;
+        let mut background = self
+            .mode_color(&settings)
+            .map(Color4f::from)
+            .unwrap_or_else(|| self.cursor.background(default_colors));
+        let mut foreground = self.cursor.character_foreground(default_colors);
+        if settings.force_contrast {
+            (background, foreground) =
+                contrasting_colors((background, foreground), &self.cursor, default_colors);
+        }
+        let background_color = grid_renderer
+            .display_color(background)
             .with_a((opacity * alpha) as u8);
-        paint.set_color(background_color);
+        block_paint.set_color(background_color);
 
         let path = if self.window_has_focus || self.cursor.shape != CursorShape::Block {
-            self.draw_rectangle(canvas, &paint)
+            self.draw_rectangle(canvas, &block_paint)
         } else {
-            let outline_width = settings.unfocused_outline_width * grid_renderer.em_size;
-            self.draw_rectangular_outline(canvas, &paint, outline_width)
+            let outline_width = outline_width(&settings, grid_renderer.em_size);
+            self.draw_rectangular_outline(canvas, &block_paint, outline_width)
         };
 
         // Draw foreground
-        let foreground_color = self
-            .cursor
-            .foreground(&grid_renderer.default_style.colors)
-            .to_color()
-            .with_a((opacity * alpha) as u8);
-        paint.set_color(foreground_color);
+        if settings.draw_character {
+            // An unclipped character is drawn over the bar in the colors of the cell itself
+            let clip = clips_character(&settings, &self.cursor.shape);
+            let foreground =
+                character_color(&settings, clip, &self.cursor, default_colors, foreground);
+            let foreground_color = grid_renderer
+                .display_color(foreground)
+                .with_a((opacity * alpha) as u8);
+            text_paint.set_color(foreground_color);
+            // The clip follows the edges of the block, so it's antialiased like the block
+            let clip_path = clip.then_some(&path);
+            let antialias = block_paint.is_anti_alias();
+            self.draw_character(grid_renderer, canvas, &text_paint, clip_path, antialias);
+        }
+
+        if let Some(vfx) = self.cursor_vfx.as_ref() {
+            vfx.render(&settings, canvas, grid_renderer, &self.cursor);
+        }
+    }
 
+    /// Draws the character under the cursor again on top of it, clipped to the cursor shape unless
+    /// `clip_path` is None.
+    fn draw_character(
+        &self,
+        grid_renderer: &mut GridRenderer,
+        canvas: &Canvas,
+        paint: &Paint,
+        clip_path: Option<&Path>,
+        antialias: bool,
+    ) {
         canvas.save();
-        canvas.clip_path(&path, None, Some(false));
+        if let Some(path) = clip_path {
+            canvas.clip_path(path, None, Some(antialias));
+        }
 
         let baseline_offset = grid_renderer.shaper.baseline_offset();
+        let character = self.cursor.grid_cell.0.clone();
         let style = &self.cursor.grid_cell.1;
         let coarse_style = style.as_ref().map(|style| style.into()).unwrap_or_default();
 
-        let blobs = &grid_renderer.shaper.shape_cached(character, coarse_style);
+        let blank = character.trim().is_empty();
+        let wide = self.cursor.cell_width > 1;
+        let aliased_blobs;
+        let blobs = if paint.is_anti_alias() {
+            grid_renderer
+                .shaper
+                .shape_cell_cached(character, coarse_style, wide)
+        } else {
+            aliased_blobs = grid_renderer
+                .shaper
+                .shape_aliased(character, coarse_style, wide);
+            &aliased_blobs
+        };
 
-        for blob in blobs.iter() {
-            canvas.draw_text_blob(
-                blob,
-                (self.destination.x, self.destination.y + baseline_offset),
-                &paint,
-            );
+        match cursor_glyph(blank, blobs) {
+            CursorGlyph::Text(blobs) => {
+                for blob in blobs {
+                    canvas.draw_text_blob(
+                        blob,
+                        (self.destination.x, self.destination.y + baseline_offset),
+                        paint,
+                    );
+                }
+            }
+            CursorGlyph::Missing => {
+                let cell_size = PixelSize::new(
+                    grid_renderer.grid_scale.width() * self.cursor.cell_width as f32,
+                    grid_renderer.grid_scale.height(),
+                );
+                draw_missing_glyph(canvas, paint, self.destination, cell_size);
+            }
+            CursorGlyph::Blank => {}
         }
 
         canvas.restore();
-
-        if let Some(vfx) = self.cursor_vfx.as_ref() {
-            vfx.render(&settings, canvas, grid_renderer, &self.cursor);
-        }
     }
 
     pub fn animate(
@@ -339,6 +655,7 @@ impl CursorRenderer {
     ) -> bool {
         tracy_zone!("cursor_animate");
         let settings = SETTINGS.get::<CursorSettings>();
+        self.current_editor_mode = current_mode.clone();
 
         if settings.vfx_mode != self.previous_vfx_mode {
             self.cursor_vfx = cursor_vfx::new_cursor_vfx(&settings.vfx_mode);
@@ -346,56 +663,69 @@ impl CursorRenderer {
         }
 
         let mut cursor_width = grid_renderer.grid_scale.width();
-        if self.cursor.double_width && self.cursor.shape == CursorShape::Block {
-            cursor_width *= 2.0;
+        if self.cursor.shape == CursorShape::Block {
+            cursor_width *= self.covered_cells(&settings).1 as f32;
         }
 
         let cursor_dimensions = PixelSize::new(cursor_width, grid_renderer.grid_scale.height());
 
-        let in_insert_mode = matches!(current_mode, EditorMode::Insert);
-
-        let changed_to_from_cmdline = !matches!(self.previous_editor_mode, EditorMode::CmdLine)
-            ^ matches!(current_mode, EditorMode::CmdLine);
-
         let center_destination = self.destination + cursor_dimensions.to_vector() * 0.5;
 
-        if self.previous_cursor_shape.as_ref() != Some(&self.cursor.shape) {
-            self.previous_cursor_shape = Some(self.cursor.shape.clone());
-            self.set_cursor_shape(
-                &self.cursor.shape.clone(),
-                self.cursor
-                    .cell_percentage
-                    .unwrap_or(DEFAULT_CELL_PERCENTAGE),
-            );
+        let cell_length = match self.cursor.shape {
+            CursorShape::Horizontal => grid_renderer.grid_scale.height(),
+            _ => grid_renderer.grid_scale.width(),
+        };
+        let cell_percentage = bar_percentage(
+            self.cursor
+                .cell_percentage
+                .unwrap_or(DEFAULT_CELL_PERCENTAGE),
+            cell_length,
+            settings.min_bar_width,
+        );
+        let bar_offset = horizontal_bar_offset(
+            grid_renderer.glyph_bottom,
+            grid_renderer.grid_scale.height(),
+        );
+        let cursor_shape = (self.cursor.shape.clone(), cell_percentage, bar_offset);
+        if self.previous_cursor_shape.as_ref() != Some(&cursor_shape) {
+            self.set_cursor_shape(&cursor_shape.0, cell_percentage, bar_offset);
+            self.previous_cursor_shape = Some(cursor_shape);
 
             if let Some(vfx) = self.cursor_vfx.as_mut() {
                 vfx.restart(center_destination);
             }
         }
 
+        if self.is_busy() {
+            // Nothing is drawn while Neovim is busy, so keep the corners on the destination
+            // instead of animating an invisible cursor, or smearing it once it's shown again
+            if center_destination != PixelPos::ZERO {
+                self.update_corners(&settings, cursor_dimensions, center_destination, dt, true);
+            }
+            self.previous_editor_mode = current_mode.clone();
+            return false;
+        }
+
         let mut animating = false;
 
         if center_destination != PixelPos::ZERO {
-            let immediate_movement = !settings.animate_in_insert_mode && in_insert_mode
-                || !settings.animate_command_line && !changed_to_from_cmdline;
-            for corner in self.corners.iter_mut() {
-                let corner_animating = corner.update(
-                    &settings,
-                    GridScale::new(cursor_dimensions),
-                    center_destination,
-                    dt,
-                    immediate_movement,
-                );
-
-                animating |= corner_animating;
-            }
+            let first_destination = !self.has_destination;
+            let immediate_movement =
+                is_motion_reduced() || !self.animates_in_mode(&settings, current_mode);
+            animating |= self.update_corners(
+                &settings,
+                cursor_dimensions,
+                center_destination,
+                dt,
+                immediate_movement,
+            );
 
             let vfx_animating = if let Some(vfx) = self.cursor_vfx.as_mut() {
                 vfx.update(
                     &settings,
                     center_destination,
                     cursor_dimensions,
-                    immediate_movement,
+                    immediate_movement || first_destination,
                     dt,
                 )
             } else {
@@ -416,6 +746,151 @@ impl CursorRenderer {
         animating
     }
 
+    /// Whether the cursor is animated in `mode`, it jumps to its destination otherwise.
+    fn animates_in_mode(&self, settings: &CursorSettings, mode: &EditorMode) -> bool {
+        let in_insert_mode = matches!(mode, EditorMode::Insert);
+        let changed_to_from_cmdline = !matches!(self.previous_editor_mode, EditorMode::CmdLine)
+            ^ matches!(mode, EditorMode::CmdLine);
+
+        !(!settings.animate_in_insert_mode && in_insert_mode
+            || !settings.animate_command_line && !changed_to_from_cmdline
+            || is_instant_mode(&settings.instant_modes, mode))
+    }
+
+    /// The color set for the current mode in the settings, which overrides the highlight of the
+    /// cursor
+    fn mode_color(&self, settings: &CursorSettings) -> Option<Color> {
+        match self.current_editor_mode {
+            EditorMode::Replace => parse_color_setting(&settings.replace_color),
+            _ => None,
+        }
+    }
+
+    /// Neovim hides the cursor between `busy_start` and `busy_stop`
+    fn is_busy(&self) -> bool {
+        !self.cursor.enabled
+    }
+
+    /// With smooth blink the cursor fades between fully visible and the blink off opacity.
+    fn opacity(&self, settings: &CursorSettings) -> f32 {
+        let off_opacity = settings.blink_off_opacity.clamp(0.0, 1.0);
+        match settings.smooth_blink {
+            true => off_opacity + (1.0 - off_opacity) * self.blink_status.opacity(),
+            false => self.blink_status.blink_opacity(off_opacity),
+        }
+    }
+
+    fn is_visible(&self, settings: &CursorSettings) -> bool {
+        !self.is_busy() && (settings.smooth_blink || self.opacity(settings) > 0.0)
+    }
+
+    fn is_axis_aligned(&self) -> bool {
+        let position = |i: usize| self.corners[i].current_position;
+        position(0).y == position(1).y
+            && position(1).x == position(2).x
+            && position(2).y == position(3).y
+            && position(3).x == position(0).x
+    }
+
+    fn update_corners(
+        &mut self,
+        settings: &CursorSettings,
+        cursor_dimensions: PixelSize<f32>,
+        center_destination: PixelPos<f32>,
+        dt: f32,
+        immediate_movement: bool,
+    ) -> bool {
+        // The corners start out at the origin, so snap them to the first known position instead
+        // of smearing the cursor across the whole screen on startup
+        let mut immediate_movement = immediate_movement
+            || !self.has_destination
+            || self.exceeds_max_animation_distance(settings, cursor_dimensions, center_destination);
+        self.has_destination = true;
+
+        // The animation is only chosen when the cursor moves, since the remaining distance shrinks
+        // while it's animating
+        let moved = self.corners[0].previous_destination != center_destination;
+        if settings.smart_animation && moved && !immediate_movement {
+            let distance = center_destination - self.current_center();
+            match smart_animation(settings, distance, cursor_dimensions) {
+                SmartAnimation::Snap => immediate_movement = true,
+                SmartAnimation::Smear => {}
+                SmartAnimation::Capped(offset) => {
+                    for corner in self.corners.iter_mut() {
+                        corner.current_position = corner.current_position + offset;
+                    }
+                }
+            }
+        }
+
+        let mut animating = false;
+        for corner in self.corners.iter_mut() {
+            animating |= corner.update(
+                settings,
+                GridScale::new(cursor_dimensions),
+                center_destination,
+                dt,
+                immediate_movement,
+            );
+        }
+        // Snap the corners that arrived exactly onto the cell, the animation and the offset from
+        // the center can be a fraction of a pixel off, which shows up as a seam against the
+        // background of the cell, like the cursorline
+        if let Some(cell_region) = self.cell_region {
+            for corner in self.corners.iter_mut().filter(|corner| corner.t >= 1.0) {
+                let resting_position = corner.resting_position(cell_region);
+                corner.settle(settings, resting_position);
+            }
+        }
+        animating
+    }
+
+    /// The center of the cursor as it's currently drawn, following the animation.
+    pub fn current_center(&self) -> PixelPos<f32> {
+        let corner_count = self.corners.len() as f32;
+        let current_center = self.corners.iter().fold(PixelVec::zero(), |sum, corner| {
+            sum + corner.current_position.to_vector()
+        }) / corner_count;
+        current_center.to_point()
+    }
+
+    // Long jumps are snapped instead of smearing the cursor over the whole distance
+    fn exceeds_max_animation_distance(
+        &self,
+        settings: &CursorSettings,
+        cursor_dimensions: PixelSize<f32>,
+        center_destination: PixelPos<f32>,
+    ) -> bool {
+        if settings.max_animation_distance <= 0.0 {
+            return false;
+        }
+
+        let distance = center_destination - self.current_center();
+        let cell_distance = PixelVec::new(
+            distance.x / cursor_dimensions.width,
+            distance.y / cursor_dimensions.height,
+        );
+        cell_distance.length() > settings.max_animation_distance
+    }
+
+    fn draw_secondary_cursors(&self, grid_renderer: &GridRenderer, canvas: &Canvas, paint: &Paint) {
+        let alpha = self.cursor.alpha() as f32 * SECONDARY_CURSOR_OPACITY;
+        let color = grid_renderer
+            .display_color(self.cursor.background(&grid_renderer.default_style.colors))
+            .with_a(alpha as u8);
+        let mut paint = paint.clone();
+        paint.set_color(color);
+
+        let cell_size = PixelSize::new(
+            grid_renderer.grid_scale.width(),
+            grid_renderer.grid_scale.height(),
+        );
+        for destination in &self.secondary_destinations {
+            let rect = PixelRect::from_origin_and_size(*destination, cell_size);
+            canvas.draw_rect(to_skia_rect(&rect), &paint);
+        }
+    }
+
     fn draw_rectangle(&self, canvas: &Canvas, paint: &Paint) -> Path {
         // The cursor is made up of four points, so I create a path with each of the four
         // corners.
@@ -431,7 +906,12 @@ impl CursorRenderer {
         path
     }
 
+    /// Strokes the outline of the cursor inside its corners, so that it doesn't draw over the
+    /// neighbouring cells. Returns the area covered by the outline.
     fn draw_rectangular_outline(&self, canvas: &Canvas, paint: &Paint, outline_width: f32) -> Path {
+        let size = self.corners[2].current_position - self.corners[0].current_position;
+        let outline_width = outline_width.min(size.x.abs().min(size.y.abs()) / 2.0);
+
         let mut rectangle = Path::new();
         rectangle.move_to(to_skia_point(self.corners[0].current_position));
         rectangle.line_to(to_skia_point(self.corners[1].current_position));
@@ -457,7 +937,18 @@ impl CursorRenderer {
         // from the larger one. This can fail in which case we return a full "rectangle".
         let path = op(&rectangle, &subtract, skia_safe::PathOp::Difference).unwrap_or(rectangle);
 
-        canvas.draw_path(&path, paint);
+        // The stroke is centered on the path, so it's drawn halfway between the two rectangles
+        let mut stroke = Path::new();
+        for (index, offset) in offsets.into_iter().enumerate() {
+            let point = to_skia_point(self.corners[index].current_position + offset * 0.5);
+            if index == 0 {
+                stroke.move_to(point);
+            } else {
+                stroke.line_to(point);
+            }
+        }
+        stroke.close();
+        canvas.draw_path(&stroke, &outline_paint(paint, outline_width));
         path
     }
 
@@ -465,3 +956,772 @@ impl CursorRenderer {
         self.destination
     }
 }
+
+/// Whether `mode` is in the comma-separated list of modes where the cursor doesn't animate. The
+/// modes are named like in the `mode_change` event, with `cmdline` for the command line.
+fn is_instant_mode(instant_modes: &str, mode: &EditorMode) -> bool {
+    let name = match mode {
+        EditorMode::Normal => "normal",
+        EditorMode::Insert => "insert",
+        EditorMode::Visual => "visual",
+        EditorMode::Replace => "replace",
+        EditorMode::CmdLine => "cmdline",
+        EditorMode::Unknown(name) => name.as_str(),
+    };
+    instant_modes
+        .split(',')
+        .map(str::trim)
+        .any(|instant_mode| instant_mode.eq_ignore_ascii_case(name))
+}
+
+/// The first cell the cursor covers, and how many cells wide it is. With `span_tabs` it covers the
+/// whole expanded tab it's on instead of only its own cell.
+fn covered_cells(
+    span_tabs: bool,
+    position: GridPos<u64>,
+    cell_width: u64,
+    tab_span: Option<(u64, u64)>,
+) -> (GridPos<u64>, u64) {
+    match tab_span {
+        Some((before, width)) if span_tabs && width > 1 => {
+            let first = GridPos::new(position.x.saturating_sub(before), position.y);
+            (first, width)
+        }
+        _ => (position, cell_width),
+    }
+}
+
+/// How far the horizontal bar sits above the bottom of the cell, as a fraction of its height. It
+/// sits on the bottom of the descenders like an underline of the glyphs, instead of on the
+/// linespace below them.
+fn horizontal_bar_offset(glyph_bottom: f32, cell_height: f32) -> f32 {
+    if cell_height <= 0.0 {
+        return 0.0;
+    }
+    (1.0 - glyph_bottom / cell_height).clamp(0.0, 1.0)
+}
+
+/// How wide the outline of the unfocused cursor is, in pixels.
+fn outline_width(settings: &CursorSettings, em_size: f32) -> f32 {
+    if settings.outline_width > 0.0 {
+        settings.outline_width
+    } else {
+        settings.unfocused_outline_width * em_size
+    }
+}
+
+fn outline_paint(paint: &Paint, outline_width: f32) -> Paint {
+    let mut paint = paint.clone();
+    paint.set_style(Style::Stroke);
+    paint.set_stroke_width(outline_width);
+    paint
+}
+
+fn luminance(color: Color4f) -> f32 {
+    0.2126 * color.r + 0.7152 * color.g + 0.0722 * color.b
+}
+
+/// The cursor is drawn after the windows, but it can still blend into the background of the cell
+/// under it, like a visual selection. In that case the colors of the cell are swapped instead, so
+/// it's drawn in reverse video. Takes and returns the background and foreground of the cursor.
+fn contrasting_colors(
+    (background, foreground): (Color4f, Color4f),
+    cursor: &Cursor,
+    default_colors: &Colors,
+) -> (Color4f, Color4f) {
+    let (cell_foreground, cell_background) = cursor.cell_colors(default_colors);
+
+    if (luminance(background) - luminance(cell_background)).abs() < MIN_CURSOR_CONTRAST {
+        (cell_foreground, cell_background)
+    } else {
+        (background, foreground)
+    }
+}
+
+/// Where a cell of the window is on the screen, offset by the window position.
+fn grid_destination(
+    grid_position: GridPos<u64>,
+    window: &RenderedWindow,
+    grid_scale: GridScale,
+) -> PixelPos<f32> {
+    let grid_position = grid_position.try_cast().unwrap();
+    let mut grid = grid_position + window.grid_current_position.to_vector();
+    grid.y -= window.scroll_animation.position;
+    grid.x -= window.horizontal_scroll_animation.position;
+
+    let top_border = window.viewport_margins.top as f32;
+    let bottom_border = window.viewport_margins.bottom as f32;
+
+    // Prevent the cursor from targeting a position outside its current window.
+    grid.x = grid
+        .x
+        .max(window.grid_current_position.x)
+        .min(window.grid_current_position.x + window.grid_size.width as f32 - 1.0);
+    grid.y = grid
+        .y
+        .max(window.grid_current_position.y + top_border)
+        .min(window.grid_current_position.y + window.grid_size.height as f32 - 1.0 - bottom_border);
+
+    grid * grid_scale
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{editor::Style, units::GridSize};
+    use std::sync::Arc;
+
+    // Animates the corners of a block cursor towards the destination and returns the number of
+    // frames each of them took to arrive
+    fn frames_until_arrival(settings: &CursorSettings, destination: PixelPos<f32>) -> Vec<usize> {
+        let cursor_dimensions = GridScale::new(PixelSize::new(10.0, 20.0));
+        let mut corners: Vec<Corner> = STANDARD_CORNERS
+            .iter()
+            .map(|&(x, y)| Corner {
+                relative_position: (x, y).into(),
+                current_position: PixelPos::new(x * 10.0, y * 20.0),
+                ..Corner::new()
+            })
+            .collect();
+
+        let mut arrivals = vec![None; corners.len()];
+        for frame in 1..10000 {
+            for (corner, arrival) in corners.iter_mut().zip(arrivals.iter_mut()) {
+                let animating =
+                    corner.update(settings, cursor_dimensions, destination, 1.0 / 60.0, false);
+                if animating && arrival.is_none() && (corner.t - 1.0).abs() < f32::EPSILON {
+                    *arrival = Some(frame);
+                }
+            }
+        }
+
+        for corner in &corners {
+            let corner_destination =
+                destination + (corner.relative_position * cursor_dimensions).to_vector();
+            assert!((corner.current_position - corner_destination).length() < 0.001);
+        }
+        arrivals
+            .into_iter()
+            .map(|arrival| arrival.expect("corner never arrived"))
+            .collect()
+    }
+
+    #[test]
+    fn thin_bars_are_clamped_to_the_minimum_width() {
+        // A 1% bar in a 20 pixel wide cell would be a fifth of a pixel
+        assert_eq!(bar_percentage(0.01, 20.0, 1.0), 0.05);
+        assert_eq!(bar_percentage(0.25, 20.0, 1.0), 0.25);
+        assert_eq!(bar_percentage(0.01, 20.0, 0.0), 0.01);
+        // Even very large minimums don't go past the whole cell
+        assert_eq!(bar_percentage(0.25, 20.0, 100.0), 1.0);
+    }
+
+    #[test]
+    fn unshapeable_characters_draw_a_missing_glyph_box() {
+        assert!(matches!(cursor_glyph(false, &[]), CursorGlyph::Missing));
+        assert!(matches!(cursor_glyph(true, &[]), CursorGlyph::Blank));
+
+        let rect = missing_glyph_rect(PixelPos::new(80.0, 40.0), PixelSize::new(8.0, 20.0));
+        assert_eq!(rect, Rect::new(81.0, 41.0, 87.0, 59.0));
+
+        let mut surface = skia_safe::surfaces::raster_n32_premul((20, 30)).unwrap();
+        surface.canvas().clear(Color::BLACK);
+        let mut paint = Paint::new(skia_safe::colors::WHITE, None);
+        paint.set_anti_alias(false);
+        draw_missing_glyph(
+            surface.canvas(),
+            &paint,
+            PixelPos::new(4.0, 5.0),
+            PixelSize::new(8.0, 20.0),
+        );
+        let pixels = surface.peek_pixels().unwrap();
+        let painted: Vec<(i32, i32)> = (0..20)
+            .flat_map(|x| (0..30).map(move |y| (x, y)))
+            .filter(|&position| pixels.get_color(position) != Color::BLACK)
+            .collect();
+
+        // The outline of the box inset into the cell at (5, 6) - (11, 24), with the one pixel
+        // stroke landing on either side of the edges
+        let xs = painted.iter().map(|(x, _)| *x);
+        let ys = painted.iter().map(|(_, y)| *y);
+        assert!([4, 5].contains(&xs.clone().min().unwrap()));
+        assert!([10, 11].contains(&xs.max().unwrap()));
+        assert!([5, 6].contains(&ys.clone().min().unwrap()));
+        assert!([23, 24].contains(&ys.max().unwrap()));
+        // Only the outline is drawn
+        assert!(!painted.contains(&(8, 15)));
+    }
+
+    #[test]
+    fn leading_corners_arrive_before_trailing_corners() {
+        let settings = CursorSettings {
+            trail_intensity: 2.0,
+            ..CursorSettings::default()
+        };
+        let arrivals = frames_until_arrival(&settings, PixelPos::new(500.0, 0.0));
+
+        // The right corners lead when moving to the right
+        assert!(arrivals[1] < arrivals[0]);
+        assert!(arrivals[2] < arrivals[3]);
+    }
+
+    #[test]
+    fn trail_intensity_makes_trailing_corners_lag_further() {
+        let destination = PixelPos::new(500.0, 0.0);
+        let normal = frames_until_arrival(&CursorSettings::default(), destination);
+        let intense = frames_until_arrival(
+            &CursorSettings {
+                trail_intensity: 3.0,
+                ..CursorSettings::default()
+            },
+            destination,
+        );
+
+        assert!(intense[0] > normal[0]);
+        assert!(intense[3] > normal[3]);
+    }
+
+    #[test]
+    fn corners_converge_with_a_full_trail() {
+        let settings = CursorSettings {
+            trail_size: 1.0,
+            trail_intensity: 5.0,
+            ..CursorSettings::default()
+        };
+        // Every corner arriving is checked by the helper
+        frames_until_arrival(&settings, PixelPos::new(300.0, 300.0));
+    }
+
+    #[test]
+    fn resting_corners_snap_to_whole_pixels() {
+        let settings = CursorSettings {
+            snap_to_pixels: true,
+            ..CursorSettings::default()
+        };
+        let cursor_dimensions = GridScale::new(PixelSize::new(8.4, 17.3));
+        let destination = PixelPos::new(100.1, 50.6);
+        let mut corner = Corner {
+            relative_position: (0.5, 0.5).into(),
+            ..Corner::new()
+        };
+
+        let mut frames = 0;
+        while corner.update(&settings, cursor_dimensions, destination, 1.0 / 60.0, false) {
+            frames += 1;
+            assert!(frames < 1000, "the corner never came to rest");
+        }
+        let position = corner.current_position;
+        assert_eq!(position, position.round());
+        assert_eq!(position, PixelPos::new(104.0, 59.0));
+    }
+
+    #[test]
+    fn first_destination_snaps_the_corners() {
+        let settings = CursorSettings::default();
+        let cursor_dimensions = PixelSize::new(10.0, 20.0);
+        let mut cursor_renderer = CursorRenderer::new();
+
+        let destination = PixelPos::new(205.0, 310.0);
+        cursor_renderer.update_corners(&settings, cursor_dimensions, destination, 0.01, false);
+        for corner in &cursor_renderer.corners {
+            let corner_destination = destination
+                + (corner.relative_position * GridScale::new(cursor_dimensions)).to_vector();
+            assert_eq!(corner.current_position, corner_destination);
+        }
+
+        // Later moves are animated as usual
+        let destination = PixelPos::new(405.0, 310.0);
+        assert!(cursor_renderer.update_corners(
+            &settings,
+            cursor_dimensions,
+            destination,
+            0.01,
+            false
+        ));
+        assert!(cursor_renderer.corners[0].current_position.x < 400.0);
+    }
+
+    #[test]
+    fn insert_mode_snaps_while_normal_mode_animates() {
+        let settings = CursorSettings {
+            instant_modes: "insert, replace".to_string(),
+            ..CursorSettings::default()
+        };
+        let cursor_dimensions = PixelSize::new(10.0, 20.0);
+        let mut cursor_renderer = CursorRenderer::new();
+        cursor_renderer.update_corners(
+            &settings,
+            cursor_dimensions,
+            PixelPos::new(5.0, 10.0),
+            0.01,
+            false,
+        );
+
+        let mut move_in_mode = |mode: EditorMode, destination: PixelPos<f32>| {
+            let immediate = !cursor_renderer.animates_in_mode(&settings, &mode);
+            cursor_renderer.update_corners(
+                &settings,
+                cursor_dimensions,
+                destination,
+                0.01,
+                immediate,
+            );
+            cursor_renderer.current_center()
+        };
+
+        // Normal mode smears towards the destination
+        let destination = PixelPos::new(205.0, 10.0);
+        assert!(move_in_mode(EditorMode::Normal, destination).x < 200.0);
+
+        let destination = PixelPos::new(405.0, 10.0);
+        assert_eq!(move_in_mode(EditorMode::Insert, destination), destination);
+
+        assert!(is_instant_mode(
+            &settings.instant_modes,
+            &EditorMode::Replace
+        ));
+        assert!(!is_instant_mode(
+            &settings.instant_modes,
+            &EditorMode::Visual
+        ));
+        assert!(!is_instant_mode("", &EditorMode::Normal));
+    }
+
+    #[test]
+    fn replace_color_only_applies_in_replace_mode() {
+        let settings = CursorSettings {
+            replace_color: "#ff0000".to_string(),
+            ..Default::default()
+        };
+        let mut cursor_renderer = CursorRenderer::new();
+        assert_eq!(cursor_renderer.mode_color(&settings), None);
+
+        cursor_renderer.current_editor_mode = EditorMode::Replace;
+        assert_eq!(
+            cursor_renderer.mode_color(&settings),
+            Some(Color::from_rgb(255, 0, 0))
+        );
+        assert_eq!(cursor_renderer.mode_color(&CursorSettings::default()), None);
+    }
+
+    #[test]
+    fn busy_cursor_is_not_drawn() {
+        let settings = CursorSettings::default();
+        let mut cursor_renderer = CursorRenderer::new();
+        assert!(cursor_renderer.is_visible(&settings));
+
+        cursor_renderer.update_cursor(Cursor {
+            enabled: false,
+            ..Cursor::new()
+        });
+        assert!(!cursor_renderer.is_visible(&settings));
+
+        cursor_renderer.update_cursor(Cursor::new());
+        assert!(cursor_renderer.is_visible(&settings));
+    }
+
+    #[test]
+    fn only_the_smeared_cursor_is_not_axis_aligned() {
+        let settings = CursorSettings::default();
+        let cursor_dimensions = PixelSize::new(10.0, 20.0);
+        let mut cursor_renderer = CursorRenderer::new();
+
+        cursor_renderer.update_corners(
+            &settings,
+            cursor_dimensions,
+            PixelPos::new(105.0, 110.0),
+            0.01,
+            false,
+        );
+        assert!(cursor_renderer.is_axis_aligned());
+
+        // Moving diagonally skews the quad
+        cursor_renderer.update_corners(
+            &settings,
+            cursor_dimensions,
+            PixelPos::new(305.0, 310.0),
+            0.01,
+            false,
+        );
+        assert!(!cursor_renderer.is_axis_aligned());
+    }
+
+    #[test]
+    fn jumps_beyond_the_max_animation_distance_snap() {
+        let settings = CursorSettings {
+            max_animation_distance: 10.0,
+            ..CursorSettings::default()
+        };
+        let cursor_dimensions = PixelSize::new(10.0, 20.0);
+        let mut cursor_renderer = CursorRenderer::new();
+        cursor_renderer.update_corners(
+            &settings,
+            cursor_dimensions,
+            PixelPos::new(105.0, 110.0),
+            0.01,
+            false,
+        );
+
+        // Five cells to the right animates
+        cursor_renderer.update_corners(
+            &settings,
+            cursor_dimensions,
+            PixelPos::new(155.0, 110.0),
+            0.01,
+            false,
+        );
+        assert!(cursor_renderer.corners[0].current_position.x < 150.0);
+
+        // Twenty lines down snaps
+        let destination = PixelPos::new(155.0, 510.0);
+        cursor_renderer.update_corners(&settings, cursor_dimensions, destination, 0.01, false);
+        assert_eq!(
+            cursor_renderer.corners[0].current_position,
+            PixelPos::new(150.0, 500.0)
+        );
+    }
+
+    #[test]
+    fn unclipped_character_is_only_drawn_over_thin_cursors() {
+        let settings = CursorSettings::default();
+        assert!(clips_character(&settings, &CursorShape::Vertical));
+
+        let settings = CursorSettings {
+            unclipped_character: true,
+            ..CursorSettings::default()
+        };
+        assert!(!clips_character(&settings, &CursorShape::Vertical));
+        assert!(!clips_character(&settings, &CursorShape::Horizontal));
+        assert!(clips_character(&settings, &CursorShape::Block));
+    }
+
+    #[test]
+    fn smart_animation_depends_on_the_distance() {
+        let settings = CursorSettings {
+            smart_animation: true,
+            smart_min_distance: 2.0,
+            smart_max_distance: 10.0,
+            ..CursorSettings::default()
+        };
+        let cursor_dimensions = PixelSize::new(10.0, 20.0);
+
+        // One cell to the right
+        let animation = smart_animation(&settings, PixelVec::new(10.0, 0.0), cursor_dimensions);
+        assert_eq!(animation, SmartAnimation::Snap);
+
+        // Five lines down
+        let animation = smart_animation(&settings, PixelVec::new(0.0, 100.0), cursor_dimensions);
+        assert_eq!(animation, SmartAnimation::Smear);
+
+        // Only the last ten of the forty lines are smeared
+        let animation = smart_animation(&settings, PixelVec::new(0.0, 800.0), cursor_dimensions);
+        assert_eq!(animation, SmartAnimation::Capped(PixelVec::new(0.0, 600.0)));
+    }
+
+    #[test]
+    fn smart_animation_caps_long_smears() {
+        let settings = CursorSettings {
+            smart_animation: true,
+            smart_min_distance: 2.0,
+            smart_max_distance: 10.0,
+            ..CursorSettings::default()
+        };
+        let cursor_dimensions = PixelSize::new(10.0, 20.0);
+        let mut cursor_renderer = CursorRenderer::new();
+        cursor_renderer.update_corners(
+            &settings,
+            cursor_dimensions,
+            PixelPos::new(105.0, 110.0),
+            0.01,
+            false,
+        );
+
+        // A single cell snaps
+        let destination = PixelPos::new(115.0, 110.0);
+        cursor_renderer.update_corners(&settings, cursor_dimensions, destination, 0.01, false);
+        assert_eq!(
+            cursor_renderer.corners[0].current_position,
+            PixelPos::new(110.0, 100.0)
+        );
+
+        // Forty lines down starts animating from ten lines above the destination
+        let destination = PixelPos::new(115.0, 910.0);
+        cursor_renderer.update_corners(&settings, cursor_dimensions, destination, 0.0, false);
+        assert_eq!(
+            cursor_renderer.corners[0].start_position,
+            PixelPos::new(110.0, 700.0)
+        );
+    }
+
+    #[test]
+    fn secondary_cursors_follow_the_primary_cursor_window() {
+        let grid_scale = GridScale::new(PixelSize::new(10.0, 20.0));
+        let mut windows = HashMap::new();
+        windows.insert(
+            2,
+            RenderedWindow::new(2, GridPos::new(5, 3), GridSize::new(40, 10)),
+        );
+
+        let mut cursor_renderer = CursorRenderer::new();
+        cursor_renderer.cursor.parent_window_id = 2;
+        cursor_renderer.set_secondary_cursors(vec![GridPos::new(1, 1), GridPos::new(4, 2)]);
+        cursor_renderer.update_cursor_destination(grid_scale, &windows);
+
+        assert_eq!(
+            cursor_renderer.secondary_destinations,
+            vec![PixelPos::new(60.0, 80.0), PixelPos::new(90.0, 100.0)]
+        );
+
+        cursor_renderer.set_secondary_cursors(Vec::new());
+        cursor_renderer.update_cursor_destination(grid_scale, &windows);
+        assert!(cursor_renderer.secondary_destinations.is_empty());
+    }
+
+    #[test]
+    fn resting_cursor_covers_the_background_of_its_cell() {
+        // A font size that doesn't fall on whole pixels, the cursorline background is drawn over
+        // the whole line and the cursor on top of it
+        let grid_scale = GridScale::new(PixelSize::new(7.3, 17.7));
+        let mut windows = HashMap::new();
+        windows.insert(
+            2,
+            RenderedWindow::new(2, GridPos::new(5, 3), GridSize::new(40, 10)),
+        );
+
+        let mut cursor_renderer = CursorRenderer::new();
+        cursor_renderer.cursor.parent_window_id = 2;
+        cursor_renderer.cursor.grid_position = (9, 7);
+        cursor_renderer.update_cursor_destination(grid_scale, &windows);
+
+        let cursor_dimensions = PixelSize::new(grid_scale.width(), grid_scale.height());
+        let center = cursor_renderer.destination + cursor_dimensions.to_vector() * 0.5;
+        let settings = CursorSettings::default();
+        cursor_renderer.update_corners(&settings, cursor_dimensions, center, 0.0, true);
+
+        // The line background is shifted to the window and drawn from the cell position
+        let window_region = windows[&2].pixel_region(grid_scale);
+        let line_top = window_region.min.y + 7.0 * grid_scale.height();
+        let cell_left = 9.0 * grid_scale.width();
+        let background = Rect::new(
+            cell_left + window_region.min.x,
+            line_top,
+            cell_left + grid_scale.width() + window_region.min.x,
+            line_top + grid_scale.height(),
+        );
+
+        let corners = &cursor_renderer.corners;
+        assert_eq!(to_skia_point(corners[0].current_position), background.tl());
+        assert_eq!(to_skia_point(corners[2].current_position), background.br());
+        assert!(cursor_renderer.is_axis_aligned());
+    }
+
+    #[test]
+    fn cursor_is_positioned_relative_to_its_grid() {
+        let grid_scale = GridScale::new(PixelSize::new(8.0, 16.0));
+        let mut windows = HashMap::new();
+        windows.insert(
+            1,
+            RenderedWindow::new(1, GridPos::new(0, 0), GridSize::new(100, 40)),
+        );
+        windows.insert(
+            3,
+            RenderedWindow::new(3, GridPos::new(10, 4), GridSize::new(20, 5)),
+        );
+
+        let mut cursor_renderer = CursorRenderer::new();
+        cursor_renderer.cursor.parent_window_id = 3;
+        cursor_renderer.cursor.grid_position = (2, 1);
+        cursor_renderer.update_cursor_destination(grid_scale, &windows);
+        assert_eq!(
+            cursor_renderer.destination,
+            PixelPos::new(12.0 * 8.0, 5.0 * 16.0)
+        );
+
+        // A grid that isn't known yet doesn't send the cursor to the same cell of the screen
+        cursor_renderer.cursor.parent_window_id = 4;
+        cursor_renderer.cursor.grid_position = (0, 0);
+        cursor_renderer.update_cursor_destination(grid_scale, &windows);
+        assert_eq!(
+            cursor_renderer.destination,
+            PixelPos::new(12.0 * 8.0, 5.0 * 16.0)
+        );
+    }
+
+    #[test]
+    fn cursor_can_span_a_whole_expanded_tab() {
+        // A tab from column 4 to 7, with the cursor on its last cell like in normal mode
+        let position = GridPos::new(7, 2);
+        let tab_span = Some((3, 4));
+
+        assert_eq!(
+            covered_cells(true, position, 1, tab_span),
+            (GridPos::new(4, 2), 4)
+        );
+        assert_eq!(covered_cells(false, position, 1, tab_span), (position, 1));
+
+        // Off the tab, only the cell itself is covered, with its own width
+        assert_eq!(covered_cells(true, position, 2, None), (position, 2));
+        assert_eq!(
+            covered_cells(true, position, 1, Some((0, 1))),
+            (position, 1)
+        );
+    }
+
+    #[test]
+    fn horizontal_bar_sits_on_the_bottom_of_the_glyphs() {
+        // A font with an unusually large descent of 8 under an ascent of 10, and 6 pixels of
+        // linespace split above and below the text
+        let (ascent, descent, linespace) = (10.0, 8.0, 6.0);
+        let cell_height = ascent + descent + linespace;
+        let glyph_bottom = ascent + linespace / 2.0 + descent;
+        let bar_offset = horizontal_bar_offset(glyph_bottom, cell_height);
+        assert_eq!(bar_offset, 0.125);
+
+        let mut cursor_renderer = CursorRenderer::new();
+        cursor_renderer.set_cursor_shape(&CursorShape::Horizontal, 0.25, bar_offset);
+        let bottom = cursor_renderer.corners[2].relative_position.y;
+        let top = cursor_renderer.corners[0].relative_position.y;
+        assert_eq!((bottom + 0.5) * cell_height, glyph_bottom);
+        assert_eq!((bottom - top) * cell_height, 0.25 * cell_height);
+
+        // Without spacing below the text the bar stays on the bottom of the cell
+        assert_eq!(horizontal_bar_offset(cell_height, cell_height), 0.0);
+    }
+
+    #[test]
+    fn outline_is_stroked_with_the_configured_width() {
+        let em_size = 16.0;
+        let default = CursorSettings::default();
+        assert_eq!(outline_width(&default, em_size), 2.0);
+
+        let settings = CursorSettings {
+            outline_width: 3.0,
+            ..Default::default()
+        };
+        let width = outline_width(&settings, em_size);
+        assert_eq!(width, 3.0);
+        let paint = outline_paint(&Paint::default(), width);
+        assert_eq!(paint.stroke_width(), 3.0);
+        assert_eq!(paint.style(), skia_safe::paint::Style::Stroke);
+    }
+
+    #[test]
+    fn block_and_text_paints_have_independent_antialiasing() {
+        let settings = CursorSettings {
+            antialiasing: false,
+            text_antialiasing: true,
+            ..Default::default()
+        };
+        let (block_paint, text_paint) = cursor_paints(&settings, false);
+        assert!(!block_paint.is_anti_alias());
+        assert!(text_paint.is_anti_alias());
+
+        let settings = CursorSettings {
+            antialiasing: true,
+            text_antialiasing: false,
+            ..Default::default()
+        };
+        let (block_paint, text_paint) = cursor_paints(&settings, false);
+        assert!(block_paint.is_anti_alias());
+        assert!(!text_paint.is_anti_alias());
+
+        // A resting block is only drawn with crisp edges when asked for, without affecting the
+        // text on it
+        let (block_paint, _) = cursor_paints(&CursorSettings::default(), true);
+        assert!(block_paint.is_anti_alias());
+        let settings = CursorSettings {
+            crisp_resting_edges: true,
+            ..Default::default()
+        };
+        let (block_paint, text_paint) = cursor_paints(&settings, true);
+        assert!(!block_paint.is_anti_alias());
+        assert!(text_paint.is_anti_alias());
+        let (block_paint, _) = cursor_paints(&settings, false);
+        assert!(block_paint.is_anti_alias());
+    }
+
+    // Draws the blobs in white on black and counts the pixels that are only partially covered
+    fn partially_covered_pixels(blobs: &[skia_safe::TextBlob], paint: &Paint) -> usize {
+        let mut surface = skia_safe::surfaces::raster_n32_premul((40, 40)).unwrap();
+        surface.canvas().clear(skia_safe::Color::BLACK);
+        for blob in blobs {
+            surface.canvas().draw_text_blob(blob, (5.0, 30.0), paint);
+        }
+        let pixels = surface.peek_pixels().unwrap();
+        (0..40)
+            .flat_map(|x| (0..40).map(move |y| (x, y)))
+            .filter(|&position| !matches!(pixels.get_color(position).r(), 0 | 255))
+            .count()
+    }
+
+    #[test]
+    fn cursor_text_antialiasing_changes_the_drawn_glyphs() {
+        let mut shaper = crate::renderer::fonts::caching_shaper::CachingShaper::new(1.0);
+        let settings = CursorSettings {
+            text_antialiasing: false,
+            ..Default::default()
+        };
+        let (_, aliased_paint) = cursor_paints(&settings, true);
+        let (_, smooth_paint) = cursor_paints(&CursorSettings::default(), true);
+
+        let smooth = shaper
+            .shape_cached("@".to_string(), Default::default())
+            .clone();
+        assert!(partially_covered_pixels(&smooth, &smooth_paint) > 0);
+        // The paint alone doesn't turn the antialiasing of the glyphs off
+        assert!(partially_covered_pixels(&smooth, &aliased_paint) > 0);
+
+        let aliased = shaper.shape_aliased("@".to_string(), Default::default(), false);
+        assert!(!aliased.is_empty());
+        assert_eq!(partially_covered_pixels(&aliased, &aliased_paint), 0);
+    }
+
+    #[test]
+    fn kept_character_color_is_the_color_of_the_cell() {
+        let white = Color4f::new(1.0, 1.0, 1.0, 1.0);
+        let black = Color4f::new(0.0, 0.0, 0.0, 1.0);
+        let red = Color4f::new(1.0, 0.0, 0.0, 1.0);
+        let default_colors = Colors::new(Some(white), Some(black), None);
+
+        let mut cursor = Cursor::new();
+        let keyword = Style::new(Colors::new(Some(red), None, None));
+        cursor.grid_cell = ("a".to_string(), Some(Arc::new(keyword)));
+
+        // By default the clipped character is inverted by the cursor
+        let settings = CursorSettings::default();
+        let color = character_color(&settings, true, &cursor, &default_colors, black);
+        assert_eq!(color, black);
+
+        let settings = CursorSettings {
+            keep_character_color: true,
+            ..Default::default()
+        };
+        let color = character_color(&settings, true, &cursor, &default_colors, black);
+        assert_eq!(color, red);
+    }
+
+    #[test]
+    fn cursor_over_a_selection_is_drawn_in_reverse() {
+        let white = Color4f::new(1.0, 1.0, 1.0, 1.0);
+        let black = Color4f::new(0.0, 0.0, 0.0, 1.0);
+        let selection = Color4f::new(0.9, 0.9, 0.9, 1.0);
+        let default_colors = Colors::new(Some(white), Some(black), None);
+
+        let selected = Style::new(Colors::new(Some(black), Some(selection), None));
+        let mut cursor = Cursor::new();
+        cursor.grid_cell = ("a".to_string(), Some(Arc::new(selected)));
+
+        // The default white cursor disappears in the light selection
+        assert_eq!(
+            contrasting_colors((white, black), &cursor, &default_colors),
+            (black, selection)
+        );
+
+        // But it's kept over the dark default background
+        cursor.grid_cell = ("a".to_string(), None);
+        assert_eq!(
+            contrasting_colors((white, black), &cursor, &default_colors),
+            (white, black)
+        );
+    }
+}
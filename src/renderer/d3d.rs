@@ -1,3 +1,4 @@
+use log::{info, warn};
 use skia_safe::{
     gpu::{
         d3d::{BackendContext, TextureResourceInfo},
@@ -23,10 +24,10 @@ use windows::Win32::Graphics::Dxgi::Common::{
     DXGI_SAMPLE_DESC,
 };
 use windows::Win32::Graphics::Dxgi::{
-    CreateDXGIFactory1, IDXGIAdapter1, IDXGIFactory2, IDXGISwapChain1, IDXGISwapChain3,
-    DXGI_ADAPTER_FLAG, DXGI_ADAPTER_FLAG_SOFTWARE, DXGI_SCALING_STRETCH, DXGI_SWAP_CHAIN_DESC1,
-    DXGI_SWAP_CHAIN_FLAG_FRAME_LATENCY_WAITABLE_OBJECT, DXGI_SWAP_EFFECT_FLIP_SEQUENTIAL,
-    DXGI_USAGE_RENDER_TARGET_OUTPUT,
+    CreateDXGIFactory1, IDXGIAdapter1, IDXGIFactory2, IDXGIFactory4, IDXGISwapChain1,
+    IDXGISwapChain3, DXGI_ADAPTER_FLAG, DXGI_ADAPTER_FLAG_SOFTWARE, DXGI_SCALING_STRETCH,
+    DXGI_SWAP_CHAIN_DESC1, DXGI_SWAP_CHAIN_FLAG_FRAME_LATENCY_WAITABLE_OBJECT,
+    DXGI_SWAP_EFFECT_FLIP_SEQUENTIAL, DXGI_USAGE_RENDER_TARGET_OUTPUT,
 };
 #[cfg(feature = "d3d_debug")]
 use windows::Win32::Graphics::{
@@ -44,11 +45,54 @@ use super::{vsync::VSyncWinSwapChain, RendererSettings, SkiaRenderer, VSync};
 #[cfg(feature = "gpu_profiling")]
 use crate::profiling::{d3d::create_d3d_gpu_context, GpuCtx};
 use crate::{
+    cmd_line::CmdLineSettings,
     profiling::{tracy_gpu_zone, tracy_zone},
     settings::SETTINGS,
     window::UserEvent,
 };
 
+/// Whether there is an adapter that supports Direct3D 12, either a hardware one or the WARP
+/// software rasterizer
+pub fn is_available() -> bool {
+    let factory: Result<IDXGIFactory2> = unsafe { CreateDXGIFactory1() };
+    factory
+        .and_then(|factory| get_hardware_adapter(&factory).or_else(|_| get_warp_adapter(&factory)))
+        .is_ok()
+}
+
+/// The WARP adapter renders on the CPU, it's used when there's no usable GPU or when software
+/// rendering is forced
+fn get_warp_adapter(factory: &IDXGIFactory2) -> Result<IDXGIAdapter1> {
+    tracy_zone!("get_warp_adapter");
+    let factory: IDXGIFactory4 = factory.cast()?;
+    let adapter: IDXGIAdapter1 = unsafe { factory.EnumWarpAdapter()? };
+    unsafe {
+        D3D12CreateDevice(
+            &adapter,
+            D3D_FEATURE_LEVEL_11_0,
+            &mut Option::<ID3D12Device>::None,
+        )?;
+    }
+    Ok(adapter)
+}
+
+fn get_adapter(factory: &IDXGIFactory2) -> IDXGIAdapter1 {
+    if !SETTINGS.get::<CmdLineSettings>().software_render {
+        match get_hardware_adapter(factory) {
+            Ok(adapter) => {
+                info!("Rendering with hardware accelerated Direct3D");
+                return adapter;
+            }
+            Err(error) => warn!(
+                "Failed to find a hardware Direct3D 12 adapter, falling back to software rendering: {error}"
+            ),
+        }
+    }
+    let adapter = get_warp_adapter(factory).expect("Failed to create the WARP Direct3D 12 adapter");
+    info!("Rendering with the WARP software Direct3D rasterizer");
+    adapter
+}
+
 fn get_hardware_adapter(factory: &IDXGIFactory2) -> Result<IDXGIAdapter1> {
     tracy_zone!("get_hardware_adapter");
     for i in 0.. {
@@ -123,8 +167,7 @@ impl D3DSkiaRenderer {
         let dxgi_factory: IDXGIFactory2 =
             unsafe { CreateDXGIFactory1().expect("Failed to create DXGI factory") };
 
-        let adapter = get_hardware_adapter(&dxgi_factory)
-            .expect("Failed to find any suitable Direct3D 12 adapters");
+        let adapter = get_adapter(&dxgi_factory);
 
         let mut device: Option<ID3D12Device> = None;
         unsafe {
@@ -381,6 +424,10 @@ impl D3DSkiaRenderer {
 }
 
 impl SkiaRenderer for D3DSkiaRenderer {
+    fn name(&self) -> &'static str {
+        "d3d"
+    }
+
     fn window(&self) -> &Window {
         &self.window
     }
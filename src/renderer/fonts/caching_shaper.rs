@@ -1,9 +1,10 @@
-use std::{num::NonZeroUsize, sync::Arc};
+use std::{mem, num::NonZeroUsize, sync::Arc};
 
 use itertools::Itertools;
 use log::{debug, error, info, trace};
 use lru::LruCache;
-use skia_safe::{graphics::set_font_cache_limit, TextBlob, TextBlobBuilder};
+use rmpv::Value;
+use skia_safe::{font::Edging, graphics::set_font_cache_limit, TextBlob, TextBlobBuilder};
 use swash::{
     shape::ShapeContext,
     text::{
@@ -18,25 +19,138 @@ use crate::{
     error_msg,
     profiling::tracy_zone,
     renderer::fonts::{font_loader::*, font_options::*},
+    settings::ParseFromValue,
     units::PixelSize,
 };
 
+/// Where the text goes vertically in the cell, which only makes a difference when the cell is
+/// taller than the font because of `linespace`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GlyphAlignment {
+    /// The linespace goes below the text
+    Top,
+    /// The capital letters are centered in the cell
+    Center,
+    /// The baseline comes from the font metrics, with the linespace split above and below
+    Baseline,
+}
+
+impl ParseFromValue for GlyphAlignment {
+    fn parse_from_value(&mut self, value: Value) {
+        if value.is_str() {
+            *self = match value.as_str().unwrap() {
+                "top" => GlyphAlignment::Top,
+                "center" => GlyphAlignment::Center,
+                "baseline" => GlyphAlignment::Baseline,
+                value => {
+                    error!("Setting GlyphAlignment expected one of `top`, `center`, or `baseline`, but received {:?}", value);
+                    return;
+                }
+            };
+        } else {
+            error!(
+                "Setting GlyphAlignment expected string, but received {:?}",
+                value
+            );
+        }
+    }
+}
+
+impl From<GlyphAlignment> for Value {
+    fn from(alignment: GlyphAlignment) -> Self {
+        match alignment {
+            GlyphAlignment::Top => Value::from("top"),
+            GlyphAlignment::Center => Value::from("center"),
+            GlyphAlignment::Baseline => Value::from("baseline"),
+        }
+    }
+}
+
 #[derive(new, Clone, Hash, PartialEq, Eq, Debug)]
 struct ShapeKey {
     pub text: String,
     pub style: CoarseStyle,
+    pub features: ShapingFeatures,
+    pub wide: bool,
+}
+
+/// The OpenType features that can be toggled globally, independent of the per-font features set
+/// in guifont.
+#[derive(Clone, Copy, Hash, PartialEq, Eq, Debug)]
+pub struct ShapingFeatures {
+    pub kerning: bool,
+    pub ligatures: bool,
+}
+
+impl Default for ShapingFeatures {
+    fn default() -> Self {
+        Self {
+            kerning: true,
+            ligatures: true,
+        }
+    }
+}
+
+impl ShapingFeatures {
+    // Programming fonts like Fira Code implement their ligatures as contextual alternates, so
+    // those are toggled together with the standard ligatures
+    fn opentype_features(&self) -> [(&'static str, u16); 3] {
+        [
+            ("kern", self.kerning as u16),
+            ("liga", self.ligatures as u16),
+            ("calt", self.ligatures as u16),
+        ]
+    }
 }
 
 const FONT_CACHE_SIZE: usize = 8 * 1024 * 1024;
+// The estimated bookkeeping Skia does for each text blob, on top of the glyphs
+const TEXT_BLOB_OVERHEAD: usize = 64;
+// How many of the most recently drawn strings are shaped again ahead of time after a font change
+const WARM_UP_LIMIT: usize = 1000;
+
+/// How the shaped text cache decides what to evict. Only one of the policies is active at a time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShapeCacheLimit {
+    /// Keep at most this many shaped strings
+    Entries(NonZeroUsize),
+    /// Keep the estimated memory footprint under this many bytes
+    Bytes(usize),
+}
+
+impl Default for ShapeCacheLimit {
+    fn default() -> Self {
+        ShapeCacheLimit::Entries(NonZeroUsize::new(10000).unwrap())
+    }
+}
+
+/// A rough estimate of the memory taken by a cached entry, Skia doesn't expose the real size of a
+/// text blob.
+fn estimated_entry_size(key: &ShapeKey, blobs: &[TextBlob]) -> usize {
+    let glyph_size = mem::size_of::<u16>() + mem::size_of::<skia_safe::Point>();
+    mem::size_of::<ShapeKey>()
+        + key.text.len()
+        + key.text.chars().count() * glyph_size
+        + blobs.len() * TEXT_BLOB_OVERHEAD
+}
 
 pub struct CachingShaper {
     options: FontOptions,
     font_loader: FontLoader,
     blob_cache: LruCache<ShapeKey, Vec<TextBlob>>,
+    cache_limit: ShapeCacheLimit,
+    cache_bytes: usize,
     shape_context: ShapeContext,
+    shaping_features: ShapingFeatures,
+    /// The fonts from `guifontwide`, when it's empty the double width cells use the normal fonts
+    wide_fonts: Vec<FontDescription>,
     scale_factor: f32,
     linespace: f32,
+    glyph_alignment: GlyphAlignment,
     font_info: Option<(Metrics, f32)>,
+    warm_up_enabled: bool,
+    /// The text that's still to be shaped ahead of time after the font changed, the next one last
+    warm_up_queue: Vec<(String, CoarseStyle, bool)>,
 }
 
 impl CachingShaper {
@@ -46,12 +160,20 @@ impl CachingShaper {
         let mut shaper = CachingShaper {
             options,
             font_loader: FontLoader::new(font_size),
-            blob_cache: LruCache::new(NonZeroUsize::new(10000).unwrap()),
+            blob_cache: LruCache::unbounded(),
+            cache_limit: ShapeCacheLimit::default(),
+            cache_bytes: 0,
             shape_context: ShapeContext::new(),
+            shaping_features: ShapingFeatures::default(),
+            wide_fonts: Vec::new(),
             scale_factor,
             linespace: 0.0,
+            glyph_alignment: GlyphAlignment::Baseline,
             font_info: None,
+            warm_up_enabled: false,
+            warm_up_queue: Vec::new(),
         };
+        shaper.set_cache_limit(ShapeCacheLimit::default());
         shaper.reset_font_loader();
         shaper
     }
@@ -95,7 +217,62 @@ impl CachingShaper {
         self.update_font_options(options);
     }
 
-    pub fn update_font_options(&mut self, options: FontOptions) {
+    /// Sets the fonts for the double width cells from `guifontwide`. Only the font names are used,
+    /// the size always comes from `guifont` so that the text lines up with the grid.
+    pub fn update_wide_font(&mut self, guifontwide_setting: &str) {
+        debug!("Updating wide font: {}", guifontwide_setting);
+
+        let wide_fonts = match FontOptions::parse(guifontwide_setting) {
+            Ok(options) => options.normal,
+            Err(msg) => {
+                error_msg!("Failed to parse guifontwide: {}", msg);
+                return;
+            }
+        };
+
+        if wide_fonts != self.wide_fonts {
+            self.wide_fonts = wide_fonts;
+            self.blob_cache.clear();
+            self.cache_bytes = 0;
+        }
+    }
+
+    /// Reloads the font using a fresh font loader, so that fonts installed after startup are
+    /// picked up. The current font is kept if the new one can't be loaded.
+    pub fn reload_font(&mut self, guifont_setting: &str) -> bool {
+        debug!("Reloading font: {}", guifont_setting);
+
+        let options = if guifont_setting.is_empty() {
+            self.options.clone()
+        } else {
+            match FontOptions::parse(guifont_setting) {
+                Ok(opt) => opt,
+                Err(msg) => {
+                    error_msg!("Failed to parse guifont: {}", msg);
+                    return false;
+                }
+            }
+        };
+
+        let previous_font_loader =
+            mem::replace(&mut self.font_loader, FontLoader::new(self.current_size()));
+        let updated = if options.possible_fonts().is_empty() {
+            // Only the bundled default font is used, which can always be loaded
+            self.options = options;
+            self.reset_font_loader();
+            true
+        } else {
+            self.update_font_options(options)
+        };
+
+        if !updated {
+            self.font_loader = previous_font_loader;
+        }
+        updated
+    }
+
+    /// Returns `true` if the font was updated.
+    pub fn update_font_options(&mut self, options: FontOptions) -> bool {
         debug!("Updating font options: {:?}", options);
 
         let keys = options
@@ -127,9 +304,100 @@ impl CachingShaper {
             debug!("Font updated to: {:?}", options);
             self.options = options;
             self.reset_font_loader();
+            true
+        } else {
+            false
         }
     }
 
+    pub fn set_cache_limit(&mut self, limit: ShapeCacheLimit) {
+        self.cache_limit = limit;
+        match limit {
+            ShapeCacheLimit::Entries(entries) => {
+                while self.blob_cache.len() > entries.get() {
+                    self.pop_lru_entry();
+                }
+                self.blob_cache.resize(entries);
+            }
+            ShapeCacheLimit::Bytes(_) => {
+                self.blob_cache.resize(NonZeroUsize::MAX);
+                self.evict_over_budget();
+            }
+        }
+    }
+
+    fn pop_lru_entry(&mut self) {
+        if let Some((key, blobs)) = self.blob_cache.pop_lru() {
+            self.cache_bytes -= estimated_entry_size(&key, &blobs);
+        }
+    }
+
+    /// Evicts the least recently used entries until the cache is under the memory budget. The most
+    /// recent entry is always kept, even when it's larger than the whole budget, since it's about
+    /// to be drawn.
+    fn evict_over_budget(&mut self) {
+        if let ShapeCacheLimit::Bytes(budget) = self.cache_limit {
+            while self.cache_bytes > budget && self.blob_cache.len() > 1 {
+                self.pop_lru_entry();
+            }
+        }
+    }
+
+    /// Enables shaping the most recently drawn text again ahead of time whenever the font changes,
+    /// so that the buffers and lines the user goes back to don't all have to be shaped on the
+    /// first frame they appear.
+    pub fn set_warm_up(&mut self, enabled: bool) {
+        let was_enabled = mem::replace(&mut self.warm_up_enabled, enabled);
+        if !enabled {
+            self.warm_up_queue.clear();
+        } else if !was_enabled && self.blob_cache.is_empty() {
+            // Nothing has been drawn yet with the loaded font
+            self.queue_warm_up();
+        }
+    }
+
+    /// Queues the text in the cache before it's cleared, the most recently used is shaped first.
+    /// Before anything has been drawn, the printable ASCII characters are shaped instead.
+    fn queue_warm_up(&mut self) {
+        self.warm_up_queue.clear();
+        if !self.warm_up_enabled {
+            return;
+        }
+        self.warm_up_queue = if self.blob_cache.is_empty() {
+            CoarseStyle::permutations()
+                .flat_map(|style| {
+                    (' '..='~').map(move |character| (character.to_string(), style, false))
+                })
+                .collect()
+        } else {
+            self.blob_cache
+                .iter()
+                .take(WARM_UP_LIMIT)
+                .map(|(key, _)| (key.text.clone(), key.style, key.wide))
+                .collect()
+        };
+        self.warm_up_queue.reverse();
+    }
+
+    /// Shapes up to `budget` of the queued warm-up text, a bit at a time so that it doesn't block
+    /// the event loop. Returns whether there's more left.
+    pub fn warm_up(&mut self, budget: usize) -> bool {
+        let start = self.warm_up_queue.len().saturating_sub(budget);
+        for (text, style, wide) in self.warm_up_queue.split_off(start) {
+            self.shape_cell_cached(text, style, wide);
+        }
+        self.is_warming_up()
+    }
+
+    pub fn is_warming_up(&self) -> bool {
+        !self.warm_up_queue.is_empty()
+    }
+
+    /// The features are part of the cache key, so already shaped text doesn't need to be thrown away.
+    pub fn set_shaping_features(&mut self, features: ShapingFeatures) {
+        self.shaping_features = features;
+    }
+
     pub fn update_linespace(&mut self, linespace: f32) {
         debug!("Updating linespace: {}", linespace);
 
@@ -150,6 +418,11 @@ impl CachingShaper {
         }
     }
 
+    /// Like the linespace, this moves all the text, so the lines need to be drawn again.
+    pub fn set_glyph_alignment(&mut self, glyph_alignment: GlyphAlignment) {
+        self.glyph_alignment = glyph_alignment;
+    }
+
     fn reset_font_loader(&mut self) {
         tracy_zone!("reset_font_loader");
         self.font_info = None;
@@ -162,13 +435,27 @@ impl CachingShaper {
             font_size, font_width
         );
 
+        self.queue_warm_up();
         self.blob_cache.clear();
+        self.cache_bytes = 0;
     }
 
     pub fn font_names(&self) -> Vec<String> {
         self.font_loader.font_names()
     }
 
+    /// The family names of the fonts loaded so far, which includes the fallback fonts that were
+    /// needed for the text drawn until now.
+    pub fn loaded_font_names(&self) -> Vec<String> {
+        self.font_loader
+            .loaded_fonts()
+            .iter()
+            .map(|font| font.skia_font.typeface().family_name())
+            .sorted()
+            .dedup()
+            .collect()
+    }
+
     fn info(&mut self) -> (Metrics, f32) {
         if let Some(info) = self.font_info {
             return info;
@@ -215,23 +502,58 @@ impl CachingShaper {
         self.baseline_offset() - metrics.underline_offset
     }
 
+    pub fn strikethrough_position(&mut self) -> f32 {
+        let metrics = self.metrics();
+        self.baseline_offset() - strikeout_offset(&metrics)
+    }
+
     pub fn stroke_size(&mut self) -> f32 {
         self.metrics().stroke_size
     }
 
     pub fn baseline_offset(&mut self) -> f32 {
         let metrics = self.metrics();
-        // NOTE: leading is also called linegap and should be equally distributed on the top and
-        // bottom, so it's centered like our linespace settings. That's how it works on the web,
-        // but some desktop applications only use the top according to:
-        // https://googlefonts.github.io/gf-guide/metrics.html#8-linegap-values-must-be-0
-        metrics.ascent + (metrics.leading + self.linespace) / 2.0
+        let cell_height = self.font_base_dimensions().height;
+        baseline_offset(self.glyph_alignment, &metrics, self.linespace, cell_height)
+    }
+
+    /// How far the bottom of the descenders is from the top of the cell. Unlike the bottom of the
+    /// cell, it leaves out the leading and linespace below the text.
+    pub fn glyph_bottom(&mut self) -> f32 {
+        let metrics = self.metrics();
+        self.baseline_offset() + metrics.descent
+    }
+
+    /// The fonts tried for a cluster starting with `character`, in order. Double width cells try
+    /// the `guifontwide` fonts first, then the fonts configured for the script of the character
+    /// and the `guifont` fonts, which are the only ones when `guifontwide` isn't set.
+    fn font_fallback_list(
+        &self,
+        character: char,
+        style: CoarseStyle,
+        wide: bool,
+    ) -> Vec<FontDescription> {
+        let wide_fonts = if wide { &self.wide_fonts[..] } else { &[] };
+        wide_fonts
+            .iter()
+            .map(|font| FontDescription {
+                family: font.family.clone(),
+                style: font
+                    .style
+                    .clone()
+                    .or_else(|| style.name().map(str::to_string)),
+            })
+            .chain(self.options.script_font_list(character, style))
+            .chain(self.options.font_list(style))
+            .unique()
+            .collect()
     }
 
     fn build_clusters(
         &mut self,
         text: &str,
         style: CoarseStyle,
+        wide: bool,
     ) -> Vec<(Vec<CharCluster>, Arc<FontPair>)> {
         let mut cluster = CharCluster::new();
 
@@ -263,18 +585,14 @@ impl CachingShaper {
             // Create font fallback list
             let mut font_fallback_keys = Vec::new();
 
-            // Add parsed fonts from guifont or config file
-            font_fallback_keys.extend(
-                self.options
-                    .font_list(style)
-                    .iter()
-                    .map(|font_desc| FontKey {
-                        font_desc: Some(font_desc.clone()),
-                        hinting: self.options.hinting.clone(),
-                        edging: self.options.edging.clone(),
-                    })
-                    .unique(),
-            );
+            // Add the wide and script fonts of the cluster, and then the parsed fonts from guifont
+            // or config file
+            let fonts = self.font_fallback_list(cluster.chars()[0].ch, style, wide);
+            font_fallback_keys.extend(fonts.into_iter().map(|font_desc| FontKey {
+                font_desc: Some(font_desc),
+                hinting: self.options.hinting.clone(),
+                edging: self.options.edging.clone(),
+            }));
 
             // Add default font
             font_fallback_keys.push(FontKey {
@@ -367,15 +685,19 @@ impl CachingShaper {
         set_font_cache_limit(FONT_CACHE_SIZE);
     }
 
-    pub fn shape(&mut self, text: String, style: CoarseStyle) -> Vec<TextBlob> {
+    /// Shapes the text into runs of glyph ids and positions, one for each font used.
+    fn shape_glyphs(
+        &mut self,
+        text: &str,
+        style: CoarseStyle,
+        wide: bool,
+    ) -> Vec<(Arc<FontPair>, Vec<(u16, (f32, f32))>)> {
         let current_size = self.current_size();
         let glyph_width = self.font_base_dimensions().width;
 
-        let mut resulting_blobs = Vec::new();
-
-        trace!("Shaping text: {:?}", text);
+        let mut runs = Vec::new();
 
-        for (cluster_group, font_pair) in self.build_clusters(&text, style) {
+        for (cluster_group, font_pair) in self.build_clusters(text, style, wide) {
             let features = self.get_font_features(
                 font_pair
                     .as_ref()
@@ -401,19 +723,56 @@ impl CachingShaper {
             let mut glyph_data = Vec::new();
 
             shaper.shape_with(|glyph_cluster| {
-                for glyph in glyph_cluster.glyphs {
+                // Neovim puts an emoji sequence, like the ones joined by zero width joiners, in a
+                // single cell. Fonts without a glyph for the whole sequence return one for each
+                // part, so only the first one is drawn instead of piling them on top of each other
+                let glyphs = if glyph_cluster.info.is_emoji() {
+                    &glyph_cluster.glyphs[..glyph_cluster.glyphs.len().min(1)]
+                } else {
+                    glyph_cluster.glyphs
+                };
+                for glyph in glyphs {
                     let position = (glyph.data as f32 * glyph_width, glyph.y);
                     glyph_data.push((glyph.id, position));
                 }
             });
 
-            if glyph_data.is_empty() {
-                continue;
+            if !glyph_data.is_empty() {
+                runs.push((font_pair, glyph_data));
             }
+        }
+
+        runs
+    }
 
+    pub fn shape(&mut self, text: String, style: CoarseStyle, wide: bool) -> Vec<TextBlob> {
+        self.shape_with_edging(text, style, wide, None)
+    }
+
+    /// Like `shape`, but the glyphs are drawn without antialiasing, whatever the edging of the
+    /// font is. The edging is baked into the blobs, so the paint can't turn it off. It's only
+    /// used for the cursor, so the blobs aren't cached.
+    pub fn shape_aliased(&mut self, text: String, style: CoarseStyle, wide: bool) -> Vec<TextBlob> {
+        self.shape_with_edging(text, style, wide, Some(Edging::Alias))
+    }
+
+    fn shape_with_edging(
+        &mut self,
+        text: String,
+        style: CoarseStyle,
+        wide: bool,
+        edging: Option<Edging>,
+    ) -> Vec<TextBlob> {
+        trace!("Shaping text: {:?}", text);
+
+        let mut resulting_blobs = Vec::new();
+        for (font_pair, glyph_data) in self.shape_glyphs(&text, style, wide) {
+            let mut font = font_pair.skia_font.clone();
+            if let Some(edging) = edging {
+                font.set_edging(edging);
+            }
             let mut blob_builder = TextBlobBuilder::new();
-            let (glyphs, positions) =
-                blob_builder.alloc_run_pos(&font_pair.skia_font, glyph_data.len(), None);
+            let (glyphs, positions) = blob_builder.alloc_run_pos(&font, glyph_data.len(), None);
             for (i, (glyph_id, glyph_position)) in glyph_data.iter().enumerate() {
                 glyphs[i] = *glyph_id;
                 positions[i] = (*glyph_position).into();
@@ -427,31 +786,447 @@ impl CachingShaper {
     }
 
     pub fn shape_cached(&mut self, text: String, style: CoarseStyle) -> &Vec<TextBlob> {
+        self.shape_cell_cached(text, style, false)
+    }
+
+    /// Like `shape_cached`, but `wide` text of a double width cell is shaped with the
+    /// `guifontwide` fonts.
+    pub fn shape_cell_cached(
+        &mut self,
+        text: String,
+        style: CoarseStyle,
+        wide: bool,
+    ) -> &Vec<TextBlob> {
         tracy_zone!("shape_cached");
-        let key = ShapeKey::new(text.clone(), style);
+        let key = ShapeKey::new(text.clone(), style, self.shaping_features, wide);
 
         if !self.blob_cache.contains(&key) {
-            let blobs = self.shape(text, style);
-            self.blob_cache.put(key.clone(), blobs);
+            let blobs = self.shape(text, style, wide);
+            self.cache_bytes += estimated_entry_size(&key, &blobs);
+            // With a limited number of entries, the cache evicts by itself
+            if let Some((evicted_key, evicted_blobs)) = self.blob_cache.push(key.clone(), blobs) {
+                self.cache_bytes -= estimated_entry_size(&evicted_key, &evicted_blobs);
+            }
+            self.evict_over_budget();
         }
 
         self.blob_cache.get(&key).unwrap()
     }
 
     fn get_font_features(&self, name: Option<&str>) -> Vec<(String, u16)> {
-        if let Some(name) = name {
-            self.options
-                .features
-                .get(name)
-                .map(|features| {
-                    features
-                        .iter()
-                        .map(|feature| (feature.0.clone(), feature.1))
-                        .collect::<Vec<_>>()
-                })
-                .unwrap_or_default()
-        } else {
-            vec![]
+        let mut features = name
+            .and_then(|name| self.options.features.get(name))
+            .map(|features| {
+                features
+                    .iter()
+                    .map(|feature| (feature.0.clone(), feature.1))
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+
+        // Features set explicitly for the font take precedence over the global toggles
+        for (name, value) in self.shaping_features.opentype_features() {
+            if !features.iter().any(|(feature, _)| feature == name) {
+                features.push((name.to_string(), value));
+            }
+        }
+
+        features
+    }
+}
+
+/// How far the baseline is from the top of a cell of `cell_height`.
+fn baseline_offset(
+    alignment: GlyphAlignment,
+    metrics: &Metrics,
+    linespace: f32,
+    cell_height: f32,
+) -> f32 {
+    match alignment {
+        GlyphAlignment::Top => metrics.ascent + metrics.leading / 2.0,
+        // Fonts without a cap height fall back to centering the whole glyph box
+        GlyphAlignment::Center if metrics.cap_height > 0.0 => {
+            (cell_height + metrics.cap_height) / 2.0
+        }
+        GlyphAlignment::Center => (cell_height + metrics.ascent - metrics.descent) / 2.0,
+        // NOTE: leading is also called linegap and should be equally distributed on the top and
+        // bottom, so it's centered like our linespace settings. That's how it works on the web,
+        // but some desktop applications only use the top according to:
+        // https://googlefonts.github.io/gf-guide/metrics.html#8-linegap-values-must-be-0
+        GlyphAlignment::Baseline => metrics.ascent + (metrics.leading + linespace) / 2.0,
+    }
+}
+
+/// The offset of the strikethrough above the baseline. Not all fonts provide it, so fall back to the
+/// middle of the lowercase letters.
+fn strikeout_offset(metrics: &Metrics) -> f32 {
+    if metrics.strikeout_offset > 0.0 {
+        metrics.strikeout_offset
+    } else {
+        metrics.x_height / 2.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strikeout_offset_comes_from_the_font_metrics() {
+        let metrics = Metrics {
+            strikeout_offset: 4.5,
+            x_height: 12.0,
+            ..Default::default()
+        };
+        assert_eq!(strikeout_offset(&metrics), 4.5);
+
+        let metrics = Metrics {
+            strikeout_offset: 0.0,
+            x_height: 12.0,
+            ..Default::default()
+        };
+        assert_eq!(strikeout_offset(&metrics), 6.0);
+    }
+
+    #[test]
+    fn glyph_alignment_moves_the_baseline_in_the_cell() {
+        // 4 pixels of leading and 10 of linespace, in a cell of 42
+        let metrics = Metrics {
+            ascent: 20.0,
+            descent: 8.0,
+            leading: 4.0,
+            cap_height: 16.0,
+            ..Default::default()
+        };
+        let baseline = |alignment| baseline_offset(alignment, &metrics, 10.0, 42.0);
+        assert_eq!(baseline(GlyphAlignment::Top), 22.0);
+        assert_eq!(baseline(GlyphAlignment::Center), 29.0);
+        assert_eq!(baseline(GlyphAlignment::Baseline), 27.0);
+
+        let metrics = Metrics {
+            cap_height: 0.0,
+            ..metrics
+        };
+        assert_eq!(
+            baseline_offset(GlyphAlignment::Center, &metrics, 10.0, 42.0),
+            27.0
+        );
+    }
+
+    #[test]
+    fn reload_font_clears_and_rebuilds_the_shape_cache() {
+        let mut shaper = CachingShaper::new(1.0);
+        let style = CoarseStyle::default();
+
+        shaper.shape_cached("abc".to_string(), style);
+        shaper.shape_cached("def".to_string(), style);
+        assert_eq!(shaper.blob_cache.len(), 2);
+
+        assert!(shaper.reload_font(""));
+        assert_eq!(shaper.blob_cache.len(), 0);
+
+        assert!(!shaper.shape_cached("abc".to_string(), style).is_empty());
+        assert_eq!(shaper.blob_cache.len(), 1);
+    }
+
+    #[test]
+    fn warm_up_shapes_the_recently_drawn_text_again() {
+        let mut shaper = CachingShaper::new(1.0);
+        let lines = ["fn main() {", "    println!(\"Hello\");", "}"];
+        for line in lines {
+            shaper.shape_cached(line.to_string(), CoarseStyle::default());
+        }
+        // It's off by default
+        assert!(shaper.reload_font(""));
+        assert!(!shaper.is_warming_up());
+
+        for line in lines {
+            shaper.shape_cached(line.to_string(), CoarseStyle::default());
+        }
+        shaper.set_warm_up(true);
+        assert!(shaper.reload_font(""));
+        assert_eq!(shaper.blob_cache.len(), 0);
+        // Nothing is shaped until the loop asks for it, and the most recent text goes first
+        assert!(shaper.warm_up(1));
+        let features = shaper.shaping_features;
+        let key =
+            |text: &str| ShapeKey::new(text.to_string(), CoarseStyle::default(), features, false);
+        assert!(shaper.blob_cache.contains(&key("}")));
+        assert!(!shaper.warm_up(10));
+        for line in lines {
+            assert!(shaper.blob_cache.contains(&key(line)));
+        }
+
+        shaper.shape_cached("let".to_string(), CoarseStyle::default());
+        assert!(shaper.reload_font(""));
+        shaper.set_warm_up(false);
+        assert!(!shaper.is_warming_up());
+    }
+
+    #[test]
+    fn warm_up_shapes_the_printable_ascii_characters_on_the_first_font_load() {
+        let mut shaper = CachingShaper::new(1.0);
+        assert!(!shaper.is_warming_up());
+
+        shaper.set_warm_up(true);
+        assert_eq!(shaper.blob_cache.len(), 0);
+        // Nothing is shaped until the loop asks for it
+        assert!(shaper.warm_up(100));
+        assert_eq!(shaper.blob_cache.len(), 100);
+        while shaper.warm_up(100) {}
+
+        let printable_ascii = 95;
+        assert_eq!(shaper.blob_cache.len(), printable_ascii * 4);
+        for style in CoarseStyle::permutations() {
+            for text in ["a", "Z", "~", " "] {
+                let key = ShapeKey::new(text.to_string(), style, shaper.shaping_features, false);
+                assert!(shaper.blob_cache.contains(&key));
+            }
+        }
+    }
+
+    #[test]
+    fn memory_budget_evicts_oversized_entries() {
+        let mut shaper = CachingShaper::new(1.0);
+        let style = CoarseStyle::default();
+        shaper.set_cache_limit(ShapeCacheLimit::Bytes(1024));
+
+        // Larger than the whole budget, but still kept while it's the latest entry
+        let oversized = "x".repeat(500);
+        assert!(!shaper.shape_cached(oversized.clone(), style).is_empty());
+        assert_eq!(shaper.blob_cache.len(), 1);
+        assert!(shaper.cache_bytes > 1024);
+
+        shaper.shape_cached("abc".to_string(), style);
+        shaper.shape_cached("def".to_string(), style);
+        assert_eq!(shaper.blob_cache.len(), 2);
+        assert!(shaper.cache_bytes <= 1024);
+        assert!(!shaper.blob_cache.contains(&ShapeKey::new(
+            oversized,
+            style,
+            ShapingFeatures::default(),
+            false
+        )));
+    }
+
+    #[test]
+    fn switching_cache_policies_keeps_the_size_estimate() {
+        let mut shaper = CachingShaper::new(1.0);
+        let style = CoarseStyle::default();
+        shaper.set_cache_limit(ShapeCacheLimit::Bytes(usize::MAX));
+        for text in ["abc", "def", "ghi"] {
+            shaper.shape_cached(text.to_string(), style);
         }
+        assert_eq!(shaper.blob_cache.len(), 3);
+
+        shaper.set_cache_limit(ShapeCacheLimit::Entries(NonZeroUsize::new(1).unwrap()));
+        assert_eq!(shaper.blob_cache.len(), 1);
+        let (key, blobs) = shaper.blob_cache.peek_lru().unwrap();
+        assert_eq!(shaper.cache_bytes, estimated_entry_size(key, blobs));
+
+        shaper.shape_cached("jkl".to_string(), style);
+        assert_eq!(shaper.blob_cache.len(), 1);
+        let (key, blobs) = shaper.blob_cache.peek_lru().unwrap();
+        assert_eq!(shaper.cache_bytes, estimated_entry_size(key, blobs));
+    }
+
+    fn feature_value(features: &[(String, u16)], name: &str) -> Option<u16> {
+        features
+            .iter()
+            .find(|(feature, _)| feature == name)
+            .map(|(_, value)| *value)
+    }
+
+    #[test]
+    fn kerning_is_toggled_independently_of_ligatures() {
+        let mut shaper = CachingShaper::new(1.0);
+
+        shaper.set_shaping_features(ShapingFeatures {
+            kerning: false,
+            ligatures: true,
+        });
+        let features = shaper.get_font_features(None);
+        assert_eq!(feature_value(&features, "kern"), Some(0));
+        assert_eq!(feature_value(&features, "liga"), Some(1));
+        assert_eq!(feature_value(&features, "calt"), Some(1));
+
+        shaper.set_shaping_features(ShapingFeatures {
+            kerning: true,
+            ligatures: false,
+        });
+        let features = shaper.get_font_features(None);
+        assert_eq!(feature_value(&features, "kern"), Some(1));
+        assert_eq!(feature_value(&features, "liga"), Some(0));
+        assert_eq!(feature_value(&features, "calt"), Some(0));
+    }
+
+    #[test]
+    #[ignore = "needs DejaVu Sans, the bundled fonts are monospaced and have no kerning pairs"]
+    fn kerning_is_applied_with_the_ligatures_off() {
+        let mut shaper = CachingShaper::new(1.0);
+        shaper.update_font("DejaVu Sans:h16");
+        // The pair is shaped with the same font and features as the cached text
+        let (font_pair, _) = shaper
+            .shape_glyphs("AV", CoarseStyle::default(), false)
+            .remove(0);
+        let family = font_pair
+            .key
+            .font_desc
+            .as_ref()
+            .map(|desc| desc.family.as_str());
+        assert_eq!(family, Some("DejaVu Sans"), "DejaVu Sans is not installed");
+
+        let advance = |shaper: &mut CachingShaper| {
+            assert!(!shaper
+                .shape_cached("AV".to_string(), CoarseStyle::default())
+                .is_empty());
+            let features = shaper.get_font_features(family);
+            let size = shaper.current_size();
+            let mut pair_shaper = shaper
+                .shape_context
+                .builder(font_pair.swash_font.as_ref())
+                .features(features.iter().map(|(name, value)| (name.as_str(), *value)))
+                .size(size)
+                .build();
+            pair_shaper.add_str("AV");
+            let mut advance = 0.0;
+            pair_shaper.shape_with(|cluster| {
+                for glyph in cluster.glyphs {
+                    advance += glyph.advance;
+                }
+            });
+            advance
+        };
+
+        shaper.set_shaping_features(ShapingFeatures {
+            kerning: false,
+            ligatures: false,
+        });
+        let unkerned = advance(&mut shaper);
+        shaper.set_shaping_features(ShapingFeatures {
+            kerning: true,
+            ligatures: false,
+        });
+        let kerned = advance(&mut shaper);
+        assert!(kerned < unkerned, "{kerned} is not less than {unkerned}");
+        // Each of the toggles has an entry of its own
+        assert_eq!(shaper.blob_cache.len(), 2);
+    }
+
+    #[test]
+    fn font_specific_features_override_the_global_toggles() {
+        let mut shaper = CachingShaper::new(1.0);
+        shaper.options.features.insert(
+            "Fira Code".to_string(),
+            vec![FontFeature("kern".to_string(), 1)],
+        );
+        shaper.set_shaping_features(ShapingFeatures {
+            kerning: false,
+            ligatures: false,
+        });
+
+        let features = shaper.get_font_features(Some("Fira Code"));
+        assert_eq!(feature_value(&features, "kern"), Some(1));
+        assert_eq!(feature_value(&features, "liga"), Some(0));
+        assert_eq!(features.len(), 3);
+    }
+
+    fn glyph_ids(shaper: &mut CachingShaper, text: &str) -> Vec<u16> {
+        shaper
+            .shape_glyphs(text, CoarseStyle::default(), false)
+            .into_iter()
+            .flat_map(|(_, glyphs)| glyphs.into_iter().map(|(id, _)| id))
+            .collect()
+    }
+
+    #[test]
+    fn toggling_ligatures_changes_the_shaped_glyphs() {
+        let mut shaper = CachingShaper::new(1.0);
+        let ligatures = glyph_ids(&mut shaper, "==");
+
+        shaper.set_shaping_features(ShapingFeatures {
+            ligatures: false,
+            ..ShapingFeatures::default()
+        });
+        let plain = glyph_ids(&mut shaper, "==");
+        assert_ne!(ligatures, plain);
+        // Without ligatures both characters are the same glyph
+        assert_eq!(plain[0], plain[1]);
+    }
+
+    #[test]
+    fn toggling_kerning_shapes_into_a_separate_cache_entry() {
+        let mut shaper = CachingShaper::new(1.0);
+        let style = CoarseStyle::default();
+        let kerned = ShapingFeatures {
+            kerning: true,
+            ligatures: false,
+        };
+        let unkerned = ShapingFeatures {
+            kerning: false,
+            ..kerned
+        };
+
+        shaper.set_shaping_features(kerned);
+        shaper.shape_cached("AV".to_string(), style);
+        shaper.set_shaping_features(unkerned);
+        shaper.shape_cached("AV".to_string(), style);
+        assert_eq!(shaper.blob_cache.len(), 2);
+
+        shaper.set_shaping_features(kerned);
+        shaper.shape_cached("AV".to_string(), style);
+        assert_eq!(shaper.blob_cache.len(), 2);
+    }
+
+    #[test]
+    fn wide_cells_are_routed_to_the_wide_font() {
+        let mut shaper = CachingShaper::new(1.0);
+        shaper.options.normal = vec![FontDescription {
+            family: "Fira Code".to_string(),
+            style: None,
+        }];
+        let families = |shaper: &CachingShaper, wide: bool| -> Vec<String> {
+            shaper
+                .font_fallback_list('好', CoarseStyle::default(), wide)
+                .into_iter()
+                .map(|font| font.family)
+                .collect()
+        };
+
+        // Without guifontwide the wide cells use the main font
+        assert_eq!(families(&shaper, true), vec!["Fira Code"]);
+
+        shaper.update_wide_font("Noto Sans CJK JP:h20");
+        assert_eq!(
+            families(&shaper, true),
+            vec!["Noto Sans CJK JP", "Fira Code"]
+        );
+        assert_eq!(families(&shaper, false), vec!["Fira Code"]);
+
+        // The wide font replaces the cached wide text
+        shaper.shape_cell_cached("好".to_string(), CoarseStyle::default(), true);
+        assert_eq!(shaper.blob_cache.len(), 1);
+        shaper.update_wide_font("");
+        assert_eq!(shaper.blob_cache.len(), 0);
+        assert_eq!(families(&shaper, true), vec!["Fira Code"]);
+    }
+
+    #[test]
+    fn zwj_emoji_sequence_is_drawn_as_a_single_glyph() {
+        let mut shaper = CachingShaper::new(1.0);
+        let style = CoarseStyle::default();
+        let glyph_count = |shaper: &mut CachingShaper, text: &str| -> usize {
+            shaper
+                .shape_glyphs(text, style, false)
+                .iter()
+                .map(|(_, glyphs)| glyphs.len())
+                .sum()
+        };
+
+        assert_eq!(
+            glyph_count(&mut shaper, "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}"),
+            1
+        );
+        assert_eq!(glyph_count(&mut shaper, "ab"), 2);
     }
 }
@@ -7,6 +7,7 @@ use skia_safe::{
     font_style::{Slant, Weight, Width},
     FontStyle,
 };
+use swash::text::Codepoint;
 
 use crate::editor;
 
@@ -15,6 +16,9 @@ const FONT_OPTS_SEPARATOR: char = ':';
 const FONT_LIST_SEPARATOR: char = ',';
 const FONT_HINTING_PREFIX: &str = "#h-";
 const FONT_EDGING_PREFIX: &str = "#e-";
+const FONT_BOLD_FAMILY_PREFIX: &str = "#b-";
+const FONT_ITALIC_FAMILY_PREFIX: &str = "#i-";
+const FONT_BOLD_ITALIC_FAMILY_PREFIX: &str = "#bi-";
 const FONT_HEIGHT_PREFIX: char = 'h';
 const FONT_WIDTH_PREFIX: char = 'w';
 const FONT_BOLD_OPT: &str = "b";
@@ -106,6 +110,8 @@ pub struct FontOptions {
     pub bold: Option<Vec<SecondaryFontDescription>>,
     pub bold_italic: Option<Vec<SecondaryFontDescription>>,
     pub features: HashMap<String /* family */, Vec<FontFeature> /* features */>,
+    /// Fonts used for the characters of a script, keyed by `script_key`
+    pub scripts: HashMap<String /* script */, Vec<FontDescription>>,
     pub size: f32,
     pub width: f32,
     pub hinting: FontHinting,
@@ -165,6 +171,12 @@ impl FontOptions {
                 font_options.hinting = FontHinting::parse(hinting_string)?;
             } else if let Some(edging_string) = part.strip_prefix(FONT_EDGING_PREFIX) {
                 font_options.edging = FontEdging::parse(edging_string)?;
+            } else if let Some(fonts) = part.strip_prefix(FONT_BOLD_FAMILY_PREFIX) {
+                font_options.bold = parse_secondary_fonts(fonts);
+            } else if let Some(fonts) = part.strip_prefix(FONT_ITALIC_FAMILY_PREFIX) {
+                font_options.italic = parse_secondary_fonts(fonts);
+            } else if let Some(fonts) = part.strip_prefix(FONT_BOLD_ITALIC_FAMILY_PREFIX) {
+                font_options.bold_italic = parse_secondary_fonts(fonts);
             } else if part.starts_with(FONT_HEIGHT_PREFIX) && part.len() > 1 {
                 font_options.size = parse_pixels(part).map_err(|_| INVALID_SIZE_ERR)?;
             } else if part.starts_with(FONT_WIDTH_PREFIX) && part.len() > 1 {
@@ -228,6 +240,31 @@ impl FontOptions {
             .unwrap_or_else(|| normal_fallback.collect())
     }
 
+    /// The fonts configured for the script of `character`, which are tried before the normal
+    /// ones.
+    pub fn script_font_list(&self, character: char, style: CoarseStyle) -> Vec<FontDescription> {
+        if self.scripts.is_empty() {
+            return Vec::new();
+        }
+
+        let script = script_key(&format!("{:?}", character.script()));
+        self.scripts
+            .get(&script)
+            .map(|fonts| {
+                fonts
+                    .iter()
+                    .map(|font| FontDescription {
+                        family: font.family.clone(),
+                        style: font
+                            .style
+                            .clone()
+                            .or_else(|| style.name().map(str::to_string)),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
     pub fn possible_fonts(&self) -> Vec<FontDescription> {
         CoarseStyle::permutations()
             // partial functions when /s
@@ -244,6 +281,7 @@ impl Default for FontOptions {
             bold: None,
             bold_italic: None,
             features: HashMap::new(),
+            scripts: HashMap::new(),
             size: points_to_pixels(DEFAULT_FONT_SIZE),
             width: 0.0,
             hinting: FontHinting::default(),
@@ -259,12 +297,22 @@ impl PartialEq for FontOptions {
             && self.italic == other.italic
             && self.bold_italic == other.bold_italic
             && self.features == other.features
+            && self.scripts == other.scripts
             && self.edging == other.edging
             && (self.size - other.size).abs() < f32::EPSILON
             && self.hinting == other.hinting
     }
 }
 
+/// Unicode script names are matched regardless of case, spaces and underscores, so `Old Italic`
+/// and `old_italic` both mean the same script.
+pub fn script_key(name: &str) -> String {
+    name.chars()
+        .filter(|character| character.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
 fn parse_pixels(part: &str) -> Result<f32, ParseFloatError> {
     Ok(points_to_pixels(part[1..].parse::<f32>()?))
 }
@@ -286,6 +334,21 @@ fn parse_font_name(font_name: impl AsRef<str>) -> String {
     parsed_font_name
 }
 
+/// Parses the families of a style paired with the normal font, which are separated like the normal
+/// font list. The style itself is taken from the paired style.
+fn parse_secondary_fonts(fonts: &str) -> Option<Vec<SecondaryFontDescription>> {
+    let fonts = fonts
+        .split(FONT_LIST_SEPARATOR)
+        .filter(|font| !font.is_empty())
+        .map(|font| SecondaryFontDescription {
+            family: Some(parse_font_name(font)),
+            style: None,
+        })
+        .collect_vec();
+
+    (!fonts.is_empty()).then_some(fonts)
+}
+
 #[derive(Clone, Debug, Hash, PartialEq, Eq, Default)]
 pub enum FontEdging {
     #[default]
@@ -574,6 +637,65 @@ mod tests {
         );
     }
 
+    #[test]
+    fn bold_uses_the_paired_family() {
+        let font_options =
+            FontOptions::parse("Fira_Code:h14:#b-Fira_Code_Condensed:#bi-Hack,Iosevka").unwrap();
+
+        let bold = font_options.font_list(CoarseStyle {
+            bold: true,
+            italic: false,
+        });
+        assert_eq!(bold[0].family, "Fira Code Condensed");
+        assert_eq!(bold[0].style.as_deref(), Some("Bold"));
+        // The normal font is still a fallback for missing glyphs
+        assert_eq!(bold[1].family, "Fira Code");
+
+        let bold_italic = font_options.font_list(CoarseStyle {
+            bold: true,
+            italic: true,
+        });
+        assert_eq!(bold_italic[0].family, "Hack");
+        assert_eq!(bold_italic[1].family, "Iosevka");
+
+        // The cell size still comes from the normal font
+        let italic = font_options.font_list(CoarseStyle {
+            bold: false,
+            italic: true,
+        });
+        assert_eq!(italic[0].family, "Fira Code");
+        assert_eq!(font_options.primary_font().unwrap().family, "Fira Code");
+    }
+
+    #[test]
+    fn script_fonts_are_used_for_their_script() {
+        let mut font_options = FontOptions::parse("Fira_Code").unwrap();
+        font_options.scripts.insert(
+            script_key("Cyrillic"),
+            vec![FontDescription {
+                family: "PT Mono".to_string(),
+                style: None,
+            }],
+        );
+
+        let fonts = font_options.script_font_list('Ж', CoarseStyle::default());
+        assert_eq!(fonts.len(), 1);
+        assert_eq!(fonts[0].family, "PT Mono");
+        assert_eq!(fonts[0].style, None);
+
+        let bold = CoarseStyle {
+            bold: true,
+            italic: false,
+        };
+        let fonts = font_options.script_font_list('ж', bold);
+        assert_eq!(fonts[0].style.as_deref(), Some("Bold"));
+
+        // Other scripts go through the normal fallback list
+        assert!(font_options
+            .script_font_list('a', CoarseStyle::default())
+            .is_empty());
+    }
+
     #[test]
     fn test_parse_font_name_with_escapes() {
         let without_escapes_or_specials_chars = parse_font_name("Fira Code Mono");
@@ -1,12 +1,15 @@
 use std::sync::Arc;
 
 use log::trace;
-use skia_safe::{colors, dash_path_effect, BlendMode, Canvas, Color, Paint, Path, HSV};
+use skia_safe::{
+    colors, dash_path_effect, BlendMode, Canvas, Color, Color4f, Paint, Path, TextBlob, HSV,
+};
+use unicode_segmentation::UnicodeSegmentation;
 
 use crate::{
     editor::{Colors, Style, UnderlineStyle},
     profiling::tracy_zone,
-    renderer::{CachingShaper, RendererSettings},
+    renderer::{fonts::caching_shaper::GlyphAlignment, CachingShaper, RendererSettings},
     settings::*,
     units::{
         to_skia_point, to_skia_rect, GridPos, GridScale, GridSize, PixelPos, PixelRect, PixelVec,
@@ -19,8 +22,47 @@ pub struct GridRenderer {
     pub shaper: CachingShaper,
     pub default_style: Arc<Style>,
     pub em_size: f32,
+    /// Where the glyphs end from the top of the cell, see [`CachingShaper::glyph_bottom`]
+    pub glyph_bottom: f32,
     pub grid_scale: GridScale,
     pub is_ready: bool,
+    color_mode: ColorMode,
+}
+
+/// How colors are converted before painting
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColorMode {
+    Color,
+    Grayscale,
+    /// Black and white, split at the given luminance
+    Monochrome(f32),
+}
+
+impl ColorMode {
+    pub fn new(grayscale: bool, threshold: f32) -> Self {
+        match (grayscale, threshold) {
+            (false, _) => ColorMode::Color,
+            (true, threshold) if threshold > 0.0 => ColorMode::Monochrome(threshold),
+            (true, _) => ColorMode::Grayscale,
+        }
+    }
+
+    pub fn convert(&self, color: Color4f) -> Color4f {
+        // Relative luminance of the sRGB primaries
+        let luminance = || 0.2126 * color.r + 0.7152 * color.g + 0.0722 * color.b;
+        let value = match self {
+            ColorMode::Color => return color,
+            ColorMode::Grayscale => luminance(),
+            ColorMode::Monochrome(threshold) => {
+                if luminance() >= *threshold {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+        };
+        Color4f::new(value, value, value, color.a)
+    }
 }
 
 /// Struct with named fields to be returned from draw_background
@@ -41,16 +83,29 @@ impl GridRenderer {
         )));
         let em_size = shaper.current_size();
         let font_dimensions = shaper.font_base_dimensions();
+        let glyph_bottom = shaper.glyph_bottom();
 
         GridRenderer {
             shaper,
             default_style,
             em_size,
+            glyph_bottom,
             grid_scale: GridScale::new(font_dimensions),
             is_ready: false,
+            color_mode: ColorMode::Color,
         }
     }
 
+    pub fn set_color_mode(&mut self, color_mode: ColorMode) {
+        self.color_mode = color_mode;
+    }
+
+    /// Converts a color according to the color mode, this should be used for everything painted
+    /// from the Neovim colors.
+    pub fn display_color(&self, color: Color4f) -> Color {
+        self.color_mode.convert(color).to_color()
+    }
+
     pub fn font_names(&self) -> Vec<String> {
         self.shaper.font_names()
     }
@@ -65,6 +120,16 @@ impl GridRenderer {
         self.update_font_dimensions();
     }
 
+    pub fn update_wide_font(&mut self, guifontwide_setting: &str) {
+        self.shaper.update_wide_font(guifontwide_setting);
+    }
+
+    pub fn reload_font(&mut self, guifont_setting: &str) {
+        if self.shaper.reload_font(guifont_setting) {
+            self.update_font_dimensions();
+        }
+    }
+
     pub fn update_font_options(&mut self, options: FontOptions) {
         self.shaper.update_font_options(options);
         self.update_font_dimensions();
@@ -75,9 +140,15 @@ impl GridRenderer {
         self.update_font_dimensions();
     }
 
+    pub fn update_glyph_alignment(&mut self, glyph_alignment: GlyphAlignment) {
+        self.shaper.set_glyph_alignment(glyph_alignment);
+        self.update_font_dimensions();
+    }
+
     fn update_font_dimensions(&mut self) {
         self.em_size = self.shaper.current_size();
         self.grid_scale = GridScale::new(self.shaper.font_base_dimensions());
+        self.glyph_bottom = self.shaper.glyph_bottom();
         self.is_ready = true;
         trace!("Updated font dimensions: {:?}", self.grid_scale);
     }
@@ -89,7 +160,11 @@ impl GridRenderer {
     }
 
     pub fn get_default_background(&self) -> Color {
-        self.default_style.colors.background.unwrap().to_color()
+        self.display_color(self.default_style.colors.background.unwrap())
+    }
+
+    pub fn get_default_foreground(&self) -> Color {
+        self.display_color(self.default_style.colors.foreground.unwrap())
     }
 
     /// Draws a single background cell with the same style
@@ -121,13 +196,11 @@ impl GridRenderer {
             let random_color = random_hsv.to_color(255);
             paint.set_color(random_color);
         } else {
-            paint.set_color(style.background(&self.default_style.colors).to_color());
-        }
-        if style.blend > 0 {
-            paint.set_alpha_f((100 - style.blend) as f32 / 100.0);
-        } else {
-            paint.set_alpha_f(1.0);
+            paint.set_color(self.display_color(style.background(&self.default_style.colors)));
         }
+        // The blend is applied to the whole window when it's composited, so that the text is
+        // blended too
+        paint.set_alpha_f(1.0);
 
         let custom_color = paint.color4f() != self.default_style.colors.background.unwrap();
         if custom_color {
@@ -149,6 +222,20 @@ impl GridRenderer {
         grid_position: GridPos<i32>,
         cell_width: i32,
         style: &Option<Arc<Style>>,
+    ) -> bool {
+        self.draw_foreground_with_blobs(canvas, text, grid_position, cell_width, style, &mut None)
+    }
+
+    /// Like `draw_foreground`, but the text is drawn with the given blobs when there are some,
+    /// instead of shaping it again. Otherwise the blobs it gets shaped to are stored there.
+    pub fn draw_foreground_with_blobs(
+        &mut self,
+        canvas: &Canvas,
+        text: &str,
+        grid_position: GridPos<i32>,
+        cell_width: i32,
+        style: &Option<Arc<Style>>,
+        blobs: &mut Option<Vec<TextBlob>>,
     ) -> bool {
         tracy_zone!("draw_foreground");
         let pos = grid_position * self.grid_scale;
@@ -185,27 +272,26 @@ impl GridRenderer {
             let random_color = random_hsv.to_color(255);
             paint.set_color(random_color);
         } else {
-            paint.set_color(style.foreground(&self.default_style.colors).to_color());
+            paint.set_color(self.display_color(style.foreground(&self.default_style.colors)));
         }
         paint.set_anti_alias(false);
 
         // There's a lot of overhead for empty blobs in Skia, for some reason they never hit the
         // cache, so trim all the spaces
-        let trimmed = text.trim_start();
-        let leading_space_bytes = text.len() - trimmed.len();
-        let leading_spaces = text[..leading_space_bytes].chars().count();
-        let trimmed = trimmed.trim_end();
+        let (leading_spaces, trimmed) = trim_spaces(text);
         let adjustment = PixelVec::new(
             leading_spaces as f32 * self.grid_scale.width(),
             self.shaper.baseline_offset(),
         );
 
         if !trimmed.is_empty() {
-            for blob in self
-                .shaper
-                .shape_cached(trimmed.to_string(), style.into())
-                .iter()
-            {
+            let blobs = blobs.get_or_insert_with(|| {
+                let wide = is_wide_cell(trimmed, cell_width);
+                self.shaper
+                    .shape_cell_cached(trimmed.to_string(), style.into(), wide)
+                    .clone()
+            });
+            for blob in blobs.iter() {
                 tracy_zone!("draw_text_blob");
                 canvas.draw_text_blob(blob, to_skia_point(pos + adjustment), &paint);
                 drawn = true;
@@ -213,8 +299,9 @@ impl GridRenderer {
         }
 
         if style.strikethrough {
-            let line_position = region.center().y;
-            paint.set_color(style.special(&self.default_style.colors).to_color());
+            let line_position = pos.y + self.shaper.strikethrough_position();
+            paint.set_color(self.display_color(style.special(&self.default_style.colors)));
+            paint.set_stroke_width(self.shaper.stroke_size());
             canvas.draw_line(
                 (pos.x, line_position),
                 (pos.x + width, line_position),
@@ -252,7 +339,7 @@ impl GridRenderer {
         let p2 = (p2.x.round(), (p2.y + stroke_width / 2.).round());
 
         underline_paint
-            .set_color(style.special(&self.default_style.colors).to_color())
+            .set_color(self.display_color(style.special(&self.default_style.colors)))
             .set_stroke_width(stroke_width);
 
         match underline_style {
@@ -305,3 +392,72 @@ impl GridRenderer {
         canvas.restore();
     }
 }
+
+/// Splits off the plain spaces around the text, returning the number of leading ones. Other
+/// whitespace is kept, since it can be wider than a single cell, like the ideographic space, and the
+/// leading spaces are used to offset the rest of the text.
+fn trim_spaces(text: &str) -> (usize, &str) {
+    let trimmed = text.trim_start_matches(' ');
+    let leading_spaces = text.len() - trimmed.len();
+    (leading_spaces, trimmed.trim_end_matches(' '))
+}
+
+/// Double width characters, like CJK, are drawn on their own covering two cells.
+fn is_wide_cell(text: &str, cell_width: i32) -> bool {
+    cell_width == 2 && text.graphemes(true).count() == 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_plain_spaces_are_trimmed() {
+        assert_eq!(trim_spaces("  ab  "), (2, "ab"));
+        // `listchars` glyphs for spaces, tabs and the end of line
+        assert_eq!(trim_spaces(" ·→»¬ "), (1, "·→»¬"));
+        assert_eq!(trim_spaces("\u{3000}a"), (0, "\u{3000}a"));
+        assert_eq!(trim_spaces("\u{a0} "), (0, "\u{a0}"));
+        assert_eq!(trim_spaces("   "), (3, ""));
+    }
+
+    #[test]
+    fn glyph_alignment_moves_the_glyph_bottom() {
+        let mut grid_renderer = GridRenderer::new(1.0);
+        grid_renderer.update_linespace(10.0);
+        let baseline = grid_renderer.glyph_bottom;
+
+        grid_renderer.update_glyph_alignment(GlyphAlignment::Top);
+        assert!(grid_renderer.glyph_bottom < baseline);
+        assert_eq!(
+            grid_renderer.glyph_bottom,
+            grid_renderer.shaper.glyph_bottom()
+        );
+    }
+
+    #[test]
+    fn grayscale_uses_the_luminance() {
+        let color = Color4f::new(1.0, 0.0, 0.0, 0.5);
+        assert_eq!(ColorMode::Color.convert(color), color);
+        assert_eq!(
+            ColorMode::Grayscale.convert(color),
+            Color4f::new(0.2126, 0.2126, 0.2126, 0.5)
+        );
+    }
+
+    #[test]
+    fn monochrome_thresholds_the_luminance() {
+        assert_eq!(ColorMode::new(true, 0.0), ColorMode::Grayscale);
+        assert_eq!(ColorMode::new(false, 0.5), ColorMode::Color);
+
+        let mode = ColorMode::new(true, 0.5);
+        assert_eq!(
+            mode.convert(Color4f::new(0.2, 0.9, 0.2, 1.0)),
+            Color4f::new(1.0, 1.0, 1.0, 1.0)
+        );
+        assert_eq!(
+            mode.convert(Color4f::new(0.9, 0.2, 0.9, 1.0)),
+            Color4f::new(0.0, 0.0, 0.0, 1.0)
+        );
+    }
+}
@@ -0,0 +1,167 @@
+use log::warn;
+use skia_safe::{Canvas, Data, Image, Paint, Rect};
+
+use crate::{
+    renderer::background_image::{image_rect, BackgroundImageMode},
+    units::GridScale,
+};
+
+/// Where a plugin placed an image, in cells of the window grid.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImagePlacement {
+    pub id: u64,
+    pub row: i64,
+    pub column: i64,
+    pub width: u64,
+    pub height: u64,
+}
+
+impl ImagePlacement {
+    /// Moves the image along with the scrolled lines. Returns false when it's scrolled out of the
+    /// grid completely.
+    fn scroll(&mut self, rows: i64, grid_height: u32) -> bool {
+        self.row -= rows;
+        self.row + self.height as i64 > 0 && self.row < i64::from(grid_height)
+    }
+}
+
+struct InlineImage {
+    placement: ImagePlacement,
+    /// The encoded image, until it's decoded on the next flush
+    data: Option<Vec<u8>>,
+    image: Option<Image>,
+}
+
+/// The images drawn over the cells of a window. They are only decoded on flush, and dropped when
+/// the inline images are disabled.
+pub struct InlineImages {
+    images: Vec<InlineImage>,
+}
+
+impl InlineImages {
+    pub fn new() -> Self {
+        Self { images: Vec::new() }
+    }
+
+    pub fn set(&mut self, placement: ImagePlacement, data: Vec<u8>) {
+        self.remove(placement.id);
+        self.images.push(InlineImage {
+            placement,
+            data: Some(data),
+            image: None,
+        });
+    }
+
+    pub fn remove(&mut self, id: u64) {
+        self.images.retain(|image| image.placement.id != id);
+    }
+
+    pub fn scroll(&mut self, rows: i64, grid_height: u32) {
+        self.images
+            .retain_mut(|image| image.placement.scroll(rows, grid_height));
+    }
+
+    pub fn flush(&mut self, enabled: bool) {
+        if !enabled {
+            self.images.clear();
+            return;
+        }
+
+        for image in &mut self.images {
+            if let Some(data) = image.data.take() {
+                image.image = Image::from_encoded(Data::new_copy(&data));
+                if image.image.is_none() {
+                    warn!("Could not decode the inline image {}", image.placement.id);
+                }
+            }
+        }
+    }
+
+    /// Draws the images, with `offset` being the scroll position in cells. Each image is scaled to
+    /// fit its cells and doesn't draw outside of them or `clip`.
+    pub fn draw(
+        &self,
+        canvas: &Canvas,
+        origin: (f32, f32),
+        offset: (f32, f32),
+        grid_scale: GridScale,
+        clip: Rect,
+    ) {
+        let paint = Paint::default();
+        for InlineImage {
+            placement, image, ..
+        } in &self.images
+        {
+            let Some(image) = image else {
+                continue;
+            };
+
+            let cells = Rect::from_xywh(
+                origin.0 + (placement.column as f32 - offset.0) * grid_scale.width(),
+                origin.1 + (placement.row as f32 - offset.1) * grid_scale.height(),
+                placement.width as f32 * grid_scale.width(),
+                placement.height as f32 * grid_scale.height(),
+            );
+            let image_size = (image.width() as f32, image.height() as f32);
+            let rect = image_rect(
+                BackgroundImageMode::Fit,
+                image_size,
+                (cells.width(), cells.height()),
+            )
+            .with_offset((cells.left, cells.top));
+
+            canvas.save();
+            canvas.clip_rect(clip, None, false);
+            canvas.clip_rect(cells, None, false);
+            canvas.draw_image_rect(image, None, rect, &paint);
+            canvas.restore();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn placement(id: u64, row: i64) -> ImagePlacement {
+        ImagePlacement {
+            id,
+            row,
+            column: 0,
+            width: 4,
+            height: 2,
+        }
+    }
+
+    #[test]
+    fn images_move_with_the_scrolled_lines() {
+        let mut images = InlineImages::new();
+        images.set(placement(1, 0), Vec::new());
+        images.set(placement(2, 5), Vec::new());
+
+        images.scroll(1, 10);
+        let rows: Vec<_> = images.images.iter().map(|i| i.placement.row).collect();
+        assert_eq!(rows, vec![-1, 4]);
+
+        // The first image is scrolled out, and the second one is still partly visible
+        images.scroll(4, 10);
+        let rows: Vec<_> = images.images.iter().map(|i| i.placement.row).collect();
+        assert_eq!(rows, vec![0]);
+    }
+
+    #[test]
+    fn setting_an_image_again_replaces_it() {
+        let mut images = InlineImages::new();
+        images.set(placement(1, 0), Vec::new());
+        images.set(placement(1, 3), Vec::new());
+        assert_eq!(images.images.len(), 1);
+        assert_eq!(images.images[0].placement.row, 3);
+
+        images.remove(1);
+        assert!(images.images.is_empty());
+
+        images.set(placement(1, 0), Vec::new());
+        images.flush(false);
+        assert!(images.images.is_empty());
+    }
+}
@@ -0,0 +1,95 @@
+use skia_safe::{
+    surfaces, AlphaType, BlendMode, Canvas, ColorSpace, ColorType, ISize, ImageInfo, Paint,
+    SamplingOptions, Surface,
+};
+
+/// An offscreen surface with a linear color space that the frame is drawn to, when blending in
+/// linear space is enabled. Skia blends in the color space of the surface, so everything that's
+/// translucent gets mixed the physically correct way, and the result is converted back to sRGB
+/// when the surface is drawn to the window.
+pub struct LinearBlending {
+    surface: Option<Surface>,
+}
+
+impl LinearBlending {
+    pub fn new() -> Self {
+        Self { surface: None }
+    }
+
+    /// The surface to draw the frame to instead of `root_canvas`, with the same size and transform.
+    /// It's kept between frames, and dropped when linear blending is disabled.
+    pub fn begin(&mut self, root_canvas: &Canvas, enabled: bool) -> Option<Surface> {
+        if !enabled {
+            self.surface = None;
+            return None;
+        }
+
+        let size = root_canvas.base_layer_size();
+        let mut surface = self
+            .surface
+            .take()
+            .filter(|surface| surface.image_info().dimensions() == size)
+            .or_else(|| {
+                let info = image_info(size);
+                root_canvas
+                    .new_surface(&info, None)
+                    .or_else(|| surfaces::raster(&info, None, None))
+            })?;
+        surface.canvas().set_matrix(&root_canvas.local_to_device());
+        Some(surface)
+    }
+
+    /// Draws the finished frame to `root_canvas`, replacing what's there so that the transparency
+    /// of the frame is kept.
+    pub fn end(&mut self, mut surface: Surface, root_canvas: &Canvas) {
+        let mut paint = Paint::default();
+        paint.set_blend_mode(BlendMode::Src);
+
+        root_canvas.save();
+        root_canvas.reset_matrix();
+        surface.draw(
+            root_canvas,
+            (0.0, 0.0),
+            SamplingOptions::default(),
+            Some(&paint),
+        );
+        root_canvas.restore();
+        self.surface = Some(surface);
+    }
+}
+
+/// Half floats keep the precision of the dark colors, which 8 bits per channel would lose in a
+/// linear color space.
+fn image_info(size: ISize) -> ImageInfo {
+    ImageInfo::new(
+        size,
+        ColorType::RGBAF16,
+        AlphaType::Premul,
+        ColorSpace::new_srgb_linear(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_are_drawn_in_linear_space_when_enabled() {
+        let mut root = surfaces::raster_n32_premul((20, 10)).unwrap();
+        let mut blending = LinearBlending::new();
+        assert!(blending.begin(root.canvas(), false).is_none());
+
+        let surface = blending.begin(root.canvas(), true).unwrap();
+        let info = surface.image_info();
+        assert_eq!(info.dimensions(), ISize::new(20, 10));
+        assert!(info
+            .color_space()
+            .is_some_and(|color_space| color_space.gamma_is_linear()));
+
+        blending.end(surface, root.canvas());
+        assert!(blending.surface.is_some());
+
+        assert!(blending.begin(root.canvas(), false).is_none());
+        assert!(blending.surface.is_none());
+    }
+}
@@ -1,11 +1,21 @@
 pub mod animation_utils;
+mod backend;
+mod background_image;
+mod bell;
+mod colorscheme_fade;
 pub mod cursor_renderer;
 pub mod fonts;
 pub mod grid_renderer;
+mod inline_image;
+mod linear_blending;
 pub mod opengl;
 pub mod profiler;
 mod rendered_layer;
 mod rendered_window;
+mod scrollbar;
+#[cfg(feature = "software-render")]
+mod software;
+mod spotlight;
 mod vsync;
 
 #[cfg(target_os = "windows")]
@@ -14,12 +24,17 @@ pub mod d3d;
 use std::{
     cmp::Ordering,
     collections::{hash_map::Entry, HashMap},
-    sync::Arc,
+    num::NonZeroUsize,
+    sync::{Arc, OnceLock},
+    time::Instant,
 };
 
 use itertools::Itertools;
-use log::{error, warn};
-use skia_safe::Canvas;
+use log::{error, info, warn};
+use skia_safe::{
+    surfaces, Canvas, Color, Data, EncodedImageFormat, ISize, Paint, PaintStyle, PictureRecorder,
+    Rect,
+};
 
 use winit::{
     event::WindowEvent,
@@ -34,8 +49,8 @@ use crate::{
     profiling::{tracy_create_gpu_context, tracy_named_frame, tracy_zone},
     renderer::rendered_layer::{group_windows, FloatingLayer},
     settings::*,
-    units::{to_skia_rect, GridPos, GridRect, GridSize, PixelPos},
-    window::{ShouldRender, UserEvent},
+    units::{to_skia_point, to_skia_rect, GridPos, GridRect, GridSize, PixelPos},
+    window::{is_motion_reduced, ShouldRender, UserEvent},
     WindowSettings,
 };
 
@@ -53,12 +68,23 @@ use crate::profiling::GpuCtx;
 
 use cursor_renderer::CursorRenderer;
 pub use fonts::caching_shaper::CachingShaper;
+use grid_renderer::ColorMode;
 pub use grid_renderer::GridRenderer;
+pub use inline_image::ImagePlacement;
 pub use rendered_window::{LineFragment, RenderedWindow, WindowDrawCommand, WindowDrawDetails};
+pub use scrollbar::{ScrollbarDetails, ScrollbarPosition};
 
+pub use backend::GraphicsBackend;
 pub use vsync::VSync;
 
-use self::fonts::font_options::FontOptions;
+use self::fonts::{
+    caching_shaper::{GlyphAlignment, ShapeCacheLimit, ShapingFeatures},
+    font_options::FontOptions,
+};
+use background_image::{BackgroundImage, BackgroundImageFilter, BackgroundImageMode};
+use bell::VisualBell;
+use colorscheme_fade::ColorschemeFade;
+use linear_blending::LinearBlending;
 
 #[cfg(feature = "profiling")]
 fn plot_skia_cache() {
@@ -85,6 +111,7 @@ pub struct RendererSettings {
     position_animation_length: f32,
     scroll_animation_length: f32,
     scroll_animation_far_lines: u32,
+    horizontal_scroll_animation_length: f32,
     floating_blur: bool,
     floating_blur_amount_x: f32,
     floating_blur_amount_y: f32,
@@ -99,6 +126,29 @@ pub struct RendererSettings {
     text_gamma: f32,
     text_contrast: f32,
     experimental_layer_grouping: bool,
+    grayscale: bool,
+    grayscale_threshold: f32,
+    focus_ring_color: String,
+    focus_ring_width: f32,
+    font_kerning: bool,
+    font_ligatures: bool,
+    background_image: String,
+    background_image_mode: BackgroundImageMode,
+    background_image_filter: BackgroundImageFilter,
+    background_image_opacity: f32,
+    inline_images: bool,
+    scrollbar: bool,
+    scrollbar_width: f32,
+    scrollbar_color: String,
+    spotlight: bool,
+    spotlight_dim: f32,
+    spotlight_radius: f32,
+    shaping_cache_size: u32,
+    shaping_cache_memory: f32,
+    shaping_warm_up: bool,
+    glyph_alignment: GlyphAlignment,
+    colorscheme_fade_length: f32,
+    linear_blending: bool,
 }
 
 impl Default for RendererSettings {
@@ -107,6 +157,7 @@ impl Default for RendererSettings {
             position_animation_length: 0.15,
             scroll_animation_length: 0.3,
             scroll_animation_far_lines: 1,
+            horizontal_scroll_animation_length: 0.3,
             floating_blur: true,
             floating_blur_amount_x: 2.0,
             floating_blur_amount_y: 2.0,
@@ -121,6 +172,29 @@ impl Default for RendererSettings {
             text_gamma: 0.0,
             text_contrast: 0.5,
             experimental_layer_grouping: false,
+            grayscale: false,
+            grayscale_threshold: 0.0,
+            focus_ring_color: "".to_string(),
+            focus_ring_width: 2.0,
+            font_kerning: true,
+            font_ligatures: true,
+            background_image: "".to_string(),
+            background_image_mode: BackgroundImageMode::Fill,
+            background_image_filter: BackgroundImageFilter::Linear,
+            background_image_opacity: 1.0,
+            inline_images: false,
+            scrollbar: false,
+            scrollbar_width: 4.0,
+            scrollbar_color: "".to_string(),
+            spotlight: false,
+            spotlight_dim: 0.3,
+            spotlight_radius: 4.0,
+            shaping_cache_size: 10000,
+            shaping_cache_memory: 0.0,
+            shaping_warm_up: false,
+            glyph_alignment: GlyphAlignment::Baseline,
+            colorscheme_fade_length: 0.0,
+            linear_blending: false,
         }
     }
 }
@@ -134,6 +208,7 @@ impl Default for RendererSettings {
 pub enum DrawCommand {
     UpdateCursor(Cursor),
     FontChanged(String),
+    WideFontChanged(String),
     LineSpaceChanged(f32),
     DefaultStyleChanged(Style),
     ModeChanged(EditorMode),
@@ -154,6 +229,15 @@ pub struct Renderer {
     pub window_regions: Vec<WindowDrawDetails>,
 
     profiler: profiler::Profiler,
+    window_has_focus: bool,
+    background_image: BackgroundImage,
+    visual_bell: VisualBell,
+    colorscheme_fade: ColorschemeFade,
+    linear_blending: LinearBlending,
+    // The size of the last drawn frame, to draw it again for a screenshot or when the colorscheme
+    // changes
+    frame_size: Option<ISize>,
+    has_default_style: bool,
     pub os_scale_factor: f64,
     pub user_scale_factor: f64,
 }
@@ -162,6 +246,7 @@ pub struct Renderer {
 pub struct DrawCommandResult {
     pub font_changed: bool,
     pub should_show: bool,
+    pub default_style_changed: bool,
 }
 
 impl Renderer {
@@ -173,6 +258,21 @@ impl Renderer {
         let cursor_renderer = CursorRenderer::new();
         let mut grid_renderer = GridRenderer::new(scale_factor);
         grid_renderer.update_font_options(init_font_settings.map(|x| x.into()).unwrap_or_default());
+        let renderer_settings = SETTINGS.get::<RendererSettings>();
+        grid_renderer.set_color_mode(ColorMode::new(
+            renderer_settings.grayscale,
+            renderer_settings.grayscale_threshold,
+        ));
+        grid_renderer
+            .shaper
+            .set_shaping_features(shaping_features(&renderer_settings));
+        grid_renderer
+            .shaper
+            .set_cache_limit(shape_cache_limit(&renderer_settings));
+        grid_renderer
+            .shaper
+            .set_warm_up(renderer_settings.shaping_warm_up);
+        grid_renderer.update_glyph_alignment(renderer_settings.glyph_alignment);
         let current_mode = EditorMode::Unknown(String::from(""));
 
         let rendered_windows = HashMap::new();
@@ -187,15 +287,68 @@ impl Renderer {
             current_mode,
             window_regions,
             profiler,
+            window_has_focus: true,
+            background_image: BackgroundImage::new(),
+            visual_bell: VisualBell::new(),
+            colorscheme_fade: ColorschemeFade::new(),
+            linear_blending: LinearBlending::new(),
+            frame_size: None,
+            has_default_style: false,
             os_scale_factor,
             user_scale_factor,
         }
     }
 
     pub fn handle_event(&mut self, event: &WindowEvent) {
+        if let WindowEvent::Focused(is_focused) = event {
+            self.window_has_focus = *is_focused;
+        }
         self.cursor_renderer.handle_event(event);
     }
 
+    pub fn update_color_mode(&mut self) {
+        let settings = SETTINGS.get::<RendererSettings>();
+        self.grid_renderer.set_color_mode(ColorMode::new(
+            settings.grayscale,
+            settings.grayscale_threshold,
+        ));
+    }
+
+    pub fn update_shaping_features(&mut self) {
+        let settings = SETTINGS.get::<RendererSettings>();
+        self.grid_renderer
+            .shaper
+            .set_shaping_features(shaping_features(&settings));
+    }
+
+    pub fn update_shaping_cache_limit(&mut self) {
+        let settings = SETTINGS.get::<RendererSettings>();
+        self.grid_renderer
+            .shaper
+            .set_cache_limit(shape_cache_limit(&settings));
+    }
+
+    pub fn update_shaping_warm_up(&mut self) {
+        let settings = SETTINGS.get::<RendererSettings>();
+        self.grid_renderer
+            .shaper
+            .set_warm_up(settings.shaping_warm_up);
+    }
+
+    pub fn update_glyph_alignment(&mut self) {
+        let settings = SETTINGS.get::<RendererSettings>();
+        self.grid_renderer
+            .update_glyph_alignment(settings.glyph_alignment);
+    }
+
+    pub fn set_secondary_cursors(&mut self, positions: Vec<GridPos<u64>>) {
+        self.cursor_renderer.set_secondary_cursors(positions);
+    }
+
+    pub fn set_cursor_tab_span(&mut self, tab_span: Option<(u64, u64)>) {
+        self.cursor_renderer.set_tab_span(tab_span);
+    }
+
     pub fn font_names(&self) -> Vec<String> {
         self.grid_renderer.font_names()
     }
@@ -204,18 +357,45 @@ impl Renderer {
         self.cursor_renderer.prepare_frame()
     }
 
-    pub fn draw_frame(&mut self, root_canvas: &Canvas, dt: f32) {
+    /// When the next frame is needed for the cursor blink, independently of the frame rate.
+    pub fn next_wake_time(&self) -> Option<Instant> {
+        self.cursor_renderer.next_wake_time()
+    }
+
+    /// Draws the frame at `frame_size`, which is the size of the window unless the caller scales
+    /// the frame to fit it.
+    pub fn draw_frame(&mut self, root_canvas: &Canvas, frame_size: ISize, dt: f32) {
         tracy_zone!("renderer_draw_frame");
+        self.frame_size = Some(frame_size);
+        let linear_blending = SETTINGS.get::<RendererSettings>().linear_blending;
+        match self.linear_blending.begin(root_canvas, linear_blending) {
+            Some(mut surface) => {
+                self.draw_scene(surface.canvas(), Some(dt));
+                self.linear_blending.end(surface, root_canvas);
+            }
+            None => self.draw_scene(root_canvas, Some(dt)),
+        }
+    }
+
+    /// Draws everything on the screen. The profiler is left out without a frame time, when the
+    /// frame is only recorded.
+    fn draw_scene(&mut self, root_canvas: &Canvas, dt: Option<f32>) {
         let default_background = self.grid_renderer.get_default_background();
         let grid_scale = self.grid_renderer.grid_scale;
 
         let transparency = SETTINGS.get::<WindowSettings>().transparency;
-        let layer_grouping = SETTINGS
-            .get::<RendererSettings>()
-            .experimental_layer_grouping;
+        let settings = SETTINGS.get::<RendererSettings>();
+        let layer_grouping = settings.experimental_layer_grouping;
+        // The layers over the whole frame are drawn with the transform of the caller too
+        let frame = Rect::from_isize(
+            self.frame_size
+                .unwrap_or_else(|| root_canvas.base_layer_size()),
+        );
         root_canvas.clear(default_background.with_a((255.0 * transparency) as u8));
+        let has_background_image = self.background_image.update(&settings);
+        self.background_image.draw(root_canvas, frame, &settings);
+        // Keep the transform of the caller, which scales the content in fullscreen transitions
         root_canvas.save();
-        root_canvas.reset_matrix();
 
         if let Some(root_window) = self.rendered_windows.get(&1) {
             let clip_rect = to_skia_rect(&root_window.pixel_region(grid_scale));
@@ -284,14 +464,16 @@ impl Renderer {
             (root_windows, floating_layers)
         };
 
-        let settings = SETTINGS.get::<RendererSettings>();
         let root_window_regions = root_windows
             .into_iter()
             .map(|window| {
+                let background = window.background(&self.grid_renderer);
                 window.draw(
                     root_canvas,
-                    default_background.with_a((255.0 * transparency) as u8),
+                    &settings,
+                    background.with_a((255.0 * transparency) as u8),
                     grid_scale,
+                    has_background_image,
                 )
             })
             .collect_vec();
@@ -303,7 +485,7 @@ impl Renderer {
                     root_canvas,
                     &settings,
                     default_background.with_a((255.0 * transparency) as u8),
-                    grid_scale,
+                    &self.grid_renderer,
                 )
             })
             .collect_vec();
@@ -315,10 +497,39 @@ impl Renderer {
         self.cursor_renderer
             .draw(&mut self.grid_renderer, root_canvas);
 
-        self.profiler.draw(root_canvas, dt);
+        if let Some(dt) = dt {
+            self.profiler.draw(root_canvas, dt);
+        }
 
         root_canvas.restore();
 
+        if settings.spotlight {
+            let center = self.cursor_renderer.current_center();
+            spotlight::draw_spotlight(
+                root_canvas,
+                frame,
+                to_skia_point(center),
+                settings.spotlight_radius * grid_scale.height(),
+                settings.spotlight_dim,
+                default_background,
+            );
+        }
+
+        let bell_flash_length = SETTINGS.get::<WindowSettings>().bell_flash_length;
+        self.visual_bell.draw(
+            root_canvas,
+            frame,
+            self.grid_renderer.get_default_foreground(),
+            bell_flash_length,
+        );
+
+        if self.window_has_focus {
+            draw_focus_ring(root_canvas, &settings);
+        }
+
+        self.colorscheme_fade
+            .draw(root_canvas, settings.colorscheme_fade_length);
+
         #[cfg(feature = "profiling")]
         plot_skia_cache();
     }
@@ -342,7 +553,12 @@ impl Renderer {
             root_windows.into_iter().chain(floating_windows)
         };
 
-        let settings = SETTINGS.get::<RendererSettings>();
+        let mut settings = SETTINGS.get::<RendererSettings>();
+        if is_motion_reduced() {
+            settings.position_animation_length = 0.0;
+            settings.scroll_animation_length = 0.0;
+            settings.horizontal_scroll_animation_length = 0.0;
+        }
         // Clippy recommends short-circuiting with any which is not what we want
         #[allow(clippy::unnecessary_fold)]
         let mut animating = windows.fold(false, |acc, window| {
@@ -358,6 +574,12 @@ impl Renderer {
             .cursor_renderer
             .animate(&self.current_mode, &self.grid_renderer, dt);
 
+        let bell_flash_length = SETTINGS.get::<WindowSettings>().bell_flash_length;
+        animating |= self.visual_bell.animate(dt, bell_flash_length);
+        animating |= self
+            .colorscheme_fade
+            .animate(dt, settings.colorscheme_fade_length);
+
         animating
     }
 
@@ -380,8 +602,20 @@ impl Renderer {
         let mut result = DrawCommandResult {
             font_changed: false,
             should_show: false,
+            default_style_changed: false,
         };
 
+        // The window commands are sorted first in the batch, so the old frame has to be recorded
+        // before any of them changes the lines
+        let default_style_changes = batch.iter().any(|draw_command| {
+            matches!(draw_command, DrawCommand::DefaultStyleChanged(style)
+                if *style != *self.grid_renderer.default_style)
+        });
+        if default_style_changes && self.has_default_style && settings.colorscheme_fade_length > 0.0
+        {
+            self.start_colorscheme_fade();
+        }
+
         for draw_command in batch {
             self.handle_draw_command(draw_command, &mut result);
             tracy_named_frame!("neovim draw batch processed");
@@ -391,6 +625,27 @@ impl Renderer {
         result
     }
 
+    /// Draws the current frame again into memory and encodes it as a PNG.
+    pub fn screenshot(&mut self) -> Option<Data> {
+        let mut surface = surfaces::raster_n32_premul(self.frame_size?)?;
+        self.draw_scene(surface.canvas(), None);
+        surface
+            .image_snapshot()
+            .encode(None, EncodedImageFormat::PNG, None)
+    }
+
+    fn start_colorscheme_fade(&mut self) {
+        let Some(frame_size) = self.frame_size else {
+            return;
+        };
+        let mut recorder = PictureRecorder::new();
+        let canvas = recorder.begin_recording(Rect::from_isize(frame_size), None);
+        self.draw_scene(canvas, None);
+        if let Some(old_frame) = recorder.finish_recording_as_picture(None) {
+            self.colorscheme_fade.start(old_frame);
+        }
+    }
+
     pub fn handle_os_scale_factor_change(&mut self, os_scale_factor: f64) {
         self.os_scale_factor = os_scale_factor;
         self.grid_renderer
@@ -451,12 +706,19 @@ impl Renderer {
                 self.grid_renderer.update_font(&new_font);
                 result.font_changed = true;
             }
+            DrawCommand::WideFontChanged(new_wide_font) => {
+                self.grid_renderer.update_wide_font(&new_wide_font);
+                // The lines keep the blobs they were shaped to, which have to be shaped again
+                result.font_changed = true;
+            }
             DrawCommand::LineSpaceChanged(new_linespace) => {
                 self.grid_renderer.update_linespace(new_linespace);
                 result.font_changed = true;
             }
             DrawCommand::DefaultStyleChanged(new_style) => {
                 self.grid_renderer.default_style = Arc::new(new_style);
+                self.has_default_style = true;
+                result.default_style_changed = true;
             }
             DrawCommand::ModeChanged(new_mode) => {
                 self.current_mode = new_mode;
@@ -474,6 +736,10 @@ impl Renderer {
             .for_each(|(_, w)| w.flush(renderer_settings));
     }
 
+    pub fn ring_visual_bell(&mut self) {
+        self.visual_bell.ring();
+    }
+
     pub fn get_cursor_destination(&self) -> PixelPos<f32> {
         self.cursor_renderer.get_destination()
     }
@@ -487,6 +753,64 @@ impl Renderer {
     }
 }
 
+fn shaping_features(settings: &RendererSettings) -> ShapingFeatures {
+    ShapingFeatures {
+        kerning: settings.font_kerning,
+        ligatures: settings.font_ligatures,
+    }
+}
+
+/// A memory budget takes precedence over the number of entries
+fn shape_cache_limit(settings: &RendererSettings) -> ShapeCacheLimit {
+    if settings.shaping_cache_memory > 0.0 {
+        ShapeCacheLimit::Bytes((settings.shaping_cache_memory * 1024.0 * 1024.0) as usize)
+    } else {
+        ShapeCacheLimit::Entries(
+            NonZeroUsize::new(settings.shaping_cache_size as usize).unwrap_or(NonZeroUsize::MIN),
+        )
+    }
+}
+
+/// Parses an optional CSS color setting, where an empty or invalid color means it's not set. This
+/// is called every frame, so invalid colors aren't reported.
+fn parse_color_setting(color: &str) -> Option<Color> {
+    if color.is_empty() {
+        return None;
+    }
+    let [r, g, b, a] = color.parse::<csscolorparser::Color>().ok()?.to_rgba8();
+    Some(Color::from_argb(a, r, g, b))
+}
+
+/// Outlines the whole window, including the padding, so it's easy to find across monitors.
+fn draw_focus_ring(canvas: &Canvas, settings: &RendererSettings) {
+    let Some(color) = parse_color_setting(&settings.focus_ring_color) else {
+        return;
+    };
+    let width = settings.focus_ring_width;
+    if width <= 0.0 {
+        return;
+    }
+
+    let size = canvas.base_layer_size();
+    let inset = width / 2.0;
+    let rect = Rect::from_xywh(
+        inset,
+        inset,
+        size.width as f32 - width,
+        size.height as f32 - width,
+    );
+
+    let mut paint = Paint::default();
+    paint
+        .set_color(color)
+        .set_style(PaintStyle::Stroke)
+        .set_stroke_width(width);
+    canvas.save();
+    canvas.reset_matrix();
+    canvas.draw_rect(rect, &paint);
+    canvas.restore();
+}
+
 /// Defines how floating windows are sorted.
 fn floating_sort(window_a: &&mut RenderedWindow, window_b: &&mut RenderedWindow) -> Ordering {
     let orda = &window_a.anchor_info.as_ref().unwrap().sort_order;
@@ -498,6 +822,9 @@ pub enum WindowConfigType {
     OpenGL(glutin::config::Config),
     #[cfg(target_os = "windows")]
     Direct3D,
+    /// Rendered on the CPU
+    #[cfg(feature = "software-render")]
+    Software,
 }
 
 pub struct WindowConfig {
@@ -505,29 +832,71 @@ pub struct WindowConfig {
     pub config: WindowConfigType,
 }
 
+/// The graphics backend that is used. It's only resolved once, since the window attributes depend
+/// on it as well.
+pub fn selected_backend() -> GraphicsBackend {
+    static BACKEND: OnceLock<GraphicsBackend> = OnceLock::new();
+    *BACKEND.get_or_init(|| {
+        let cmd_line_settings = SETTINGS.get::<CmdLineSettings>();
+        #[cfg(target_os = "windows")]
+        let requested = if cmd_line_settings.opengl {
+            GraphicsBackend::OpenGL
+        } else {
+            cmd_line_settings.backend
+        };
+        #[cfg(not(target_os = "windows"))]
+        let requested = cmd_line_settings.backend;
+
+        #[cfg(target_os = "windows")]
+        let direct3d_available = requested != GraphicsBackend::OpenGL && d3d::is_available();
+        #[cfg(not(target_os = "windows"))]
+        let direct3d_available = false;
+
+        let backend = requested.resolve(direct3d_available);
+        info!("Using the {backend} graphics backend (requested {requested})");
+        backend
+    })
+}
+
 pub fn build_window_config(
     window_attributes: WindowAttributes,
     event_loop: &ActiveEventLoop,
 ) -> WindowConfig {
+    let backend = selected_backend();
+
     #[cfg(target_os = "windows")]
-    {
-        let cmd_line_settings = SETTINGS.get::<CmdLineSettings>();
-        if cmd_line_settings.opengl {
-            opengl::build_window(window_attributes, event_loop)
-        } else {
-            let window = event_loop.create_window(window_attributes).unwrap();
-            let config = WindowConfigType::Direct3D;
-            WindowConfig { window, config }
-        }
+    if backend == GraphicsBackend::Direct3D {
+        let window = event_loop.create_window(window_attributes).unwrap();
+        let config = WindowConfigType::Direct3D;
+        return WindowConfig { window, config };
     }
-
     #[cfg(not(target_os = "windows"))]
-    {
-        opengl::build_window(window_attributes, event_loop)
+    debug_assert_ne!(backend, GraphicsBackend::Direct3D);
+
+    if SETTINGS.get::<CmdLineSettings>().software_render {
+        #[cfg(feature = "software-render")]
+        return build_software_window(window_attributes, event_loop);
+        #[cfg(not(feature = "software-render"))]
+        warn!("Neovide was built without the software-render feature, falling back to OpenGL");
     }
+    opengl::build_window(window_attributes, event_loop)
+}
+
+#[cfg(feature = "software-render")]
+fn build_software_window(
+    window_attributes: WindowAttributes,
+    event_loop: &ActiveEventLoop,
+) -> WindowConfig {
+    let window = event_loop
+        .create_window(window_attributes)
+        .expect("Failed to create Window");
+    let config = WindowConfigType::Software;
+    WindowConfig { window, config }
 }
 
 pub trait SkiaRenderer {
+    /// What the frames are rendered with, as shown in the diagnostics
+    fn name(&self) -> &'static str;
     fn window(&self) -> &Window;
     fn flush(&mut self);
     fn swap_buffers(&mut self);
@@ -543,13 +912,33 @@ pub fn create_skia_renderer(
     srgb: bool,
     vsync: bool,
 ) -> Box<dyn SkiaRenderer> {
-    let renderer: Box<dyn SkiaRenderer> = match &window.config {
-        WindowConfigType::OpenGL(..) => {
-            Box::new(opengl::OpenGLSkiaRenderer::new(window, srgb, vsync))
+    let renderer: Box<dyn SkiaRenderer> = match window.config {
+        WindowConfigType::OpenGL(config) => {
+            match opengl::OpenGLSkiaRenderer::new(window.window, config, srgb, vsync) {
+                Ok(renderer) => Box::new(renderer),
+                #[cfg(feature = "software-render")]
+                Err((window, error)) => {
+                    warn!("Failed to create the OpenGL context, falling back to software rendering: {error}");
+                    create_software_renderer(window)
+                }
+                #[cfg(not(feature = "software-render"))]
+                Err((_, error)) => panic!("Failed to create the OpenGL context: {error}"),
+            }
         }
         #[cfg(target_os = "windows")]
         WindowConfigType::Direct3D => Box::new(d3d::D3DSkiaRenderer::new(window.window)),
+        #[cfg(feature = "software-render")]
+        WindowConfigType::Software => create_software_renderer(window.window),
     };
     tracy_create_gpu_context("main_render_context", renderer.as_ref());
     renderer
 }
+
+#[cfg(feature = "software-render")]
+fn create_software_renderer(window: Window) -> Box<dyn SkiaRenderer> {
+    info!("Rendering on the CPU");
+    Box::new(
+        software::SoftwareSkiaRenderer::new(window)
+            .expect("Failed to create the software renderer"),
+    )
+}
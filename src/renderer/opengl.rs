@@ -2,6 +2,7 @@ use std::{
     convert::TryInto,
     env,
     env::consts::OS,
+    error::Error,
     ffi::{c_void, CStr, CString},
     num::NonZeroU32,
 };
@@ -16,6 +17,7 @@ use glutin::{
     surface::{Surface, SurfaceAttributesBuilder, WindowSurface},
 };
 use glutin_winit::DisplayBuilder;
+use log::info;
 use raw_window_handle::HasWindowHandle;
 use skia_safe::{
     canvas::Canvas,
@@ -37,6 +39,8 @@ pub use super::vsync::VSyncWinDwm;
 #[cfg(target_os = "macos")]
 pub use super::vsync::VSyncMacos;
 
+#[cfg(feature = "software-render")]
+use super::build_software_window;
 use super::{RendererSettings, SkiaRenderer, VSync, WindowConfig, WindowConfigType};
 
 use crate::{profiling::tracy_gpu_zone, settings::SETTINGS, window::UserEvent};
@@ -68,16 +72,31 @@ fn get_proc_address(surface: &Surface<WindowSurface>, addr: &CStr) -> *const c_v
 }
 
 impl OpenGLSkiaRenderer {
-    pub fn new(window: WindowConfig, srgb: bool, vsync: bool) -> Self {
-        #[allow(irrefutable_let_patterns)] // This can only be something else than OpenGL on Windows
-        let config = if let WindowConfigType::OpenGL(config) = window.config {
-            config
-        } else {
-            panic!("Not an opengl window");
-        };
-        let window = window.window;
+    /// Fails when the driver can't create any part of the context, the window is given back then,
+    /// so that it can be rendered to in another way.
+    pub fn new(
+        window: Window,
+        config: Config,
+        srgb: bool,
+        vsync: bool,
+    ) -> Result<Self, (Window, Box<dyn Error>)> {
+        match Self::create(&window, config, srgb, vsync) {
+            Ok(mut renderer) => {
+                renderer.window = Some(window);
+                Ok(renderer)
+            }
+            Err(error) => Err((window, error)),
+        }
+    }
+
+    fn create(
+        window: &Window,
+        config: Config,
+        srgb: bool,
+        vsync: bool,
+    ) -> Result<Self, Box<dyn Error>> {
         let gl_display = config.display();
-        let raw_window_handle = window.window_handle().unwrap().as_raw();
+        let raw_window_handle = window.window_handle()?.as_raw();
 
         let size = clamp_render_buffer_size(&window.inner_size());
 
@@ -89,16 +108,13 @@ impl OpenGLSkiaRenderer {
                 NonZeroU32::new(size.height).unwrap(),
             );
         let window_surface =
-            unsafe { gl_display.create_window_surface(&config, &surface_attributes) }
-                .expect("Failed to create Windows Surface");
+            unsafe { gl_display.create_window_surface(&config, &surface_attributes) }?;
 
         let context_attributes = ContextAttributesBuilder::new()
             .with_profile(GlProfile::Core)
             .build(Some(raw_window_handle));
-        let context = unsafe { gl_display.create_context(&config, &context_attributes) }
-            .expect("Failed to create OpenGL context")
-            .make_current(&window_surface)
-            .unwrap();
+        let context = unsafe { gl_display.create_context(&config, &context_attributes) }?
+            .make_current(&window_surface)?;
 
         // NOTE: We don't care if these fails, the driver can override the SwapInterval in any case, so it needs to work in all cases
         // The OpenGL VSync is always disabled on Wayland and Windows, since they have their own
@@ -119,16 +135,16 @@ impl OpenGLSkiaRenderer {
             }
             get_proc_address(&window_surface, CString::new(name).unwrap().as_c_str())
         })
-        .expect("Could not create interface");
+        .ok_or("Could not create interface")?;
 
         let mut gr_context = skia_safe::gpu::direct_contexts::make_gl(interface, None)
-            .expect("Could not create direct context");
+            .ok_or("Could not create direct context")?;
         let fb_info = {
             let mut fboid: GLint = 0;
             unsafe { gl::GetIntegerv(gl::FRAMEBUFFER_BINDING, &mut fboid) };
 
             FramebufferInfo {
-                fboid: fboid.try_into().expect("Could not create frame buffer id"),
+                fboid: fboid.try_into()?,
                 format: skia_safe::gpu::gl::Format::RGBA8.into(),
                 ..Default::default()
             }
@@ -140,21 +156,25 @@ impl OpenGLSkiaRenderer {
             &window_surface,
             &mut gr_context,
             &fb_info,
-        );
+        )?;
 
-        Self {
+        Ok(Self {
             window_surface,
             context,
-            window: Some(window),
+            window: None,
             config,
             gr_context,
             fb_info,
             skia_surface,
-        }
+        })
     }
 }
 
 impl SkiaRenderer for OpenGLSkiaRenderer {
+    fn name(&self) -> &'static str {
+        "opengl"
+    }
+
     fn window(&self) -> &Window {
         self.window.as_ref().unwrap()
     }
@@ -179,14 +199,17 @@ impl SkiaRenderer for OpenGLSkiaRenderer {
     }
 
     fn resize(&mut self) {
-        self.skia_surface = create_surface(
+        match create_surface(
             &self.config,
             &self.window().inner_size(),
             &self.context,
             &self.window_surface,
             &mut self.gr_context,
             &self.fb_info,
-        );
+        ) {
+            Ok(skia_surface) => self.skia_surface = skia_surface,
+            Err(error) => log::error!("Could not resize the OpenGL surface: {error}"),
+        }
     }
 
     #[allow(unused_variables)]
@@ -237,24 +260,58 @@ impl Drop for OpenGLSkiaRenderer {
     }
 }
 
-fn gen_config(mut config_iterator: Box<dyn Iterator<Item = Config> + '_>) -> Config {
-    config_iterator.next().unwrap()
+/// Prefers a hardware accelerated config, but takes any config instead of failing, so that
+/// machines with only a software OpenGL driver still use it.
+fn gen_config(config_iterator: Box<dyn Iterator<Item = Config> + '_>) -> Config {
+    let configs: Vec<Config> = config_iterator.collect();
+    let preferred = configs
+        .iter()
+        .position(|config| config.hardware_accelerated())
+        .unwrap_or(0);
+    configs
+        .into_iter()
+        .nth(preferred)
+        .expect("Failed to find any OpenGL configs")
 }
 
-pub fn build_window(
+fn create_window(
     window_attributes: WindowAttributes,
     event_loop: &ActiveEventLoop,
-) -> WindowConfig {
+) -> Result<(Window, Config), Box<dyn Error>> {
     let template_builder = ConfigTemplateBuilder::new()
         .with_stencil_size(8)
         .with_transparency(true);
     let (window, config) = DisplayBuilder::new()
         .with_window_attributes(Some(window_attributes))
-        .build(event_loop, template_builder, gen_config)
-        .expect("Failed to create Window");
-    let window = window.expect("Could not create Window");
-    let config = WindowConfigType::OpenGL(config);
-    WindowConfig { window, config }
+        .build(event_loop, template_builder, gen_config)?;
+    let window = window.ok_or("Could not create Window")?;
+    Ok((window, config))
+}
+
+/// Gives a window without an OpenGL config when OpenGL can't be initialized, which is then
+/// rendered on the CPU, if Neovide is built with the `software-render` feature.
+pub fn build_window(
+    window_attributes: WindowAttributes,
+    event_loop: &ActiveEventLoop,
+) -> WindowConfig {
+    match create_window(window_attributes.clone(), event_loop) {
+        Ok((window, config)) => {
+            if config.hardware_accelerated() {
+                info!("Rendering with hardware accelerated OpenGL");
+            } else {
+                info!("Rendering with software OpenGL");
+            }
+            let config = WindowConfigType::OpenGL(config);
+            WindowConfig { window, config }
+        }
+        #[cfg(feature = "software-render")]
+        Err(error) => {
+            log::warn!("Failed to initialize OpenGL, falling back to software rendering: {error}");
+            build_software_window(window_attributes, event_loop)
+        }
+        #[cfg(not(feature = "software-render"))]
+        Err(error) => panic!("Failed to initialize OpenGL: {error}"),
+    }
 }
 
 fn create_surface(
@@ -264,7 +321,7 @@ fn create_surface(
     window_surface: &Surface<WindowSurface>,
     gr_context: &mut DirectContext,
     fb_info: &FramebufferInfo,
-) -> skia_safe::Surface {
+) -> Result<skia_safe::Surface, Box<dyn Error>> {
     let size = clamp_render_buffer_size(size);
     let backend_render_target = make_gl(
         size.into(),
@@ -297,5 +354,5 @@ fn create_surface(
         ColorSpace::new_srgb(),
         Some(surface_props).as_ref(),
     )
-    .expect("Could not create skia backend render target")
+    .ok_or_else(|| "Could not create skia backend render target".into())
 }
@@ -10,7 +10,7 @@ use glamour::Intersection;
 
 use crate::units::{to_skia_rect, GridScale, PixelRect};
 
-use super::{RenderedWindow, RendererSettings, WindowDrawDetails};
+use super::{GridRenderer, RenderedWindow, RendererSettings, WindowDrawDetails};
 
 struct LayerWindow<'w> {
     window: &'w mut RenderedWindow,
@@ -27,8 +27,9 @@ impl<'w> FloatingLayer<'w> {
         root_canvas: &Canvas,
         settings: &RendererSettings,
         default_background: Color,
-        grid_scale: GridScale,
+        grid_renderer: &GridRenderer,
     ) -> Vec<WindowDrawDetails> {
+        let grid_scale = grid_renderer.grid_scale;
         let pixel_regions = self
             .windows
             .iter()
@@ -76,7 +77,9 @@ impl<'w> FloatingLayer<'w> {
         let save_layer_rec = SaveLayerRec::default().bounds(&bound_rect).paint(&paint);
 
         root_canvas.save_layer(&save_layer_rec);
-        root_canvas.clear(default_background.with_a(255));
+        root_canvas.clear(Color::TRANSPARENT);
+        let mut background_paint = Paint::default();
+        background_paint.set_anti_alias(false);
 
         let regions = self
             .windows
@@ -88,11 +91,23 @@ impl<'w> FloatingLayer<'w> {
 
         (0..self.windows.len()).for_each(|i| {
             let window = &mut self.windows[i];
+            // Blended windows are drawn in a layer of their own, so that both the backgrounds and
+            // the text are blended with what's beneath them
+            let blend = window.blend();
+            if blend > 0 {
+                root_canvas.save_layer_alpha(to_skia_rect(&regions[i]), blend_alpha(blend).into());
+            }
+            background_paint.set_color(window.background(grid_renderer).with_a(255));
+            root_canvas.draw_rect(to_skia_rect(&regions[i]), &background_paint);
             window.draw_background_surface(root_canvas, regions[i], grid_scale);
             window.draw_foreground_surface(root_canvas, regions[i], grid_scale);
+            if blend > 0 {
+                root_canvas.restore();
+            }
             ret.push(WindowDrawDetails {
                 id: window.id,
                 region: regions[i],
+                scrollbar: None,
             });
         });
 
@@ -137,6 +152,12 @@ impl<'w> FloatingLayer<'w> {
     }
 }
 
+/// The opacity of a window with the `blend` percentage, like `winblend`.
+fn blend_alpha(blend: u8) -> u8 {
+    let opacity = (100 - blend.min(100)) as f32 / 100.0;
+    (opacity * 255.0).round() as u8
+}
+
 fn get_window_group(windows: &mut Vec<LayerWindow>, index: usize) -> usize {
     if windows[index].group != index {
         windows[index].group = get_window_group(windows, windows[index].group);
@@ -230,3 +251,62 @@ fn rect_to_round_rect_path(rect: &Rect, settings: &RendererSettings, grid_scale:
 
     rounded_path
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use skia_safe::{colors, surfaces, AlphaType, ColorType, ImageInfo};
+
+    use super::*;
+    use crate::{
+        editor::{Colors, Style},
+        renderer::{GridRenderer, LineFragment, WindowDrawCommand},
+        settings::SETTINGS,
+        units::{GridPos, GridSize},
+    };
+
+    #[test]
+    fn blended_window_shows_the_grid_beneath() {
+        SETTINGS.set(&RendererSettings::default());
+        let settings = SETTINGS.get::<RendererSettings>();
+        let mut grid_renderer = GridRenderer::new(1.0);
+        let grid_scale = grid_renderer.grid_scale;
+
+        // A float with `winblend=50` and a blue background over a red base grid
+        let style = Style {
+            blend: 50,
+            ..Style::new(Colors::new(Some(colors::WHITE), Some(colors::BLUE), None))
+        };
+        let mut window = RenderedWindow::new(2, GridPos::new(0, 0), GridSize::new(4, 1));
+        window.handle_window_draw_command(WindowDrawCommand::DrawLine {
+            row: 0,
+            line_fragments: vec![LineFragment {
+                text: "    ".to_string(),
+                window_left: 0,
+                width: 4,
+                style: Some(Arc::new(style)),
+            }],
+        });
+        window.prepare_lines(&mut grid_renderer, true);
+        assert_eq!(window.blend(), 50);
+
+        let size = GridSize::new(4.0, 1.0) * grid_scale;
+        let mut surface = surfaces::raster_n32_premul((size.width as i32, size.height as i32))
+            .expect("Could not create the surface");
+        surface.canvas().clear(colors::RED);
+        let mut layer = FloatingLayer {
+            windows: vec![&mut window],
+        };
+        layer.draw(surface.canvas(), &settings, Color::BLACK, &grid_renderer);
+
+        let mut pixel = [0u8; 4];
+        let info = ImageInfo::new((1, 1), ColorType::RGBA8888, AlphaType::Unpremul, None);
+        let center = ((size.width / 2.0) as i32, (size.height / 2.0) as i32);
+        assert!(surface.read_pixels(&info, &mut pixel, 4, center));
+        let [red, green, blue, _] = pixel;
+        assert!(red.abs_diff(128) <= 2, "red is {red}");
+        assert_eq!(green, 0);
+        assert!(blue.abs_diff(128) <= 2, "blue is {blue}");
+    }
+}
@@ -1,16 +1,22 @@
 use std::{cell::RefCell, rc::Rc, sync::Arc};
 
 use skia_safe::{
-    canvas::SaveLayerRec, BlendMode, Canvas, Color, Matrix, Paint, Picture, PictureRecorder, Rect,
+    canvas::SaveLayerRec, BlendMode, Canvas, Color, Color4f, Matrix, Paint, Picture,
+    PictureRecorder, Rect, TextBlob,
 };
 
 use crate::{
     cmd_line::CmdLineSettings,
     editor::{AnchorInfo, SortOrder, Style, WindowType},
     profiling::{tracy_plot, tracy_zone},
-    renderer::{animation_utils::*, GridRenderer, RendererSettings},
+    renderer::{
+        animation_utils::*,
+        inline_image::{ImagePlacement, InlineImages},
+        scrollbar::{draw_scrollbar, ScrollbarDetails, ScrollbarPosition},
+        GridRenderer, RendererSettings,
+    },
     settings::SETTINGS,
-    units::{to_skia_rect, GridPos, GridRect, GridScale, GridSize, PixelRect, PixelVec},
+    units::{to_skia_rect, GridPos, GridRect, GridScale, GridSize, PixelPos, PixelRect, PixelVec},
     utils::RingBuffer,
 };
 
@@ -55,6 +61,19 @@ pub enum WindowDrawCommand {
     Viewport {
         scroll_delta: f64,
     },
+    HorizontalScroll {
+        columns: i64,
+        text_offset: u64,
+    },
+    SetImage {
+        placement: ImagePlacement,
+        data: Vec<u8>,
+    },
+    RemoveImage {
+        id: u64,
+    },
+    /// None when the number of lines in the buffer is unknown
+    Scrollbar(Option<ScrollbarPosition>),
     ViewportMargins {
         top: u64,
         bottom: u64,
@@ -62,14 +81,20 @@ pub enum WindowDrawCommand {
         right: u64,
     },
     SortOrder(SortOrder),
+    /// The background of the `Normal` highlight the window uses, None for the default one
+    Background(Option<Color4f>),
 }
 
 #[derive(Clone)]
 struct Line {
     line_fragments: Vec<LineFragment>,
+    /// The text blobs each fragment was shaped to, once it has been drawn
+    fragment_blobs: Vec<Option<Vec<TextBlob>>>,
     background_picture: Option<Picture>,
     foreground_picture: Option<Picture>,
     has_transparency: bool,
+    /// The lowest blend of the cells, from `winblend` or `pumblend`
+    blend: u8,
     is_valid: bool,
 }
 
@@ -92,12 +117,20 @@ pub struct RenderedWindow {
     position_t: f32,
 
     pub scroll_animation: CriticallyDampedSpringAnimation,
+    pub horizontal_scroll_animation: CriticallyDampedSpringAnimation,
+    // The line numbers and sign column on the left don't scroll horizontally
+    text_offset: u64,
+
+    images: InlineImages,
+    scrollbar: Option<ScrollbarPosition>,
+    background: Option<Color4f>,
 }
 
 #[derive(Clone, Debug)]
 pub struct WindowDrawDetails {
     pub id: u64,
     pub region: PixelRect<f32>,
+    pub scrollbar: Option<ScrollbarDetails>,
 }
 
 impl WindowDrawDetails {
@@ -131,6 +164,21 @@ impl RenderedWindow {
             position_t: 2.0, // 2.0 is out of the 0.0 to 1.0 range and stops animation.
 
             scroll_animation: CriticallyDampedSpringAnimation::new(),
+            horizontal_scroll_animation: CriticallyDampedSpringAnimation::new(),
+            text_offset: 0,
+
+            images: InlineImages::new(),
+            scrollbar: None,
+            background: None,
+        }
+    }
+
+    /// The color the grid is cleared to, which is the background of its own `Normal` highlight
+    /// when it has one.
+    pub fn background(&self, grid_renderer: &GridRenderer) -> Color {
+        match self.background {
+            Some(background) => grid_renderer.display_color(background),
+            None => grid_renderer.get_default_background(),
         }
     }
 
@@ -141,6 +189,25 @@ impl RenderedWindow {
         ) * grid_scale
     }
 
+    /// The pixels covered by `width` cells at `position`, computed in the same order as the line
+    /// backgrounds are positioned when the window isn't scrolling. Anything drawn over the cells
+    /// with this region lines up with their backgrounds, without a seam from rounding.
+    pub fn cell_region(
+        &self,
+        grid_scale: GridScale,
+        position: GridPos<u64>,
+        width: u64,
+    ) -> PixelRect<f32> {
+        let pixel_region = self.pixel_region(grid_scale);
+        let left = position.x as f32 * grid_scale.width();
+        let right = left + width as f32 * grid_scale.width();
+        let top = pixel_region.min.y + position.y as f32 * grid_scale.height();
+        PixelRect::new(
+            PixelPos::new(left + pixel_region.min.x, top),
+            PixelPos::new(right + pixel_region.min.x, top + grid_scale.height()),
+        )
+    }
+
     fn get_target_position(&self, grid_rect: &GridRect<f32>) -> GridPos<f32> {
         let destination = self.grid_destination + grid_rect.min.to_vector();
 
@@ -207,6 +274,10 @@ impl RenderedWindow {
             tracy_plot!("Scroll position {}", self.scroll_animation.position.into());
         }
 
+        animating |= self
+            .horizontal_scroll_animation
+            .update(dt, settings.horizontal_scroll_animation_length);
+
         animating
     }
 
@@ -232,7 +303,13 @@ impl RenderedWindow {
         for (matrix, line) in self.iter_scrollable_lines_with_transform(pixel_region, grid_scale) {
             let line = line.borrow();
             if let Some(background_picture) = &line.background_picture {
-                canvas.draw_picture(background_picture, Some(&matrix), None);
+                self.draw_scrollable_picture(
+                    canvas,
+                    background_picture,
+                    &matrix,
+                    inner_region,
+                    grid_scale,
+                );
                 pics += 1;
             }
         }
@@ -258,17 +335,76 @@ impl RenderedWindow {
                 canvas.draw_picture(foreground_picture, Some(&matrix), None);
             }
         }
+        let inner_region = self.inner_region(pixel_region, grid_scale);
         canvas.save();
-        canvas.clip_rect(self.inner_region(pixel_region, grid_scale), None, false);
+        canvas.clip_rect(inner_region, None, false);
         for (matrix, line) in self.iter_scrollable_lines_with_transform(pixel_region, grid_scale) {
             let line = line.borrow();
             if let Some(foreground_picture) = &line.foreground_picture {
-                canvas.draw_picture(foreground_picture, Some(&matrix), None);
+                self.draw_scrollable_picture(
+                    canvas,
+                    foreground_picture,
+                    &matrix,
+                    inner_region,
+                    grid_scale,
+                );
             }
         }
         canvas.restore();
     }
 
+    fn horizontal_scroll_offset_pixels(&self, grid_scale: GridScale) -> f32 {
+        (-self.horizontal_scroll_animation.position * grid_scale.width()).round()
+    }
+
+    /// Draws a scrollable line, with the text part shifted by the horizontal scroll animation, and
+    /// the numbers and signs to the left of it kept in place.
+    fn draw_scrollable_picture(
+        &self,
+        canvas: &Canvas,
+        picture: &Picture,
+        matrix: &Matrix,
+        inner_region: Rect,
+        grid_scale: GridScale,
+    ) {
+        let offset = self.horizontal_scroll_offset_pixels(grid_scale);
+        if offset == 0.0 {
+            canvas.draw_picture(picture, Some(matrix), None);
+            return;
+        }
+
+        let text_left = inner_region.left + self.text_offset as f32 * grid_scale.width();
+        canvas.save();
+        canvas.clip_rect(
+            Rect::new(
+                inner_region.left,
+                inner_region.top,
+                text_left,
+                inner_region.bottom,
+            ),
+            None,
+            false,
+        );
+        canvas.draw_picture(picture, Some(matrix), None);
+        canvas.restore();
+
+        canvas.save();
+        canvas.clip_rect(
+            Rect::new(
+                text_left,
+                inner_region.top,
+                inner_region.right,
+                inner_region.bottom,
+            ),
+            None,
+            false,
+        );
+        let mut shifted = *matrix;
+        shifted.post_translate((offset, 0.0));
+        canvas.draw_picture(picture, Some(&shifted), None);
+        canvas.restore();
+    }
+
     pub fn has_transparency(&self) -> bool {
         let scroll_offset_lines = self.scroll_animation.position.floor() as isize;
         if self.scrollback_lines.is_empty() {
@@ -282,11 +418,25 @@ impl RenderedWindow {
             .any(|line| line.borrow().has_transparency)
     }
 
+    /// How much the whole window blends with what's beneath it, from 0 for opaque to 100. Neovim
+    /// sets the `winblend` of a floating window on all of its cells, so any opaque cell makes the
+    /// window opaque.
+    pub fn blend(&self) -> u8 {
+        self.actual_lines
+            .iter()
+            .flatten()
+            .map(|line| line.borrow().blend)
+            .min()
+            .unwrap_or(0)
+    }
+
     pub fn draw(
         &mut self,
         root_canvas: &Canvas,
+        settings: &RendererSettings,
         default_background: Color,
         grid_scale: GridScale,
+        has_background_image: bool,
     ) -> WindowDrawDetails {
         let pixel_region_box = self.pixel_region(grid_scale);
         let pixel_region = to_skia_rect(&pixel_region_box);
@@ -297,7 +447,9 @@ impl RenderedWindow {
         let paint = Paint::default()
             .set_anti_alias(false)
             .set_color(Color::from_argb(255, 255, 255, default_background.a()))
-            .set_blend_mode(if self.anchor_info.is_some() {
+            // Blend the root windows over the background image, so that it shows through the
+            // transparent background
+            .set_blend_mode(if self.anchor_info.is_some() || has_background_image {
                 BlendMode::SrcOver
             } else {
                 BlendMode::Src
@@ -319,6 +471,21 @@ impl RenderedWindow {
         self.draw_background_surface(root_canvas, pixel_region_box, grid_scale);
         root_canvas.restore();
         self.draw_foreground_surface(root_canvas, pixel_region_box, grid_scale);
+        self.images.draw(
+            root_canvas,
+            (pixel_region_box.min.x, pixel_region_box.min.y),
+            (
+                self.horizontal_scroll_animation.position,
+                self.scroll_animation.position,
+            ),
+            grid_scale,
+            self.inner_region(pixel_region_box, grid_scale),
+        );
+        let scrollbar = self
+            .scrollbar
+            .as_ref()
+            .filter(|_| settings.scrollbar)
+            .and_then(|position| draw_scrollbar(root_canvas, settings, pixel_region_box, position));
         root_canvas.restore();
 
         root_canvas.restore();
@@ -326,6 +493,7 @@ impl RenderedWindow {
         WindowDrawDetails {
             id: self.id,
             region: pixel_region_box,
+            scrollbar,
         }
     }
 
@@ -388,11 +556,24 @@ impl RenderedWindow {
             } => {
                 tracy_zone!("draw_line_cmd", 0);
 
+                let blend = line_fragments
+                    .iter()
+                    .map(|fragment| fragment.style.as_ref().map_or(0, |style| style.blend))
+                    .min()
+                    .unwrap_or(0);
+                let fragment_blobs = match &self.actual_lines[row] {
+                    Some(old_line) => {
+                        reused_fragment_blobs(&mut old_line.borrow_mut(), &line_fragments)
+                    }
+                    None => vec![None; line_fragments.len()],
+                };
                 let line = Line {
                     line_fragments,
+                    fragment_blobs,
                     background_picture: None,
                     foreground_picture: None,
                     has_transparency: false,
+                    blend,
                     is_valid: false,
                 };
 
@@ -414,15 +595,20 @@ impl RenderedWindow {
                     && cols == 0
                 {
                     self.actual_lines.rotate(rows as isize);
+                    self.images.scroll(rows, self.grid_size.height);
                 }
             }
             WindowDrawCommand::Clear => {
                 tracy_zone!("clear_cmd", 0);
                 self.scroll_delta = 0;
+                // Neovim only sends the lines that aren't empty after a clear, so the old lines
+                // have to be dropped here, otherwise they are shown again on the next flush
+                self.actual_lines.iter_mut().for_each(|line| *line = None);
                 self.scrollback_lines
                     .iter_mut()
                     .for_each(|line| *line = None);
                 self.scroll_animation.reset();
+                self.horizontal_scroll_animation.reset();
             }
             WindowDrawCommand::Show => {
                 tracy_zone!("show_cmd", 0);
@@ -432,6 +618,7 @@ impl RenderedWindow {
                                            // so we set t to 2.0 to stop animations.
                     self.grid_start_position = self.grid_destination;
                     self.scroll_animation.reset();
+                    self.horizontal_scroll_animation.reset();
                 }
             }
             WindowDrawCommand::Hide => {
@@ -442,6 +629,25 @@ impl RenderedWindow {
                 log::trace!("Handling Viewport {}", self.id);
                 self.scroll_delta = scroll_delta.round() as isize;
             }
+            WindowDrawCommand::HorizontalScroll {
+                columns,
+                text_offset,
+            } => {
+                let max_columns = self.grid_size.width.saturating_sub(text_offset as u32) as f32;
+                let position = self.horizontal_scroll_animation.position - columns as f32;
+                self.horizontal_scroll_animation.position =
+                    position.clamp(-max_columns, max_columns);
+                self.text_offset = text_offset;
+            }
+            WindowDrawCommand::SetImage { placement, data } => {
+                self.images.set(placement, data);
+            }
+            WindowDrawCommand::RemoveImage { id } => {
+                self.images.remove(id);
+            }
+            WindowDrawCommand::Scrollbar(position) => {
+                self.scrollbar = position;
+            }
             WindowDrawCommand::ViewportMargins { top, bottom, .. } => {
                 self.viewport_margins = ViewportMargins { top, bottom }
             }
@@ -450,11 +656,14 @@ impl RenderedWindow {
                     anchor_info.sort_order = sort_order;
                 }
             }
+            WindowDrawCommand::Background(background) => self.background = background,
             _ => {}
         };
     }
 
     pub fn flush(&mut self, renderer_settings: &RendererSettings) {
+        self.images.flush(renderer_settings.inline_images);
+
         // If the borders are changed, reset the scrollback to only fit the inner view
         let inner_range = self.viewport_margins.top as isize
             ..(self.actual_lines.len() - self.viewport_margins.bottom as usize) as isize;
@@ -603,6 +812,11 @@ impl RenderedWindow {
             if line.is_valid && !force {
                 return;
             }
+            let line = &mut *line;
+            // The blobs were shaped with the previous font
+            if force {
+                line.fragment_blobs.fill(None);
+            }
 
             let mut recorder = PictureRecorder::new();
 
@@ -635,7 +849,11 @@ impl RenderedWindow {
 
             let canvas = recorder.begin_recording(grid_rect, None);
             let mut foreground_drawn = false;
-            for line_fragment in &line.line_fragments {
+            let fragments = line
+                .line_fragments
+                .iter()
+                .zip(line.fragment_blobs.iter_mut());
+            for (line_fragment, blobs) in fragments {
                 let LineFragment {
                     text,
                     window_left,
@@ -644,12 +862,13 @@ impl RenderedWindow {
                 } = line_fragment;
                 let grid_position = (i32::try_from(*window_left).unwrap(), 0).into();
 
-                foreground_drawn |= grid_renderer.draw_foreground(
+                foreground_drawn |= grid_renderer.draw_foreground_with_blobs(
                     canvas,
                     text,
                     grid_position,
                     i32::try_from(*width).unwrap(),
                     style,
+                    blobs,
                 );
             }
             let foreground_picture =
@@ -690,3 +909,175 @@ impl RenderedWindow {
         }
     }
 }
+
+/// Diffs the fragments of a redrawn line against the ones it had before. The runs that didn't
+/// change move their blobs over to the new line, so that only the changed runs get shaped again.
+/// The old line is only kept around for the scroll animation, which draws its recorded pictures,
+/// so it gives up all of its blobs. That way there's at most one line with blobs for each row of
+/// the grid, and they don't outgrow what's on the screen.
+fn reused_fragment_blobs(
+    old_line: &mut Line,
+    new_fragments: &[LineFragment],
+) -> Vec<Option<Vec<TextBlob>>> {
+    // Both lines are ordered by column, so they are walked together
+    let mut old_fragments = old_line
+        .line_fragments
+        .iter()
+        .zip(old_line.fragment_blobs.iter_mut())
+        .peekable();
+    let blobs = new_fragments
+        .iter()
+        .map(|fragment| {
+            while old_fragments
+                .next_if(|(old_fragment, _)| old_fragment.window_left < fragment.window_left)
+                .is_some()
+            {}
+            old_fragments
+                .next_if(|(old_fragment, _)| *old_fragment == fragment)
+                .and_then(|(_, blobs)| blobs.take())
+        })
+        .collect();
+    old_line.fragment_blobs.fill(None);
+    blobs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use skia_safe::{colors, surfaces, AlphaType, ColorType, ImageInfo};
+
+    fn line_count(lines: &RingBuffer<Option<Rc<RefCell<Line>>>>) -> usize {
+        lines.iter().filter(|line| line.is_some()).count()
+    }
+
+    #[test]
+    fn clear_drops_all_lines() {
+        let mut window = RenderedWindow::new(2, GridPos::new(0, 0), GridSize::new(10, 3));
+        for row in 0..3 {
+            window.handle_window_draw_command(WindowDrawCommand::DrawLine {
+                row,
+                line_fragments: vec![LineFragment {
+                    text: "abc".to_string(),
+                    window_left: 0,
+                    width: 3,
+                    style: None,
+                }],
+            });
+        }
+        window.flush(&RendererSettings::default());
+        assert_eq!(line_count(&window.actual_lines), 3);
+
+        window.handle_window_draw_command(WindowDrawCommand::Clear);
+        window.flush(&RendererSettings::default());
+        assert_eq!(line_count(&window.actual_lines), 0);
+        assert_eq!(line_count(&window.scrollback_lines), 0);
+    }
+
+    #[test]
+    fn grids_clear_to_their_own_background() {
+        SETTINGS.set(&RendererSettings::default());
+        let settings = SETTINGS.get::<RendererSettings>();
+        let grid_renderer = GridRenderer::new(1.0);
+        let grid_scale = grid_renderer.grid_scale;
+
+        // Two splits side by side, the right one with `winhighlight=Normal:Sidebar`
+        let mut left = RenderedWindow::new(2, GridPos::new(0, 0), GridSize::new(4, 1));
+        let mut right = RenderedWindow::new(3, GridPos::new(4, 0), GridSize::new(4, 1));
+        right.handle_window_draw_command(WindowDrawCommand::Background(Some(colors::BLUE)));
+
+        let size = GridSize::new(8.0, 1.0) * grid_scale;
+        let mut surface = surfaces::raster_n32_premul((size.width as i32, size.height as i32))
+            .expect("Could not create the surface");
+        for window in [&mut left, &mut right] {
+            let background = window.background(&grid_renderer);
+            window.draw(surface.canvas(), &settings, background, grid_scale, false);
+        }
+
+        let pixel_at = |surface: &mut skia_safe::Surface, x: f32| {
+            let mut pixel = [0u8; 4];
+            let info = ImageInfo::new((1, 1), ColorType::RGBA8888, AlphaType::Unpremul, None);
+            let position = (x as i32, (size.height / 2.0) as i32);
+            assert!(surface.read_pixels(&info, &mut pixel, 4, position));
+            Color::from_argb(pixel[3], pixel[0], pixel[1], pixel[2])
+        };
+        assert_eq!(
+            pixel_at(&mut surface, size.width / 4.0),
+            grid_renderer.get_default_background()
+        );
+        assert_eq!(pixel_at(&mut surface, size.width * 3.0 / 4.0), Color::BLUE);
+    }
+
+    #[test]
+    fn unchanged_runs_reuse_their_blobs() {
+        let mut shaper = crate::renderer::CachingShaper::new(1.0);
+        let mut shape = |text: &str| {
+            Some(
+                shaper
+                    .shape_cached(text.to_string(), Default::default())
+                    .clone(),
+            )
+        };
+        let fragment = |text: &str, window_left| LineFragment {
+            text: text.to_string(),
+            window_left,
+            width: text.len() as u64,
+            style: None,
+        };
+        let mut old_line = Line {
+            line_fragments: vec![fragment("fn ", 0), fragment("main", 3), fragment("()", 7)],
+            fragment_blobs: vec![shape("fn"), shape("main"), shape("()")],
+            background_picture: None,
+            foreground_picture: None,
+            has_transparency: false,
+            blend: 0,
+            is_valid: true,
+        };
+
+        // Only the middle run was edited
+        let unique_ids = |blobs: &Option<Vec<TextBlob>>| {
+            blobs
+                .iter()
+                .flatten()
+                .map(TextBlob::unique_id)
+                .collect::<Vec<_>>()
+        };
+        let old_ids = old_line
+            .fragment_blobs
+            .iter()
+            .map(unique_ids)
+            .collect::<Vec<_>>();
+        let mut shifted_line = old_line.clone();
+
+        let new_fragments = [fragment("fn ", 0), fragment("mian", 3), fragment("()", 7)];
+        let blobs = reused_fragment_blobs(&mut old_line, &new_fragments);
+        let hits = blobs.iter().filter(|blobs| blobs.is_some()).count();
+        assert_eq!(hits, 2);
+        assert!(blobs[1].is_none());
+        assert_eq!(unique_ids(&blobs[0]), old_ids[0]);
+        assert_eq!(unique_ids(&blobs[2]), old_ids[2]);
+        // The old line doesn't keep any, but can still be drawn again
+        assert_eq!(old_line.fragment_blobs.len(), 3);
+        assert!(old_line.fragment_blobs.iter().all(Option::is_none));
+
+        // A run that moved to another column is drawn again
+        let shifted = [fragment("fn ", 1)];
+        assert!(reused_fragment_blobs(&mut shifted_line, &shifted)[0].is_none());
+    }
+
+    #[test]
+    fn horizontal_scroll_is_limited_to_the_text_width() {
+        let mut window = RenderedWindow::new(2, GridPos::new(0, 0), GridSize::new(10, 3));
+        window.handle_window_draw_command(WindowDrawCommand::HorizontalScroll {
+            columns: 3,
+            text_offset: 4,
+        });
+        assert_eq!(window.horizontal_scroll_animation.position, -3.0);
+        assert_eq!(window.text_offset, 4);
+
+        window.handle_window_draw_command(WindowDrawCommand::HorizontalScroll {
+            columns: 100,
+            text_offset: 4,
+        });
+        assert_eq!(window.horizontal_scroll_animation.position, -6.0);
+    }
+}
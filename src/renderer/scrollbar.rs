@@ -0,0 +1,140 @@
+use skia_safe::{Canvas, Color, Paint};
+
+use crate::{
+    renderer::{parse_color_setting, RendererSettings},
+    units::{to_skia_rect, PixelPos, PixelRect, PixelSize},
+};
+
+// The thumb doesn't get shorter than this many widths, so it's still easy to grab in long buffers
+const MIN_THUMB_LENGTH: f32 = 3.0;
+
+/// The part of the buffer shown in a window, from `win_viewport`. The lines are zero based, and
+/// the bottom line is exclusive.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScrollbarPosition {
+    /// The Neovim window handle, which is scrolled when the scrollbar is dragged
+    pub window: u64,
+    pub top_line: f64,
+    pub bottom_line: f64,
+    pub line_count: f64,
+}
+
+/// Where the scrollbar of a window was drawn, so that the mouse can drag it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScrollbarDetails {
+    pub track: PixelRect<f32>,
+    pub position: ScrollbarPosition,
+}
+
+/// The track runs along the right edge of the window region.
+pub fn scrollbar_track(region: PixelRect<f32>, width: f32) -> PixelRect<f32> {
+    let width = width.min(region.width());
+    PixelRect::from_origin_and_size(
+        PixelPos::new(region.max.x - width, region.min.y),
+        PixelSize::new(width, region.height()),
+    )
+}
+
+impl ScrollbarPosition {
+    /// Returns the part of the track showing the visible lines, or None when the whole buffer
+    /// fits in the window.
+    pub fn thumb(&self, track: PixelRect<f32>) -> Option<PixelRect<f32>> {
+        let visible_lines = self.bottom_line - self.top_line;
+        if self.line_count <= 0.0 || visible_lines >= self.line_count {
+            return None;
+        }
+
+        let track_height = track.height();
+        let length = (track_height * (visible_lines / self.line_count) as f32)
+            .max(track.width() * MIN_THUMB_LENGTH)
+            .min(track_height);
+        // The thumb is placed relative to the lines that can be scrolled to, so that it still
+        // reaches the end of the track when it's enlarged
+        let scrollable_lines = (self.line_count - visible_lines).max(1.0);
+        let progress = (self.top_line / scrollable_lines).clamp(0.0, 1.0) as f32;
+        let top = track.min.y + (track_height - length) * progress;
+
+        Some(PixelRect::from_origin_and_size(
+            PixelPos::new(track.min.x, top),
+            PixelSize::new(track.width(), length),
+        ))
+    }
+
+    /// The one based top line to scroll to when the thumb is dragged to `y`, which puts the
+    /// middle of the thumb under the pointer.
+    pub fn top_line_at(&self, track: PixelRect<f32>, y: f32) -> u64 {
+        let visible_lines = self.bottom_line - self.top_line;
+        let progress = ((y - track.min.y) / track.height()).clamp(0.0, 1.0) as f64;
+        let top_line = progress * self.line_count - visible_lines / 2.0;
+        let max_top_line = (self.line_count - visible_lines).max(0.0);
+
+        top_line.clamp(0.0, max_top_line).round() as u64 + 1
+    }
+}
+
+pub fn draw_scrollbar(
+    canvas: &Canvas,
+    settings: &RendererSettings,
+    region: PixelRect<f32>,
+    position: &ScrollbarPosition,
+) -> Option<ScrollbarDetails> {
+    let track = scrollbar_track(region, settings.scrollbar_width);
+    let thumb = position.thumb(track)?;
+
+    let color = parse_color_setting(&settings.scrollbar_color)
+        .unwrap_or_else(|| Color::from_argb(128, 128, 128, 128));
+    let mut paint = Paint::default();
+    paint.set_anti_alias(true);
+    paint.set_color(color);
+    let radius = thumb.width() / 2.0;
+    canvas.draw_round_rect(to_skia_rect(&thumb), radius, radius, &paint);
+
+    Some(ScrollbarDetails {
+        track,
+        position: position.clone(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn position(top_line: f64, line_count: f64) -> ScrollbarPosition {
+        ScrollbarPosition {
+            window: 1000,
+            top_line,
+            bottom_line: top_line + 20.0,
+            line_count,
+        }
+    }
+
+    #[test]
+    fn thumb_shows_the_visible_part_of_the_buffer() {
+        let region = PixelRect::from_size(PixelSize::new(800.0, 400.0));
+        let track = scrollbar_track(region, 4.0);
+        assert_eq!(track.min, PixelPos::new(796.0, 0.0));
+
+        // The top of a buffer with 200 lines
+        let thumb = position(0.0, 200.0).thumb(track).unwrap();
+        assert_eq!(thumb.min.y, 0.0);
+        assert_eq!(thumb.height(), 40.0);
+
+        // And the bottom of it
+        let thumb = position(180.0, 200.0).thumb(track).unwrap();
+        assert_eq!(thumb.max.y, 400.0);
+
+        // Buffers that fit in the window don't have a scrollbar
+        assert_eq!(position(0.0, 10.0).thumb(track), None);
+    }
+
+    #[test]
+    fn dragging_the_thumb_scrolls_to_the_line_under_the_pointer() {
+        let region = PixelRect::from_size(PixelSize::new(800.0, 400.0));
+        let track = scrollbar_track(region, 4.0);
+        let position = position(0.0, 200.0);
+
+        assert_eq!(position.top_line_at(track, 0.0), 1);
+        assert_eq!(position.top_line_at(track, 200.0), 91);
+        assert_eq!(position.top_line_at(track, 1000.0), 181);
+    }
+}
@@ -0,0 +1,136 @@
+use std::{error::Error, num::NonZeroU32, rc::Rc};
+
+use skia_safe::{
+    surfaces, AlphaType, Canvas, ColorSpace, ColorType, ImageInfo, PixelGeometry, SurfaceProps,
+    SurfacePropsFlags,
+};
+use winit::{event_loop::EventLoopProxy, window::Window};
+
+use super::{RendererSettings, SkiaRenderer, VSync};
+
+use crate::{profiling::tracy_gpu_zone, settings::SETTINGS, window::UserEvent};
+
+#[cfg(feature = "gpu_profiling")]
+use crate::profiling::GpuCtx;
+
+/// Renders on the CPU into a Skia raster surface, which is copied to the window on every frame.
+/// It's the last resort when there's no working graphics driver, or when it's requested with
+/// `--software-render`.
+pub struct SoftwareSkiaRenderer {
+    skia_surface: skia_safe::Surface,
+    window_surface: softbuffer::Surface<Rc<Window>, Rc<Window>>,
+    window: Rc<Window>,
+}
+
+impl SoftwareSkiaRenderer {
+    pub fn new(window: Window) -> Result<Self, Box<dyn Error>> {
+        let window = Rc::new(window);
+        let context = softbuffer::Context::new(window.clone())?;
+        let window_surface = softbuffer::Surface::new(&context, window.clone())?;
+        let mut renderer = Self {
+            skia_surface: create_surface(1, 1)?,
+            window_surface,
+            window,
+        };
+        renderer.try_resize()?;
+        Ok(renderer)
+    }
+
+    fn try_resize(&mut self) -> Result<(), Box<dyn Error>> {
+        let size = self.window.inner_size();
+        let width = NonZeroU32::new(size.width).unwrap_or(NonZeroU32::MIN);
+        let height = NonZeroU32::new(size.height).unwrap_or(NonZeroU32::MIN);
+        self.window_surface.resize(width, height)?;
+        self.skia_surface = create_surface(width.get(), height.get())?;
+        Ok(())
+    }
+}
+
+impl SkiaRenderer for SoftwareSkiaRenderer {
+    fn name(&self) -> &'static str {
+        "software"
+    }
+
+    fn window(&self) -> &Window {
+        &self.window
+    }
+
+    fn flush(&mut self) {}
+
+    fn swap_buffers(&mut self) {
+        tracy_gpu_zone!("swap buffers");
+        let Some(pixels) = self.skia_surface.peek_pixels() else {
+            return;
+        };
+        let Some(bytes) = pixels.bytes() else {
+            return;
+        };
+        let mut buffer = match self.window_surface.buffer_mut() {
+            Ok(buffer) => buffer,
+            Err(error) => {
+                log::error!("Could not get the window buffer: {error}");
+                return;
+            }
+        };
+        // The window takes the pixels as 0RGB, which leaves the alpha channel out
+        for (pixel, bgra) in buffer.iter_mut().zip(bytes.chunks_exact(4)) {
+            *pixel = u32::from_le_bytes([bgra[0], bgra[1], bgra[2], 0]);
+        }
+        self.window.pre_present_notify();
+        if let Err(error) = buffer.present() {
+            log::error!("Could not present the frame: {error}");
+        }
+    }
+
+    fn canvas(&mut self) -> &Canvas {
+        self.skia_surface.canvas()
+    }
+
+    fn resize(&mut self) {
+        if let Err(error) = self.try_resize() {
+            log::error!("Could not resize the software surface: {error}");
+        }
+    }
+
+    fn create_vsync(&self, _proxy: EventLoopProxy<UserEvent>) -> VSync {
+        VSync::WinitThrottling()
+    }
+
+    #[cfg(feature = "gpu_profiling")]
+    fn tracy_create_gpu_context(&self, _name: &str) -> Box<dyn GpuCtx> {
+        Box::new(NoGpuCtx)
+    }
+}
+
+fn create_surface(width: u32, height: u32) -> Result<skia_safe::Surface, Box<dyn Error>> {
+    let render_settings = SETTINGS.get::<RendererSettings>();
+    let surface_props = SurfaceProps::new_with_text_properties(
+        SurfacePropsFlags::default(),
+        PixelGeometry::default(),
+        render_settings.text_contrast,
+        render_settings.text_gamma,
+    );
+    let image_info = ImageInfo::new(
+        (width as i32, height as i32),
+        ColorType::BGRA8888,
+        AlphaType::Premul,
+        ColorSpace::new_srgb(),
+    );
+    surfaces::raster(&image_info, None, Some(&surface_props))
+        .ok_or_else(|| "Could not create the software surface".into())
+}
+
+/// There's nothing to measure on the GPU when rendering on the CPU
+#[cfg(feature = "gpu_profiling")]
+struct NoGpuCtx;
+
+#[cfg(feature = "gpu_profiling")]
+impl GpuCtx for NoGpuCtx {
+    fn gpu_collect(&mut self) {}
+
+    fn gpu_begin(&mut self, _loc_data: &tracy_client_sys::___tracy_source_location_data) -> i64 {
+        0
+    }
+
+    fn gpu_end(&mut self, _query_id: i64) {}
+}
@@ -0,0 +1,66 @@
+use skia_safe::{gradient_shader, Canvas, Color, Paint, Point, Rect, TileMode};
+
+// The cutout fades into the dimmed area over the outer part of the radius
+const FADE_START: f32 = 0.6;
+
+/// How dark the overlay is at `distance` pixels from the cursor, from 0 to `strength`.
+fn dim_at(distance: f32, radius: f32, strength: f32) -> f32 {
+    let strength = strength.clamp(0.0, 1.0);
+    if radius <= 0.0 {
+        return strength;
+    }
+    let fade = ((distance / radius - FADE_START) / (1.0 - FADE_START)).clamp(0.0, 1.0);
+    strength * fade
+}
+
+/// Dims everything except a circle around the cursor, to help focusing on the text being
+/// edited. It's drawn every frame at the animated cursor position, so it follows the cursor.
+pub fn draw_spotlight(
+    canvas: &Canvas,
+    frame: Rect,
+    center: Point,
+    radius: f32,
+    strength: f32,
+    color: Color,
+) {
+    let dim = dim_at(f32::INFINITY, radius, strength);
+    if dim <= 0.0 {
+        return;
+    }
+
+    let dimmed = color.with_a((dim * 255.0) as u8);
+    let mut paint = Paint::default();
+    if radius > 0.0 {
+        let transparent = color.with_a(0);
+        paint.set_shader(gradient_shader::radial(
+            center,
+            radius,
+            [transparent, transparent, dimmed].as_slice(),
+            [0.0, FADE_START, 1.0].as_slice(),
+            TileMode::Clamp,
+            None,
+            None,
+        ));
+    } else {
+        paint.set_color(dimmed);
+    }
+
+    canvas.draw_rect(frame, &paint);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_area_around_the_cursor_is_left_undimmed() {
+        assert_eq!(dim_at(0.0, 100.0, 0.5), 0.0);
+        assert_eq!(dim_at(60.0, 100.0, 0.5), 0.0);
+        assert_eq!(dim_at(80.0, 100.0, 0.5), 0.25);
+        assert_eq!(dim_at(500.0, 100.0, 0.5), 0.5);
+
+        // Without a radius everything is dimmed, and the strength can't go past fully opaque
+        assert_eq!(dim_at(0.0, 0.0, 0.5), 0.5);
+        assert_eq!(dim_at(500.0, 100.0, 3.0), 1.0);
+    }
+}
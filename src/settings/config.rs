@@ -9,7 +9,7 @@ use notify_debouncer_full::{
 use serde::Deserialize;
 use winit::event_loop::EventLoopProxy;
 
-use crate::{error_msg, frame::Frame, window::UserEvent};
+use crate::{error_msg, frame::Frame, renderer::GraphicsBackend, window::UserEvent};
 
 use std::path::{Path, PathBuf};
 
@@ -39,13 +39,17 @@ pub fn config_path() -> PathBuf {
 #[derive(Debug, Deserialize, Default, Clone)]
 #[serde(rename_all = "kebab-case")]
 pub struct Config {
+    pub backend: Option<GraphicsBackend>,
     pub font: Option<FontSettings>,
     pub fork: Option<bool>,
     pub frame: Option<Frame>,
     pub idle: Option<bool>,
     pub maximized: Option<bool>,
+    pub monitor: Option<usize>,
     pub neovim_bin: Option<PathBuf>,
     pub no_multigrid: Option<bool>,
+    pub single_instance: Option<bool>,
+    pub software_render: Option<bool>,
     pub srgb: Option<bool>,
     pub tabs: Option<bool>,
     pub theme: Option<String>,
@@ -85,6 +89,9 @@ impl Config {
         if let Some(maximized) = self.maximized {
             env::set_var("NEOVIDE_MAXIMIZED", maximized.to_string());
         }
+        if let Some(monitor) = self.monitor {
+            env::set_var("NEOVIDE_MONITOR", monitor.to_string());
+        }
         if let Some(vsync) = self.vsync {
             env::set_var("NEOVIDE_VSYNC", vsync.to_string());
         }
@@ -100,6 +107,15 @@ impl Config {
         if let Some(frame) = self.frame {
             env::set_var("NEOVIDE_FRAME", frame.to_string());
         }
+        if let Some(backend) = self.backend {
+            env::set_var("NEOVIDE_BACKEND", backend.to_string());
+        }
+        if let Some(single_instance) = self.single_instance {
+            env::set_var("NEOVIDE_SINGLE_INSTANCE", single_instance.to_string());
+        }
+        if let Some(software_render) = self.software_render {
+            env::set_var("NEOVIDE_SOFTWARE_RENDER", software_render.to_string());
+        }
         if let Some(neovim_bin) = &self.neovim_bin {
             env::set_var("NEOVIM_BIN", neovim_bin.to_string_lossy().to_string());
         }
@@ -3,8 +3,8 @@ use std::collections::HashMap;
 use serde::Deserialize;
 
 use crate::renderer::fonts::font_options::{
-    points_to_pixels, FontDescription, FontEdging, FontFeature, FontHinting, FontOptions,
-    SecondaryFontDescription,
+    points_to_pixels, script_key, FontDescription, FontEdging, FontFeature, FontHinting,
+    FontOptions, SecondaryFontDescription,
 };
 
 #[derive(Debug, Clone, Deserialize, PartialEq)]
@@ -45,6 +45,7 @@ pub struct FontSettings {
     pub size: f32,
     pub width: Option<f32>,
     pub features: Option<HashMap<String /* family */, Vec<String> /* features */>>,
+    pub scripts: Option<HashMap<String /* script */, FontDescriptionSettings>>,
     pub allow_float_size: Option<bool>,
     pub hinting: Option<String>,
     pub edging: Option<String>,
@@ -119,6 +120,15 @@ impl From<FontSettings> for FontOptions {
                         .collect()
                 })
                 .unwrap_or_default(),
+            scripts: value
+                .scripts
+                .map(|scripts| {
+                    scripts
+                        .into_iter()
+                        .map(|(script, fonts)| (script_key(&script), fonts.into()))
+                        .collect()
+                })
+                .unwrap_or_default(),
             size: points_to_pixels(value.size),
             width: points_to_pixels(value.width.unwrap_or_default()),
             hinting: value
@@ -300,4 +310,27 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn test_script_fonts() {
+        let settings = r#"
+        {
+            "normal": "Consolas",
+            "scripts": { "Cyrillic": "PT Mono", "Old_Italic": ["Noto Sans Old Italic"] },
+            "size": 20
+        }
+        "#;
+
+        let settings: FontSettings = serde_json::from_str(settings).unwrap();
+        let options = FontOptions::from(settings);
+        let fonts = options.script_font_list('Ж', CoarseStyle::default());
+        assert_eq!(
+            fonts,
+            vec![FontDescription {
+                family: "PT Mono".into(),
+                style: None
+            }]
+        );
+        assert!(options.scripts.contains_key(&script_key("Old Italic")));
+    }
 }
@@ -19,7 +19,7 @@ use crate::{bridge::NeovimWriter, window::UserEvent};
 pub use from_value::ParseFromValue;
 pub use window_size::{
     clamped_grid_size, load_last_window_settings, save_window_size, PersistentWindowSettings,
-    DEFAULT_GRID_SIZE, MIN_GRID_SIZE,
+    DEFAULT_GRID_SIZE, MAX_DRAWN_GRID_SIZE, MIN_GRID_SIZE,
 };
 
 mod config;
@@ -102,6 +102,23 @@ impl Settings {
         (*value).clone()
     }
 
+    /// The current value of every setting with its name in Neovim, in alphabetical order.
+    pub fn current_values(&self) -> Vec<(String, Value)> {
+        let readers = self.readers.read();
+        let mut values: Vec<_> = readers
+            .iter()
+            .filter_map(|(location, reader)| {
+                let name = match location {
+                    SettingLocation::NeovideGlobal(name) => format!("g:neovide_{name}"),
+                    SettingLocation::NeovimOption(name) => format!("&{name}"),
+                };
+                Some((name, reader(self)?))
+            })
+            .collect();
+        values.sort_by(|(a, _), (b, _)| a.cmp(b));
+        values
+    }
+
     pub fn setting_locations(&self) -> Vec<SettingLocation> {
         self.updaters.read().keys().cloned().collect()
     }
@@ -265,6 +282,22 @@ mod tests {
         assert_eq!(&(noop_update as UpdateHandlerFunc), listener);
     }
 
+    #[test]
+    fn current_values_are_named_like_in_neovim() {
+        let settings = Settings::new();
+        settings.register::<TestSettings>();
+
+        let values = settings.current_values();
+        assert_eq!(
+            values
+                .iter()
+                .map(|(name, _)| name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["g:neovide_bar", "g:neovide_baz", "g:neovide_foo"]
+        );
+        assert_eq!(values[2].1, Value::from("foo"));
+    }
+
     #[test]
     fn test_set() {
         let settings = Settings::new();
@@ -21,6 +21,13 @@ pub const MAX_GRID_SIZE: GridSize<u32> = GridSize {
     width: 10000,
     height: 1000,
 };
+/// The largest grid Neovide allocates and draws, a fullscreen window on a high resolution display
+/// with a tiny font would otherwise need millions of cells, each of them in the line fragments
+/// that are shaped and drawn.
+pub const MAX_DRAWN_GRID_SIZE: GridSize<u32> = GridSize {
+    width: 1000,
+    height: 500,
+};
 
 #[derive(Serialize, Deserialize, Debug)]
 pub enum PersistentWindowSettings {
@@ -0,0 +1,337 @@
+//! In single instance mode the first Neovide listens on a local socket. Later launches send their
+//! files to it over that socket and exit, instead of starting another Neovim.
+
+use std::{env, io, time::Duration};
+
+use anyhow::Result;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWriteExt},
+    time::{sleep, timeout},
+};
+use winit::event_loop::EventLoopProxy;
+
+#[cfg(target_os = "windows")]
+use tokio::net::windows::named_pipe::{ClientOptions, NamedPipeServer, ServerOptions};
+#[cfg(not(target_os = "windows"))]
+use {
+    std::{
+        fs::{self, DirBuilder},
+        os::unix::fs::{DirBuilderExt, PermissionsExt},
+        path::{Path, PathBuf},
+    },
+    tokio::net::{UnixListener, UnixStream},
+};
+
+use crate::{
+    bridge::{send_ui, ParallelCommand},
+    cmd_line::CmdLineSettings,
+    window::{UserEvent, WindowCommand},
+};
+
+// Two launches at the same time can both find the socket taken, but not accepting connections
+// yet, so try a few times before giving up
+const CLAIM_ATTEMPTS: u32 = 5;
+const RETRY_DELAY: Duration = Duration::from_millis(100);
+const FORWARD_TIMEOUT: Duration = Duration::from_secs(2);
+// The running instance serves one launch at a time, so a client that never finishes its request
+// can't be allowed to block the others
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[cfg(target_os = "windows")]
+type SocketName = str;
+#[cfg(not(target_os = "windows"))]
+type SocketName = Path;
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+struct Request {
+    files: Vec<String>,
+}
+
+pub struct Listener {
+    name: Box<SocketName>,
+    #[cfg(target_os = "windows")]
+    server: NamedPipeServer,
+    #[cfg(not(target_os = "windows"))]
+    listener: UnixListener,
+}
+
+pub enum Claim {
+    /// This is the running instance, and should serve the later launches
+    Primary(Listener),
+    /// The files were sent to the running instance, so this one should exit
+    Forwarded,
+    /// Single instance mode is off, or couldn't be set up
+    Standalone,
+}
+
+fn user_name() -> String {
+    env::var("USER")
+        .or_else(|_| env::var("USERNAME"))
+        .unwrap_or_else(|_| "default".to_string())
+}
+
+#[cfg(target_os = "windows")]
+fn socket_name() -> io::Result<Box<SocketName>> {
+    Ok(format!(r"\\.\pipe\neovide-{}", user_name()).into_boxed_str())
+}
+
+/// Anyone who can write to the directory of the socket can take the place of the running
+/// instance, so it has to be private to the user. The runtime directory already is, otherwise a
+/// directory of our own is made in the shared temporary directory.
+#[cfg(not(target_os = "windows"))]
+fn socket_dir() -> io::Result<PathBuf> {
+    if let Some(dir) = dirs::runtime_dir() {
+        return Ok(dir);
+    }
+
+    let dir = env::temp_dir().join(format!("neovide-{}", user_name()));
+    match DirBuilder::new().mode(0o700).create(&dir) {
+        Ok(()) => {}
+        Err(error) if error.kind() == io::ErrorKind::AlreadyExists => {}
+        Err(error) => return Err(error),
+    }
+    // The directory could have been made by someone else first
+    let metadata = fs::symlink_metadata(&dir)?;
+    if !metadata.is_dir() || metadata.permissions().mode() & 0o077 != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("{} is not private to the user", dir.display()),
+        ));
+    }
+    Ok(dir)
+}
+
+#[cfg(not(target_os = "windows"))]
+fn socket_name() -> io::Result<Box<SocketName>> {
+    Ok(socket_dir()?.join("neovide.sock").into_boxed_path())
+}
+
+/// Connecting to a server, running in WSL, or passing arguments through to Neovim all ask for a
+/// Neovim of their own, which can't be shared.
+fn can_share_instance(settings: &CmdLineSettings) -> bool {
+    let own_args = settings.files_to_open.len() + usize::from(settings.tabs);
+    settings.single_instance
+        && settings.server.is_none()
+        && !settings.wsl
+        && settings.neovim_args.len() <= own_args
+}
+
+/// The running instance has its own working directory, so the paths have to be absolute.
+fn absolute_files(files: &[String]) -> Vec<String> {
+    let Ok(current_dir) = env::current_dir() else {
+        return files.to_vec();
+    };
+    files
+        .iter()
+        .map(|file| current_dir.join(file).to_string_lossy().into_owned())
+        .collect()
+}
+
+#[cfg(target_os = "windows")]
+fn bind(name: &SocketName) -> io::Result<Listener> {
+    let server = ServerOptions::new()
+        .first_pipe_instance(true)
+        .create(name)?;
+    Ok(Listener {
+        name: name.into(),
+        server,
+    })
+}
+
+#[cfg(not(target_os = "windows"))]
+fn bind(name: &SocketName) -> io::Result<Listener> {
+    let listener = UnixListener::bind(name)?;
+    Ok(Listener {
+        name: name.into(),
+        listener,
+    })
+}
+
+/// Creating the first pipe instance is denied when another process owns the pipe.
+#[cfg(target_os = "windows")]
+fn is_taken(error: &io::Error) -> bool {
+    error.kind() == io::ErrorKind::PermissionDenied
+}
+
+#[cfg(not(target_os = "windows"))]
+fn is_taken(error: &io::Error) -> bool {
+    error.kind() == io::ErrorKind::AddrInUse
+}
+
+#[cfg(target_os = "windows")]
+async fn send(name: &SocketName, message: &[u8]) -> io::Result<()> {
+    let mut client = ClientOptions::new().open(name)?;
+    client.write_all(message).await?;
+    client.shutdown().await
+}
+
+#[cfg(not(target_os = "windows"))]
+async fn send(name: &SocketName, message: &[u8]) -> io::Result<()> {
+    let mut stream = UnixStream::connect(name).await?;
+    stream.write_all(message).await?;
+    stream.shutdown().await
+}
+
+/// A running instance can refuse a connection when it's busy, so it's asked again before the
+/// socket is treated as stale.
+async fn forward(name: &SocketName, message: &[u8]) -> io::Result<()> {
+    match send(name, message).await {
+        Err(error) if error.kind() == io::ErrorKind::ConnectionRefused => {
+            sleep(RETRY_DELAY).await;
+            send(name, message).await
+        }
+        result => result,
+    }
+}
+
+/// A socket file is left behind when Neovide doesn't exit cleanly. Nothing listens on it, so
+/// connecting is refused, and it can be replaced. Pipes on Windows go away with their process.
+#[cfg(not(target_os = "windows"))]
+fn remove_stale_socket(name: &SocketName, error: &io::Error) {
+    if error.kind() == io::ErrorKind::ConnectionRefused {
+        info!(
+            "Removing the stale single instance socket {}",
+            name.display()
+        );
+        let _ = std::fs::remove_file(name);
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn remove_stale_socket(_name: &SocketName, _error: &io::Error) {}
+
+/// Binding the socket is what decides which launch becomes the running instance, so two launches
+/// at the same time can't both win. The loser forwards its files to the winner instead.
+async fn claim_socket(name: &SocketName, request: &Request) -> Result<Claim> {
+    let message = serde_json::to_vec(request)?;
+    for _ in 0..CLAIM_ATTEMPTS {
+        match bind(name) {
+            Ok(listener) => return Ok(Claim::Primary(listener)),
+            Err(error) if is_taken(&error) => {}
+            Err(error) => {
+                warn!("Could not listen for other Neovide launches: {error}");
+                return Ok(Claim::Standalone);
+            }
+        }
+
+        match timeout(FORWARD_TIMEOUT, forward(name, &message)).await {
+            Ok(Ok(())) => return Ok(Claim::Forwarded),
+            Ok(Err(error)) => remove_stale_socket(name, &error),
+            Err(_) => warn!("The running Neovide didn't accept the files in time"),
+        }
+        sleep(RETRY_DELAY).await;
+    }
+
+    warn!("Could not reach the running Neovide, starting a new one");
+    Ok(Claim::Standalone)
+}
+
+pub async fn claim(settings: &CmdLineSettings) -> Result<Claim> {
+    if !can_share_instance(settings) {
+        return Ok(Claim::Standalone);
+    }
+    let request = Request {
+        files: absolute_files(&settings.files_to_open),
+    };
+    let name = match socket_name() {
+        Ok(name) => name,
+        Err(error) => {
+            warn!("Could not listen for other Neovide launches: {error}");
+            return Ok(Claim::Standalone);
+        }
+    };
+    claim_socket(&name, &request).await
+}
+
+async fn read_request(mut stream: impl AsyncRead + Unpin) -> Result<Request> {
+    let mut message = Vec::new();
+    stream.read_to_end(&mut message).await?;
+    Ok(serde_json::from_slice(&message)?)
+}
+
+fn open_request(request: Request, proxy: &EventLoopProxy<UserEvent>) {
+    info!("Opening {:?} from another Neovide launch", request.files);
+    for file in request.files {
+        send_ui(ParallelCommand::FileDrop(file));
+    }
+    let _ = proxy.send_event(WindowCommand::FocusWindow.into());
+}
+
+impl Listener {
+    #[cfg(target_os = "windows")]
+    async fn accept(&mut self) -> io::Result<NamedPipeServer> {
+        self.server.connect().await?;
+        // Every client needs its own pipe instance, so create the next one before handing this one
+        // over
+        let next = ServerOptions::new().create(&*self.name)?;
+        Ok(std::mem::replace(&mut self.server, next))
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    async fn accept(&mut self) -> io::Result<UnixStream> {
+        self.listener.accept().await.map(|(stream, _)| stream)
+    }
+
+    /// Opens the files of every later launch until Neovide exits.
+    pub async fn serve(mut self, proxy: EventLoopProxy<UserEvent>) {
+        loop {
+            match self.accept().await {
+                Ok(stream) => match timeout(REQUEST_TIMEOUT, read_request(stream)).await {
+                    Ok(Ok(request)) => open_request(request, &proxy),
+                    Ok(Err(error)) => warn!("Invalid request from another Neovide launch: {error}"),
+                    Err(_) => warn!("Another Neovide launch didn't send its request in time"),
+                },
+                Err(error) => {
+                    warn!("Stopped listening for other Neovide launches: {error}");
+                    return;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+impl Drop for Listener {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.name);
+    }
+}
+
+#[cfg(all(test, not(target_os = "windows")))]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn second_launch_forwards_to_the_first() {
+        let name = env::temp_dir().join(format!("neovide-test-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&name);
+        let request = Request {
+            files: vec!["/tmp/foo.txt".to_string()],
+        };
+
+        let Claim::Primary(mut listener) = claim_socket(&name, &Request::default()).await.unwrap()
+        else {
+            panic!("The first launch should become the running instance");
+        };
+        let (claim, stream) = tokio::join!(claim_socket(&name, &request), listener.accept());
+        assert!(matches!(claim.unwrap(), Claim::Forwarded));
+        assert_eq!(read_request(stream.unwrap()).await.unwrap(), request);
+
+        drop(listener);
+        assert!(!name.exists());
+    }
+
+    #[tokio::test]
+    async fn stale_socket_is_replaced() {
+        let name = env::temp_dir().join(format!("neovide-stale-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&name);
+        // Binding with std and dropping the listener leaves the file behind, like a crash would
+        drop(std::os::unix::net::UnixListener::bind(&name).unwrap());
+        assert!(name.exists());
+
+        let claim = claim_socket(&name, &Request::default()).await.unwrap();
+        assert!(matches!(claim, Claim::Primary(_)));
+    }
+}
@@ -0,0 +1,44 @@
+/// Decodes standard base64, with or without padding. Returns `None` for invalid input.
+pub fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    fn value(byte: u8) -> Option<u32> {
+        match byte {
+            b'A'..=b'Z' => Some((byte - b'A') as u32),
+            b'a'..=b'z' => Some((byte - b'a') as u32 + 26),
+            b'0'..=b'9' => Some((byte - b'0') as u32 + 52),
+            b'+' => Some(62),
+            b'/' => Some(63),
+            _ => None,
+        }
+    }
+
+    let text = text.trim_end().trim_end_matches('=').as_bytes();
+    if text.len() % 4 == 1 {
+        return None;
+    }
+
+    let mut bytes = Vec::with_capacity(text.len() * 3 / 4);
+    for chunk in text.chunks(4) {
+        let mut bits = 0;
+        for (i, byte) in chunk.iter().enumerate() {
+            bits |= value(*byte)? << (18 - 6 * i);
+        }
+        let decoded = [(bits >> 16) as u8, (bits >> 8) as u8, bits as u8];
+        bytes.extend_from_slice(&decoded[..chunk.len() - 1]);
+    }
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_with_and_without_padding() {
+        assert_eq!(decode_base64("bmVvdmlkZQ=="), Some(b"neovide".to_vec()));
+        assert_eq!(decode_base64("bmVvdmlkZQ"), Some(b"neovide".to_vec()));
+        assert_eq!(decode_base64("bmVv"), Some(b"neo".to_vec()));
+        assert_eq!(decode_base64(""), Some(Vec::new()));
+        assert_eq!(decode_base64("bm*v"), None);
+        assert_eq!(decode_base64("bmVvd"), None);
+    }
+}
@@ -1,7 +1,9 @@
+mod base64;
 mod ring_buffer;
 #[cfg(test)]
 mod test;
 
+pub use base64::*;
 pub use ring_buffer::*;
 
 #[cfg(not(target_os = "windows"))]
@@ -0,0 +1,121 @@
+use std::fmt::Write;
+
+use rmpv::Value;
+
+use crate::{cmd_line::CmdLineSettings, renderer::GraphicsBackend, units::PixelSize};
+
+/// What's useful to know about the environment for a bug report, shown by the `diagnostics`
+/// command.
+pub struct Diagnostics {
+    pub backend: GraphicsBackend,
+    /// What the frames are actually rendered with, which is the software renderer when the
+    /// backend couldn't be set up
+    pub renderer: &'static str,
+    pub scale_factor: f64,
+    pub cell_size: PixelSize<f32>,
+    /// The family names of the loaded fonts, including the fallback fonts
+    pub fonts: Vec<String>,
+    /// The current value of every setting, by its name in Neovim
+    pub settings: Vec<(String, Value)>,
+    /// The command line arguments, one line per field of [`CmdLineSettings`]
+    pub command_line: Vec<String>,
+}
+
+impl Diagnostics {
+    pub fn report(&self) -> String {
+        let backend: &str = (&self.backend).into();
+        let mut report = String::new();
+        let _ = writeln!(report, "Neovide version: {}", env!("CARGO_PKG_VERSION"));
+        let _ = writeln!(report, "Platform: {}", std::env::consts::OS);
+        if self.renderer == backend {
+            let _ = writeln!(report, "Backend: {backend}");
+        } else {
+            let _ = writeln!(report, "Backend: {} (instead of {backend})", self.renderer);
+        }
+        let _ = writeln!(report, "Scale factor: {}", self.scale_factor);
+        let _ = writeln!(
+            report,
+            "Cell size: {}x{}",
+            self.cell_size.width, self.cell_size.height
+        );
+        let _ = writeln!(report, "Fonts:");
+        for font in &self.fonts {
+            let _ = writeln!(report, "  {font}");
+        }
+        let _ = writeln!(report, "Settings:");
+        for (name, value) in &self.settings {
+            let _ = writeln!(report, "  {name} = {value}");
+        }
+        let _ = writeln!(report, "Command line settings:");
+        for line in &self.command_line {
+            let _ = writeln!(report, "  {line}");
+        }
+        report
+    }
+}
+
+/// Lists the fields of the command line settings, without the struct around them
+pub fn command_line_settings(settings: &CmdLineSettings) -> Vec<String> {
+    field_lines(&format!("{settings:#?}"))
+}
+
+/// Takes the lines between the struct name and its closing brace out of a pretty debug output
+fn field_lines(debug: &str) -> Vec<String> {
+    let lines: Vec<&str> = debug.lines().collect();
+    let fields = lines
+        .get(1..lines.len().saturating_sub(1))
+        .unwrap_or_default();
+    fields
+        .iter()
+        .map(|line| line.strip_prefix("    ").unwrap_or(line).to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_includes_the_environment_and_the_settings() {
+        let diagnostics = Diagnostics {
+            backend: GraphicsBackend::OpenGL,
+            renderer: "software",
+            scale_factor: 2.0,
+            cell_size: PixelSize::new(9.0, 20.0),
+            fonts: vec!["Fira Code".to_string(), "Noto Color Emoji".to_string()],
+            settings: vec![
+                ("&linespace".to_string(), Value::from(0)),
+                (
+                    "g:neovide_cursor_vfx_mode".to_string(),
+                    Value::from("railgun"),
+                ),
+            ],
+            command_line: vec!["srgb: false,".to_string()],
+        };
+        let report = diagnostics.report();
+
+        for line in [
+            "Neovide version: ",
+            "Backend: software (instead of opengl)",
+            "Scale factor: 2",
+            "Cell size: 9x20",
+            "  Fira Code",
+            "  Noto Color Emoji",
+            "  &linespace = 0",
+            "  g:neovide_cursor_vfx_mode = \"railgun\"",
+            "Command line settings:\n  srgb: false,",
+        ] {
+            assert!(report.contains(line), "{line:?} is missing from {report}");
+        }
+    }
+
+    #[test]
+    fn command_line_settings_are_listed_by_field() {
+        let debug =
+            "CmdLineSettings {\n    srgb: true,\n    neovim_args: [\n        \"-u\",\n    ],\n}";
+        assert_eq!(
+            field_lines(debug),
+            vec!["srgb: true,", "neovim_args: [", "    \"-u\",", "],"]
+        );
+    }
+}
@@ -0,0 +1,121 @@
+use std::time::Duration;
+
+use crate::{
+    renderer::animation_utils::{ease, ease_out_cubic},
+    units::{PixelPos, PixelRect, PixelSize},
+};
+
+/// A smooth transition of the content when entering or leaving fullscreen. The window itself
+/// jumps to its new size, so the grid keeps its old size and gets stretched from where it was to
+/// the whole new window. Neovim only gets the new size once the transition has ended, and the
+/// grid stays stretched over the window until it has been resized.
+pub struct FullscreenTransition {
+    from: PixelSize<f32>,
+    to: Option<PixelSize<f32>>,
+    elapsed: f32,
+    length: f32,
+}
+
+/// How long the finished transition waits for the resized grid, in case Neovim never sends it.
+const GRID_RESIZE_TIMEOUT: Duration = Duration::from_secs(1);
+
+impl FullscreenTransition {
+    pub fn new(from: PixelSize<f32>, length: f32) -> Self {
+        Self {
+            from,
+            to: None,
+            elapsed: 0.0,
+            length,
+        }
+    }
+
+    /// The new size of the window, once the platform has resized it.
+    pub fn set_target(&mut self, to: PixelSize<f32>) {
+        self.to = Some(to);
+    }
+
+    /// Returns whether the transition is still running. It also ends when the window never gets
+    /// resized, so that the grid doesn't get stuck at its old size.
+    pub fn animate(&mut self, dt: f32) -> bool {
+        self.elapsed += dt;
+        !self.is_finished()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.elapsed >= self.length
+    }
+
+    /// Whether the resized grid has been waited for long enough after the end of the transition.
+    pub fn is_stale(&self) -> bool {
+        self.elapsed >= self.length + GRID_RESIZE_TIMEOUT.as_secs_f32()
+    }
+
+    /// Where the content, drawn at its old size, goes in the window. It starts centered in the new
+    /// window at the old size and ends filling it.
+    pub fn content_rect(&self) -> PixelRect<f32> {
+        let Some(to) = self.to else {
+            return PixelRect::from_size(self.from);
+        };
+        let t = if self.length > 0.0 {
+            (self.elapsed / self.length).clamp(0.0, 1.0)
+        } else {
+            1.0
+        };
+        let size = PixelSize::new(
+            ease(ease_out_cubic, self.from.width, to.width, t),
+            ease(ease_out_cubic, self.from.height, to.height, t),
+        );
+        let origin = PixelPos::origin() + (to - size) / 2.0;
+        PixelRect::from_origin_and_size(origin, size)
+    }
+
+    /// The size the content is drawn at, before it's scaled to `content_rect`.
+    pub fn content_size(&self) -> PixelSize<f32> {
+        self.from
+    }
+
+    /// The scale applied to the content so that it fills `content_rect`.
+    pub fn content_scale(&self) -> (f32, f32) {
+        let size = self.content_rect().size();
+        if self.from.width <= 0.0 || self.from.height <= 0.0 {
+            return (1.0, 1.0);
+        }
+        (size.width / self.from.width, size.height / self.from.height)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn content_goes_from_the_old_to_the_new_size() {
+        let from = PixelSize::new(800.0, 600.0);
+        let to = PixelSize::new(1920.0, 1080.0);
+        let mut transition = FullscreenTransition::new(from, 0.2);
+        assert_eq!(transition.content_rect(), PixelRect::from_size(from));
+
+        transition.set_target(to);
+        let start = transition.content_rect();
+        assert_eq!(start.size(), from);
+        assert_eq!(start.min, PixelPos::new(560.0, 240.0));
+
+        assert!(transition.animate(0.1));
+        let middle = transition.content_rect();
+        assert!(middle.width() > from.width && middle.width() < to.width);
+        assert!(middle.height() > from.height && middle.height() < to.height);
+        let (scale_x, scale_y) = transition.content_scale();
+        assert!(scale_x > 1.0 && scale_y > 1.0);
+
+        // Overshooting the length doesn't go past the new size
+        assert!(!transition.animate(0.5));
+        assert_eq!(transition.content_rect(), PixelRect::from_size(to));
+        assert_eq!(transition.content_scale(), (2.4, 1.8));
+
+        // The grid stays stretched while waiting for it to be resized
+        assert!(!transition.is_stale());
+        transition.animate(1.0);
+        assert!(transition.is_stale());
+        assert_eq!(transition.content_rect(), PixelRect::from_size(to));
+    }
+}
@@ -1,4 +1,10 @@
-use crate::bridge::{send_ui, SerialCommand};
+use std::time::{Duration, Instant};
+
+use crate::{
+    bridge::{send_ui, SerialCommand},
+    settings::SETTINGS,
+    window::WindowSettings,
+};
 
 #[allow(unused_imports)]
 use winit::platform::modifier_supplement::KeyEventExtModifierSupplement;
@@ -7,10 +13,7 @@ use winit::{
     keyboard::{Key, KeyCode, KeyLocation, NamedKey, PhysicalKey},
 };
 #[cfg(target_os = "macos")]
-use {
-    crate::{settings::SETTINGS, window::settings::OptionAsMeta, window::WindowSettings},
-    winit::keyboard::ModifiersKeyState,
-};
+use {crate::window::settings::OptionAsMeta, winit::keyboard::ModifiersKeyState};
 
 use crate::profiling::tracy_named_frame;
 
@@ -18,10 +21,66 @@ fn is_ascii_alphabetic_char(text: &str) -> bool {
     text.len() == 1 && text.chars().next().unwrap().is_ascii_alphabetic()
 }
 
+/// A key held down while `g:neovide_key_repeat` is enabled, which Neovide repeats itself instead
+/// of relying on the repeat of the OS.
+struct HeldKey {
+    physical_key: PhysicalKey,
+    text: String,
+    pressed_at: Instant,
+    repeats: u32,
+}
+
+impl HeldKey {
+    fn new(physical_key: PhysicalKey, text: String, pressed_at: Instant) -> Self {
+        Self {
+            physical_key,
+            text,
+            pressed_at,
+            repeats: 0,
+        }
+    }
+
+    /// When the next repeat is due, the first one after `delay` and then `rate` times a second.
+    fn next_repeat(&self, delay: Duration, rate: f32) -> Instant {
+        self.pressed_at + delay + repeat_interval(rate) * self.repeats
+    }
+
+    /// How many repeats have to be sent at `now` to catch up.
+    fn due_repeats(&self, now: Instant, delay: Duration, rate: f32) -> u32 {
+        let Some(repeating) = now.checked_duration_since(self.pressed_at + delay) else {
+            return 0;
+        };
+        let total = (repeating.as_secs_f64() / repeat_interval(rate).as_secs_f64()) as u32 + 1;
+        total.saturating_sub(self.repeats)
+    }
+}
+
+fn repeat_interval(rate: f32) -> Duration {
+    Duration::from_secs_f32(1.0 / rate.max(1.0))
+}
+
+/// The text committed by the input method is typed, unless it spans several lines. Then it's most
+/// likely pasted, so it's pasted into Neovim too, which doesn't auto-indent each line.
+fn ime_commit_command(text: &str) -> SerialCommand {
+    if text.contains('\n') {
+        SerialCommand::Paste(text.to_string())
+    } else {
+        SerialCommand::Keyboard(text.to_string())
+    }
+}
+
+fn key_repeat_timing(settings: &WindowSettings) -> (Duration, f32) {
+    (
+        Duration::from_secs_f32(settings.key_repeat_delay.max(0.0)),
+        settings.key_repeat_rate,
+    )
+}
+
 pub struct KeyboardManager {
     modifiers: Modifiers,
     ime_preedit: (String, Option<(usize, usize)>),
     meta_is_pressed: bool, // see note on 'meta' below
+    held_key: Option<HeldKey>,
 }
 
 impl KeyboardManager {
@@ -30,6 +89,7 @@ impl KeyboardManager {
             modifiers: Modifiers::default(),
             ime_preedit: ("".to_string(), None),
             meta_is_pressed: false,
+            held_key: None,
         }
     }
 
@@ -41,17 +101,43 @@ impl KeyboardManager {
                 ..
             } if self.ime_preedit.0.is_empty() => {
                 log::trace!("{:#?}", key_event);
-                if key_event.state == ElementState::Pressed {
-                    if let Some(text) = self.format_key(key_event) {
-                        log::trace!("Key pressed {} {:?}", text, self.modifiers.state());
-                        tracy_named_frame!("keyboard input");
-                        send_ui(SerialCommand::Keyboard(text));
+                let settings = SETTINGS.get::<WindowSettings>();
+                let key_repeat = settings.key_repeat;
+                match key_event.state {
+                    // The OS repeats are replaced by the ones from `repeat_held_key`
+                    ElementState::Pressed if key_repeat && key_event.repeat => {}
+                    ElementState::Pressed => {
+                        let text = self
+                            .format_key(key_event)
+                            .filter(|text| !settings.input_passthrough_keys.contains(text));
+                        if let Some(text) = text {
+                            log::trace!("Key pressed {} {:?}", text, self.modifiers.state());
+                            tracy_named_frame!("keyboard input");
+                            if key_repeat {
+                                self.held_key = Some(HeldKey::new(
+                                    key_event.physical_key,
+                                    text.clone(),
+                                    Instant::now(),
+                                ));
+                            }
+                            send_ui(SerialCommand::Keyboard(text));
+                        }
+                    }
+                    ElementState::Released => {
+                        if self.held_key.as_ref().map(|key| key.physical_key)
+                            == Some(key_event.physical_key)
+                        {
+                            self.held_key = None;
+                        }
                     }
                 }
             }
+            WindowEvent::Focused(false) => {
+                self.held_key = None;
+            }
             WindowEvent::Ime(Ime::Commit(text)) => {
                 log::trace!("Ime commit {text}");
-                send_ui(SerialCommand::Keyboard(text.to_string()));
+                send_ui(ime_commit_command(text));
             }
             WindowEvent::Ime(Ime::Preedit(text, cursor_offset)) => {
                 self.ime_preedit = (text.to_string(), *cursor_offset)
@@ -60,6 +146,8 @@ impl KeyboardManager {
                 // Record the modifier states so that we can properly add them to the keybinding text
                 log::trace!("{:?}", *modifiers);
                 self.modifiers = *modifiers;
+                // The held key would be repeated with the old modifiers
+                self.held_key = None;
 
                 #[cfg(target_os = "macos")]
                 {
@@ -85,6 +173,73 @@ impl KeyboardManager {
         }
     }
 
+    /// Sends the repeats of the held key that are due, when `g:neovide_key_repeat` is enabled.
+    pub fn repeat_held_key(&mut self) {
+        let Some(held_key) = self.held_key.as_mut() else {
+            return;
+        };
+        let settings = SETTINGS.get::<WindowSettings>();
+        if !settings.key_repeat {
+            self.held_key = None;
+            return;
+        }
+
+        // Like the OS repeat, repeats that were missed while the loop was busy are dropped
+        // instead of being sent all at once
+        let (delay, rate) = key_repeat_timing(&settings);
+        let due_repeats = held_key.due_repeats(Instant::now(), delay, rate);
+        if due_repeats > 0 {
+            send_ui(SerialCommand::Keyboard(held_key.text.clone()));
+            held_key.repeats += due_repeats;
+        }
+    }
+
+    /// When the held key has to be repeated next.
+    pub fn next_key_repeat(&self) -> Option<Instant> {
+        let held_key = self.held_key.as_ref()?;
+        let (delay, rate) = key_repeat_timing(&SETTINGS.get::<WindowSettings>());
+        Some(held_key.next_repeat(delay, rate))
+    }
+
+    /// Returns the character of a Ctrl shortcut, which may be one of the zoom bindings.
+    fn zoom_key<'a>(&self, event: &'a WindowEvent) -> Option<&'a str> {
+        let WindowEvent::KeyboardInput {
+            event: key_event,
+            is_synthetic: false,
+            ..
+        } = event
+        else {
+            return None;
+        };
+        let state = self.modifiers.state();
+        if key_event.state != ElementState::Pressed
+            || !state.control_key()
+            || state.alt_key()
+            || state.super_key()
+        {
+            return None;
+        }
+        match key_event.logical_key.as_ref() {
+            Key::Character(character) => Some(character),
+            _ => None,
+        }
+    }
+
+    /// Returns the number of zoom steps for Ctrl+= and Ctrl+-, which are handled by Neovide
+    /// instead of being sent to Neovim.
+    pub fn zoom_steps(&self, event: &WindowEvent) -> Option<i32> {
+        match self.zoom_key(event)? {
+            "=" | "+" => Some(1),
+            "-" => Some(-1),
+            _ => None,
+        }
+    }
+
+    /// Whether the event is Ctrl+0, which resets the zoom.
+    pub fn resets_zoom(&self, event: &WindowEvent) -> bool {
+        self.zoom_key(event) == Some("0")
+    }
+
     fn handle_numpad_numkey<'a>(
         is_numlock_enabled: bool,
         numlock_str: &'a str,
@@ -366,3 +521,50 @@ fn get_special_key(key_event: &KeyEvent) -> Option<&str> {
 // avoid confusing users who have a post-2017 keyboard and are not aware of this
 // history, it is probably best to refer to this physical key as the 'option'
 // key, and not as the 'alt' key.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn multi_line_commits_are_pasted() {
+        assert!(matches!(
+            ime_commit_command("fn main() {\n    todo!()\n}\n"),
+            SerialCommand::Paste(text) if text.lines().count() == 3
+        ));
+        assert!(matches!(
+            ime_commit_command("日本語"),
+            SerialCommand::Keyboard(text) if text == "日本語"
+        ));
+    }
+
+    #[test]
+    fn held_keys_repeat_after_the_delay_at_the_rate() {
+        let pressed_at = Instant::now();
+        let delay = Duration::from_millis(500);
+        let mut held_key = HeldKey::new(
+            PhysicalKey::Code(KeyCode::KeyJ),
+            "j".to_string(),
+            pressed_at,
+        );
+
+        assert_eq!(held_key.next_repeat(delay, 10.0), pressed_at + delay);
+        assert_eq!(held_key.due_repeats(pressed_at, delay, 10.0), 0);
+        assert_eq!(held_key.due_repeats(pressed_at + delay, delay, 10.0), 1);
+
+        held_key.repeats = 1;
+        assert_eq!(
+            held_key.next_repeat(delay, 10.0),
+            pressed_at + Duration::from_millis(600)
+        );
+        assert_eq!(
+            held_key.due_repeats(pressed_at + Duration::from_millis(550), delay, 10.0),
+            0
+        );
+        // After a busy frame more than one repeat can be due
+        assert_eq!(
+            held_key.due_repeats(pressed_at + Duration::from_millis(720), delay, 10.0),
+            2
+        );
+    }
+}
@@ -1,6 +1,9 @@
+mod diagnostics;
 mod error_window;
+mod fullscreen_transition;
 mod keyboard_manager;
 mod mouse_manager;
+mod reduce_motion;
 mod settings;
 mod update_loop;
 mod window_wrapper;
@@ -12,8 +15,9 @@ mod macos;
 use std::env;
 
 use winit::{
-    dpi::{PhysicalSize, Size},
+    dpi::{PhysicalPosition, PhysicalSize, Size},
     event_loop::{ActiveEventLoop, EventLoop},
+    monitor::MonitorHandle,
     window::{Icon, Theme, Window},
 };
 
@@ -26,6 +30,9 @@ use winit::platform::{wayland::WindowAttributesExtWayland, x11::WindowAttributes
 #[cfg(target_os = "windows")]
 use winit::platform::windows::WindowAttributesExtWindows;
 
+#[cfg(target_os = "windows")]
+use crate::renderer::{selected_backend, GraphicsBackend};
+
 #[cfg(target_os = "macos")]
 use winit::platform::macos::EventLoopBuilderExtMacOS;
 
@@ -41,12 +48,14 @@ use crate::{
         clamped_grid_size, load_last_window_settings, save_window_size, HotReloadConfigs,
         PersistentWindowSettings, SettingsChanged, SETTINGS,
     },
-    units::GridSize,
+    units::{GridPos, GridSize},
 };
 pub use error_window::show_error_window;
+pub use reduce_motion::is_motion_reduced;
 pub use settings::{WindowSettings, WindowSettingsChanged};
 pub use update_loop::ShouldRender;
 pub use update_loop::UpdateLoop;
+pub use update_loop::{control_flow, event_deadline};
 pub use window_wrapper::WinitWindowWrapper;
 
 static ICON: &[u8] = include_bytes!("../../assets/neovide.ico");
@@ -69,8 +78,22 @@ pub enum WindowCommand {
     TitleChanged(String),
     SetMouseEnabled(bool),
     ListAvailableFonts,
+    /// Shows the diagnostics report in Neovim, or writes it to the file when there's a path
+    ShowDiagnostics(Option<String>),
+    /// Saves the current frame as a PNG file at the path
+    Screenshot(String),
+    ReloadFont(String),
+    SetSecondaryCursors(Vec<GridPos<u64>>),
+    /// The expanded tab under the cursor, as the cells before the cursor cell and its width
+    SetCursorTabSpan(Option<(u64, u64)>),
     FocusWindow,
     Minimize,
+    ToggleFullscreen,
+    Zoom(i32),
+    ResetZoom,
+    Bell {
+        visual: bool,
+    },
     #[allow(dead_code)] // Theme change is only used on macOS right now
     ThemeChanged(Option<Theme>),
     #[cfg(windows)]
@@ -136,6 +159,12 @@ pub fn create_window(event_loop: &ActiveEventLoop, maximized: bool, title: &str)
         Some(PersistentWindowSettings::Windowed { position, .. }) => Some(position),
         _ => None,
     };
+    let previous_position = match (preferred_monitor(event_loop), previous_position) {
+        // The last position is still used when it's on the requested monitor
+        (Some(monitor), Some(position)) if monitor_contains(&monitor, position) => Some(position),
+        (Some(monitor), _) => Some(monitor.position()),
+        (None, position) => position,
+    };
 
     let window_attributes = Window::default_attributes()
         .with_title(title)
@@ -145,7 +174,7 @@ pub fn create_window(event_loop: &ActiveEventLoop, maximized: bool, title: &str)
         .with_visible(false);
 
     #[cfg(target_os = "windows")]
-    let window_attributes = if !cmd_line_settings.opengl {
+    let window_attributes = if selected_backend() == GraphicsBackend::Direct3D {
         WindowAttributesExtWindows::with_no_redirection_bitmap(window_attributes, true)
     } else {
         window_attributes
@@ -205,6 +234,24 @@ pub fn create_window(event_loop: &ActiveEventLoop, maximized: bool, title: &str)
     window_config
 }
 
+/// The monitor selected with `--monitor`, or the primary one when it isn't connected. Without a
+/// monitor the window manager places the window, usually where the mouse or the focused window is.
+fn preferred_monitor(event_loop: &ActiveEventLoop) -> Option<MonitorHandle> {
+    let index = SETTINGS.get::<CmdLineSettings>().monitor?;
+    event_loop.available_monitors().nth(index).or_else(|| {
+        log::warn!("Monitor {index} isn't connected, opening the window on the primary monitor");
+        event_loop.primary_monitor()
+    })
+}
+
+fn monitor_contains(monitor: &MonitorHandle, position: PhysicalPosition<i32>) -> bool {
+    let monitor_position = monitor.position();
+    let monitor_size = monitor.size();
+    (monitor_position.x..monitor_position.x + monitor_size.width as i32).contains(&position.x)
+        && (monitor_position.y..monitor_position.y + monitor_size.height as i32)
+            .contains(&position.y)
+}
+
 #[derive(Clone, Debug)]
 pub enum WindowSize {
     Size(PhysicalSize<u32>),
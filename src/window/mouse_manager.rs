@@ -5,16 +5,18 @@ use std::{
 };
 
 use winit::{
+    dpi::PhysicalPosition,
     event::WindowEvent,
     event::{DeviceId, ElementState, MouseButton, MouseScrollDelta, Touch, TouchPhase},
-    window::Window,
+    window::{CursorIcon, Window},
 };
 
 use glamour::Contains;
 
 use crate::{
     bridge::{send_ui, SerialCommand},
-    renderer::{Renderer, WindowDrawDetails},
+    clipboard,
+    renderer::{Renderer, ScrollbarDetails, WindowDrawDetails},
     settings::SETTINGS,
     units::{GridPos, GridScale, GridVec, PixelPos, PixelRect, PixelSize, PixelVec},
     window::keyboard_manager::KeyboardManager,
@@ -32,6 +34,40 @@ fn clamp_position(
     position.clamp(min, max.into())
 }
 
+/// Winit reports the pointer in physical pixels, which is also what the grid scale and the window
+/// regions are measured in. Converting it anywhere else would offset the clicks on HiDPI screens.
+fn pixel_position(position: PhysicalPosition<f64>) -> PixelPos<f32> {
+    PixelPos::new(position.x as f32, position.y as f32)
+}
+
+/// Maps a pointer position to the cell under it. The region already starts after the window
+/// padding, so the position is made relative to it before dividing by the cell size.
+fn grid_position(
+    position: PixelPos<f32>,
+    region: PixelRect<f32>,
+    grid_scale: GridScale,
+) -> GridPos<u32> {
+    let clamped_position = clamp_position(position, region, grid_scale);
+    let relative_position = (clamped_position - region.min).to_point();
+
+    (relative_position / grid_scale)
+        .floor()
+        .max((0.0, 0.0).into())
+        .try_cast()
+        .unwrap()
+}
+
+/// Parses the icon shown over the text grid, using the CSS cursor names
+fn text_cursor_icon(name: &str) -> CursorIcon {
+    name.parse().unwrap_or(CursorIcon::Text)
+}
+
+/// Middle clicks paste the primary selection on the platforms that have one, like other Linux
+/// apps, instead of being sent to Neovim.
+fn pastes_primary_selection(mouse_button: MouseButton, settings: &WindowSettings) -> bool {
+    cfg!(target_os = "linux") && settings.middle_click_paste && mouse_button == MouseButton::Middle
+}
+
 fn mouse_button_to_button_text(mouse_button: MouseButton) -> Option<String> {
     match mouse_button {
         MouseButton::Left => Some("left".to_owned()),
@@ -51,7 +87,7 @@ struct DragDetails {
 struct EditorState<'a> {
     grid_scale: &'a GridScale,
     window_regions: &'a Vec<WindowDrawDetails>,
-    window: &'a Window,
+    window_size: PixelSize<f32>,
     keyboard_manager: &'a KeyboardManager,
 }
 
@@ -76,7 +112,11 @@ pub struct MouseManager {
     touch_position: HashMap<(DeviceId, u64), TouchTrace>,
 
     mouse_hidden: bool,
-    pub enabled: bool,
+    cursor_icon: Option<CursorIcon>,
+    enabled: bool,
+
+    // The scrollbar being dragged, along with the last line it was scrolled to
+    scrollbar_drag: Option<(ScrollbarDetails, u64)>,
 }
 
 impl MouseManager {
@@ -89,7 +129,21 @@ impl MouseManager {
             scroll_position: GridPos::default(),
             touch_position: HashMap::new(),
             mouse_hidden: false,
+            cursor_icon: None,
             enabled: true,
+            scrollbar_drag: None,
+        }
+    }
+
+    /// Follows Neovim's `mouse` option, which is signaled with the `mouse_on` and `mouse_off`
+    /// events. No mouse input is forwarded while it's disabled.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            // Don't finish a drag that started before the mouse was disabled
+            self.drag_details = None;
+            self.scrollbar_drag = None;
+            self.has_moved = false;
         }
     }
 
@@ -113,31 +167,26 @@ impl MouseManager {
         window_details: &WindowDrawDetails,
         editor_state: &EditorState,
     ) -> GridPos<u32> {
-        let global_bounds = window_details.region;
-        let clamped_position = clamp_position(
+        grid_position(
             self.window_position,
-            global_bounds,
+            window_details.region,
             *editor_state.grid_scale,
-        );
-        let relative_position = (clamped_position - window_details.region.min).to_point();
-
-        (relative_position / *editor_state.grid_scale)
-            .floor()
-            .max((0.0, 0.0).into())
-            .try_cast()
-            .unwrap()
+        )
     }
 
     fn handle_pointer_motion(&mut self, position: PixelPos<f32>, editor_state: &EditorState) {
-        let window_size = editor_state.window.inner_size();
-        let window_size = PixelSize::new(window_size.width as f32, window_size.height as f32);
-        let relative_window_rect = PixelRect::from_size(window_size);
+        let relative_window_rect = PixelRect::from_size(editor_state.window_size);
         if !relative_window_rect.contains(&position) {
             return;
         }
 
         self.window_position = position;
 
+        if self.scrollbar_drag.is_some() {
+            self.drag_scrollbar();
+            return;
+        }
+
         // If dragging, the relevant window (the one which we send all commands to) is the one
         // which the mouse drag started on. Otherwise its the top rendered window
         let window_details = if let Some(drag_details) = &self.drag_details {
@@ -166,7 +215,7 @@ impl MouseManager {
                             .keyboard_manager
                             .format_modifier_string("", true),
                     });
-                } else if SETTINGS.get::<WindowSettings>().mouse_move_event {
+                } else if self.enabled && SETTINGS.get::<WindowSettings>().mouse_move_event {
                     // Send a mouse move command
                     send_ui(SerialCommand::MouseButton {
                         button: "move".into(),
@@ -184,12 +233,41 @@ impl MouseManager {
         }
     }
 
+    // Show the text icon over the grid and the default one over everything else, like the padding
+    fn update_cursor_icon(&mut self, window: &Window, editor_state: &EditorState) {
+        let icon = if self.get_window_details_under_mouse(editor_state).is_some() {
+            text_cursor_icon(&SETTINGS.get::<WindowSettings>().mouse_cursor_icon)
+        } else {
+            CursorIcon::Default
+        };
+
+        if self.cursor_icon != Some(icon) {
+            window.set_cursor(icon);
+            self.cursor_icon = Some(icon);
+        }
+    }
+
     fn handle_pointer_transition(
         &mut self,
         mouse_button: MouseButton,
         down: bool,
         editor_state: &EditorState,
     ) {
+        if self.enabled
+            && mouse_button == MouseButton::Left
+            && self.handle_scrollbar_transition(down, editor_state)
+        {
+            return;
+        }
+
+        if self.enabled && pastes_primary_selection(mouse_button, &SETTINGS.get::<WindowSettings>())
+        {
+            if down {
+                self.paste_primary_selection(editor_state);
+            }
+            return;
+        }
+
         // For some reason pointer down is handled differently from pointer up and drag.
         // Floating windows: relative coordinates are great.
         // Non floating windows: rather than global coordinates, relative are needed
@@ -237,6 +315,70 @@ impl MouseManager {
         }
     }
 
+    /// Moves the cursor to the cell under the mouse with a left click, and pastes the primary
+    /// selection there.
+    fn paste_primary_selection(&self, editor_state: &EditorState) {
+        let Some(details) = self.get_window_details_under_mouse(editor_state) else {
+            return;
+        };
+        let text = match clipboard::get_contents("*") {
+            Ok(text) if !text.is_empty() => text,
+            Ok(_) => return,
+            Err(error) => {
+                log::warn!("Could not read the primary selection: {error}");
+                return;
+            }
+        };
+
+        let position = self.get_relative_position(details, editor_state).to_tuple();
+        for action in ["press", "release"] {
+            send_ui(SerialCommand::MouseButton {
+                button: "left".to_owned(),
+                action: action.to_owned(),
+                grid_id: details.event_grid_id(),
+                position,
+                modifier_string: String::new(),
+            });
+        }
+        send_ui(SerialCommand::Paste(text));
+    }
+
+    /// Starts or ends dragging a scrollbar, returns true when the click was on one.
+    fn handle_scrollbar_transition(&mut self, down: bool, editor_state: &EditorState) -> bool {
+        if !down {
+            return self.scrollbar_drag.take().is_some();
+        }
+
+        let scrollbar = self
+            .get_window_details_under_mouse(editor_state)
+            .and_then(|details| details.scrollbar.as_ref())
+            .filter(|scrollbar| scrollbar.track.contains(&self.window_position));
+        let Some(scrollbar) = scrollbar else {
+            return false;
+        };
+
+        self.scrollbar_drag = Some((scrollbar.clone(), 0));
+        self.drag_scrollbar();
+        true
+    }
+
+    fn drag_scrollbar(&mut self) {
+        let Some((scrollbar, last_line)) = self.scrollbar_drag.as_mut() else {
+            return;
+        };
+
+        let top_line = scrollbar
+            .position
+            .top_line_at(scrollbar.track, self.window_position.y);
+        if top_line != *last_line {
+            *last_line = top_line;
+            send_ui(SerialCommand::ScrollWindow {
+                window: scrollbar.position.window,
+                top_line,
+            });
+        }
+    }
+
     fn handle_line_scroll(&mut self, amount: GridVec<f32>, editor_state: &EditorState) {
         if !self.enabled {
             return;
@@ -295,6 +437,26 @@ impl MouseManager {
         self.handle_line_scroll(amount, editor_state);
     }
 
+    /// Returns the number of zoom steps for Ctrl+Scroll, which is handled by Neovide instead of
+    /// being sent to Neovim.
+    pub fn zoom_steps(
+        &self,
+        event: &WindowEvent,
+        keyboard_manager: &KeyboardManager,
+    ) -> Option<i32> {
+        let WindowEvent::MouseWheel {
+            delta: MouseScrollDelta::LineDelta(_, y),
+            ..
+        } = event
+        else {
+            return None;
+        };
+        if *y == 0.0 || keyboard_manager.format_modifier_string("", true) != "C-" {
+            return None;
+        }
+        Some((y.abs().round() as i32).max(1) * y.signum() as i32)
+    }
+
     fn handle_touch(
         &mut self,
         finger_id: (DeviceId, u64),
@@ -387,22 +549,28 @@ impl MouseManager {
         renderer: &Renderer,
         window: &Window,
     ) {
+        let window_size = window.inner_size();
         let editor_state = EditorState {
             grid_scale: &renderer.grid_renderer.grid_scale,
             window_regions: &renderer.window_regions,
-            window,
+            window_size: PixelSize::new(window_size.width as f32, window_size.height as f32),
             keyboard_manager,
         };
+        if matches!(
+            event,
+            WindowEvent::CursorMoved { .. }
+                | WindowEvent::MouseInput { .. }
+                | WindowEvent::MouseWheel { .. }
+        ) && self.mouse_hidden
+        {
+            window.set_cursor_visible(true);
+            self.mouse_hidden = false;
+        }
+
         match event {
             WindowEvent::CursorMoved { position, .. } => {
-                self.handle_pointer_motion(
-                    (position.x as f32, position.y as f32).into(),
-                    &editor_state,
-                );
-                if self.mouse_hidden {
-                    window.set_cursor_visible(true);
-                    self.mouse_hidden = false;
-                }
+                self.handle_pointer_motion(pixel_position(*position), &editor_state);
+                self.update_cursor_icon(window, &editor_state);
             }
             WindowEvent::MouseWheel {
                 delta: MouseScrollDelta::LineDelta(x, y),
@@ -420,7 +588,7 @@ impl MouseManager {
                 ..
             }) => self.handle_touch(
                 (*device_id, *id),
-                PixelPos::new(location.x as f32, location.y as f32),
+                pixel_position(*location),
                 phase,
                 &editor_state,
             ),
@@ -435,7 +603,11 @@ impl MouseManager {
             } => {
                 if key_event.state == ElementState::Pressed {
                     let window_settings = SETTINGS.get::<WindowSettings>();
-                    if window_settings.hide_mouse_when_typing && !self.mouse_hidden {
+                    // Keep the mouse visible while it's used for a selection
+                    if window_settings.hide_mouse_when_typing
+                        && !self.mouse_hidden
+                        && self.drag_details.is_none()
+                    {
                         window.set_cursor_visible(false);
                         self.mouse_hidden = true;
                     }
@@ -445,3 +617,146 @@ impl MouseManager {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_cursor_icon_falls_back_to_the_text_beam() {
+        assert_eq!(text_cursor_icon("text"), CursorIcon::Text);
+        assert_eq!(text_cursor_icon("default"), CursorIcon::Default);
+        assert_eq!(text_cursor_icon("crosshair"), CursorIcon::Crosshair);
+        assert_eq!(text_cursor_icon(""), CursorIcon::Text);
+        assert_eq!(text_cursor_icon("not an icon"), CursorIcon::Text);
+    }
+
+    #[test]
+    fn clicks_land_on_the_cell_under_the_pointer_on_hidpi() {
+        use winit::dpi::LogicalPosition;
+
+        let scale_factor = 2.0;
+        // 8x16 logical cells, with 10 logical pixels of padding to the left
+        let grid_scale = GridScale::new(PixelSize::new(8.0, 16.0) * scale_factor as f32);
+        let region =
+            PixelRect::from_origin_and_size(PixelPos::new(20.0, 0.0), PixelSize::new(160.0, 320.0));
+
+        // 25 logical pixels into the grid is the fourth column, and 20 the second row
+        let position = LogicalPosition::new(35.0, 20.0).to_physical::<f64>(scale_factor);
+        assert_eq!(
+            grid_position(pixel_position(position), region, grid_scale),
+            GridPos::new(3, 1)
+        );
+
+        // The padding maps to the first column, and beyond the grid to the last cell
+        let position = LogicalPosition::new(5.0, 500.0).to_physical::<f64>(scale_factor);
+        assert_eq!(
+            grid_position(pixel_position(position), region, grid_scale),
+            GridPos::new(0, 9)
+        );
+    }
+
+    #[test]
+    fn middle_clicks_paste_the_primary_selection_on_linux() {
+        let mut settings = WindowSettings::default();
+        // Neovim gets the middle clicks unless it's asked for
+        assert!(!pastes_primary_selection(MouseButton::Middle, &settings));
+
+        settings.middle_click_paste = true;
+        assert_eq!(
+            pastes_primary_selection(MouseButton::Middle, &settings),
+            cfg!(target_os = "linux")
+        );
+        assert!(!pastes_primary_selection(MouseButton::Left, &settings));
+    }
+
+    #[test]
+    fn disabling_the_mouse_cancels_the_drag() {
+        let mut mouse_manager = MouseManager::new();
+        mouse_manager.drag_details = Some(DragDetails {
+            draw_details: WindowDrawDetails {
+                id: 1,
+                region: PixelRect::from_size(PixelSize::new(100.0, 100.0)),
+                scrollbar: None,
+            },
+            button: MouseButton::Left,
+        });
+        mouse_manager.has_moved = true;
+
+        mouse_manager.set_enabled(false);
+        assert!(!mouse_manager.enabled);
+        assert!(mouse_manager.drag_details.is_none());
+        assert!(!mouse_manager.has_moved);
+
+        mouse_manager.set_enabled(true);
+        assert!(mouse_manager.enabled);
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn mouse_input_is_only_forwarded_while_the_mouse_is_on() {
+        use crate::{
+            bridge::{take_sent_ui_commands, UiCommand},
+            cmd_line::CmdLineSettings,
+        };
+
+        SETTINGS.set(&CmdLineSettings::default());
+        SETTINGS.set(&WindowSettings {
+            mouse_move_event: true,
+            ..WindowSettings::default()
+        });
+        let grid_scale = GridScale::new(PixelSize::new(10.0, 20.0));
+        let window_regions = vec![WindowDrawDetails {
+            id: 1,
+            region: PixelRect::from_size(PixelSize::new(100.0, 100.0)),
+            scrollbar: None,
+        }];
+        let keyboard_manager = KeyboardManager::new();
+        let editor_state = EditorState {
+            grid_scale: &grid_scale,
+            window_regions: &window_regions,
+            window_size: PixelSize::new(100.0, 100.0),
+            keyboard_manager: &keyboard_manager,
+        };
+        let use_the_mouse = |mouse_manager: &mut MouseManager| {
+            mouse_manager.handle_pointer_motion(PixelPos::new(15.0, 25.0), &editor_state);
+            mouse_manager.handle_pointer_transition(MouseButton::Left, true, &editor_state);
+            mouse_manager.handle_pointer_motion(PixelPos::new(55.0, 45.0), &editor_state);
+            mouse_manager.handle_pointer_transition(MouseButton::Left, false, &editor_state);
+            mouse_manager.handle_line_scroll(GridVec::new(0.0, 2.0), &editor_state);
+            take_sent_ui_commands()
+        };
+        let mut mouse_manager = MouseManager::new();
+        take_sent_ui_commands();
+
+        // Neovim sends mouse_off when its `mouse` option is empty
+        mouse_manager.set_enabled(false);
+        assert!(use_the_mouse(&mut mouse_manager).is_empty());
+
+        mouse_manager.set_enabled(true);
+        let sent: Vec<_> = use_the_mouse(&mut mouse_manager)
+            .into_iter()
+            .map(|command| match command {
+                UiCommand::Serial(SerialCommand::MouseButton { button, action, .. }) => {
+                    format!("{button} {action}")
+                }
+                UiCommand::Serial(SerialCommand::Drag { button, .. }) => format!("{button} drag"),
+                UiCommand::Serial(SerialCommand::Scroll { direction, .. }) => {
+                    format!("scroll {direction}")
+                }
+                command => format!("{command:?}"),
+            })
+            .collect();
+        assert_eq!(
+            sent,
+            vec![
+                "move ",
+                "left press",
+                "left drag",
+                "left release",
+                "scroll up",
+                "scroll up"
+            ]
+        );
+    }
+}
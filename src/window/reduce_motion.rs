@@ -0,0 +1,57 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::{
+    settings::SETTINGS,
+    window::{settings::ReduceMotion, WindowSettings},
+};
+
+static SYSTEM_PREFERS_REDUCED_MOTION: AtomicBool = AtomicBool::new(false);
+
+/// Whether animations should be skipped, either because of `g:neovide_reduce_motion` or the
+/// "reduce motion" accessibility preference of the OS.
+pub fn is_motion_reduced() -> bool {
+    match SETTINGS.get::<WindowSettings>().reduce_motion {
+        ReduceMotion::Auto => SYSTEM_PREFERS_REDUCED_MOTION.load(Ordering::Relaxed),
+        ReduceMotion::Always => true,
+        ReduceMotion::Never => false,
+    }
+}
+
+/// Queries the OS preference again, it can change at any time, so this is done whenever the window
+/// gains focus.
+pub fn update_system_preference() {
+    SYSTEM_PREFERS_REDUCED_MOTION.store(query_system_preference(), Ordering::Relaxed);
+}
+
+#[cfg(target_os = "windows")]
+fn query_system_preference() -> bool {
+    use windows::Win32::{
+        Foundation::BOOL,
+        UI::WindowsAndMessaging::{
+            SystemParametersInfoW, SPI_GETCLIENTAREAANIMATION, SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS,
+        },
+    };
+
+    let mut animations_enabled = BOOL::from(true);
+    let result = unsafe {
+        SystemParametersInfoW(
+            SPI_GETCLIENTAREAANIMATION,
+            0,
+            Some(&mut animations_enabled as *mut BOOL as *mut _),
+            SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS(0),
+        )
+    };
+    result.is_ok() && !animations_enabled.as_bool()
+}
+
+#[cfg(target_os = "macos")]
+fn query_system_preference() -> bool {
+    use icrate::AppKit::NSWorkspace;
+
+    unsafe { NSWorkspace::sharedWorkspace().accessibilityDisplayShouldReduceMotion() }
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn query_system_preference() -> bool {
+    false
+}
@@ -1,5 +1,5 @@
-#[cfg(target_os = "macos")]
-use {log::error, rmpv::Value};
+use log::error;
+use rmpv::Value;
 
 use crate::settings::*;
 
@@ -7,28 +7,45 @@ use crate::settings::*;
 pub struct WindowSettings {
     pub refresh_rate: u64,
     pub refresh_rate_idle: u64,
+    pub no_idle: bool,
     pub transparency: f32,
     pub window_blurred: bool,
     pub scale_factor: f32,
     pub fullscreen: bool,
+    pub fullscreen_animation: bool,
+    pub fullscreen_animation_length: f32,
     pub iso_layout: bool,
     pub remember_window_size: bool,
     pub remember_window_position: bool,
     pub hide_mouse_when_typing: bool,
+    pub middle_click_paste: bool,
+    pub mouse_cursor_icon: String,
+    pub zoom_bindings: bool,
+    pub reduce_motion: ReduceMotion,
     pub touch_deadzone: f32,
     pub touch_drag_timeout: f32,
     pub background_color: String,
     pub confirm_quit: bool,
+    pub wait_for_default_colors: bool,
+    pub loading_indicator: bool,
     pub padding_top: u32,
     pub padding_left: u32,
     pub padding_right: u32,
     pub padding_bottom: u32,
+    pub gutter_columns: u32,
+    pub snap_window_to_grid: bool,
+    pub bell: BellStyle,
+    pub bell_flash_length: f32,
     pub theme: String,
     #[cfg(target_os = "macos")]
     pub input_macos_alt_is_meta: bool,
     #[cfg(target_os = "macos")]
     pub input_macos_option_key_is_meta: OptionAsMeta,
     pub input_ime: bool,
+    pub key_repeat: bool,
+    pub key_repeat_delay: f32,
+    pub key_repeat_rate: f32,
+    pub input_passthrough_keys: PassthroughKeys,
     pub show_border: bool,
 
     #[option = "mousemoveevent"]
@@ -46,26 +63,43 @@ impl Default for WindowSettings {
             window_blurred: false,
             scale_factor: 1.0,
             fullscreen: false,
+            fullscreen_animation: false,
+            fullscreen_animation_length: 0.2,
             iso_layout: false,
             refresh_rate: 60,
             refresh_rate_idle: 5,
+            no_idle: false,
             remember_window_size: true,
             remember_window_position: true,
             hide_mouse_when_typing: false,
+            middle_click_paste: false,
+            mouse_cursor_icon: "text".to_string(),
+            zoom_bindings: false,
+            reduce_motion: ReduceMotion::Auto,
             touch_deadzone: 6.0,
             touch_drag_timeout: 0.17,
             background_color: "".to_string(),
             confirm_quit: true,
+            wait_for_default_colors: true,
+            loading_indicator: false,
             padding_top: 0,
             padding_left: 0,
             padding_right: 0,
             padding_bottom: 0,
+            gutter_columns: 0,
+            snap_window_to_grid: false,
+            bell: BellStyle::None,
+            bell_flash_length: 0.15,
             theme: "".to_string(),
             #[cfg(target_os = "macos")]
             input_macos_alt_is_meta: false,
             #[cfg(target_os = "macos")]
             input_macos_option_key_is_meta: OptionAsMeta::None,
             input_ime: true,
+            key_repeat: false,
+            key_repeat_delay: 0.5,
+            key_repeat_rate: 30.0,
+            input_passthrough_keys: PassthroughKeys::default(),
             mouse_move_event: false,
             observed_lines: None,
             observed_columns: None,
@@ -74,6 +108,174 @@ impl Default for WindowSettings {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReduceMotion {
+    /// Follow the preference of the OS
+    Auto,
+    Always,
+    Never,
+}
+
+impl ParseFromValue for ReduceMotion {
+    fn parse_from_value(&mut self, value: Value) {
+        if let Some(reduce_motion) = value.as_bool() {
+            *self = if reduce_motion {
+                ReduceMotion::Always
+            } else {
+                ReduceMotion::Never
+            };
+        } else if value.is_str() {
+            *self = match value.as_str().unwrap() {
+                "auto" => ReduceMotion::Auto,
+                "always" => ReduceMotion::Always,
+                "never" => ReduceMotion::Never,
+                value => {
+                    error!("Setting ReduceMotion expected one of `auto`, `always`, or `never`, but received {:?}", value);
+                    return;
+                }
+            };
+        } else {
+            error!(
+                "Setting ReduceMotion expected a string or boolean, but received {:?}",
+                value
+            );
+        }
+    }
+}
+
+impl From<ReduceMotion> for Value {
+    fn from(reduce_motion: ReduceMotion) -> Self {
+        match reduce_motion {
+            ReduceMotion::Auto => Value::from("auto"),
+            ReduceMotion::Always => Value::from("always"),
+            ReduceMotion::Never => Value::from("never"),
+        }
+    }
+}
+
+/// What to do when Neovim rings the bell. A `visual_bell`, sent when 'visualbell' is set, always
+/// flashes unless the bell is disabled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BellStyle {
+    None,
+    Flash,
+    Beep,
+    Both,
+}
+
+impl BellStyle {
+    /// Returns whether to flash the window and whether to play the system beep.
+    pub fn actions(self, visual: bool) -> (bool, bool) {
+        match (self, visual) {
+            (BellStyle::None, _) => (false, false),
+            (_, true) => (true, false),
+            (BellStyle::Flash, false) => (true, false),
+            (BellStyle::Beep, false) => (false, true),
+            (BellStyle::Both, false) => (true, true),
+        }
+    }
+}
+
+impl ParseFromValue for BellStyle {
+    fn parse_from_value(&mut self, value: Value) {
+        if value.is_str() {
+            *self = match value.as_str().unwrap() {
+                "none" => BellStyle::None,
+                "flash" => BellStyle::Flash,
+                "beep" => BellStyle::Beep,
+                "both" => BellStyle::Both,
+                value => {
+                    error!("Setting BellStyle expected one of `none`, `flash`, `beep`, or `both`, but received {:?}", value);
+                    return;
+                }
+            };
+        } else {
+            error!(
+                "Setting BellStyle expected string, but received {:?}",
+                value
+            );
+        }
+    }
+}
+
+impl From<BellStyle> for Value {
+    fn from(bell: BellStyle) -> Self {
+        match bell {
+            BellStyle::None => Value::from("none"),
+            BellStyle::Flash => Value::from("flash"),
+            BellStyle::Beep => Value::from("beep"),
+            BellStyle::Both => Value::from("both"),
+        }
+    }
+}
+
+/// The keys in Neovim's notation that Neovide doesn't send to Neovim, set either as a list or as a
+/// comma separated string, where `\,` is a literal comma.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PassthroughKeys(Vec<String>);
+
+impl PassthroughKeys {
+    /// The names of special keys and modifiers are case insensitive, like in Neovim, but plain
+    /// characters aren't.
+    pub fn contains(&self, key: &str) -> bool {
+        self.0.iter().any(|passthrough_key| {
+            if passthrough_key.starts_with('<') {
+                passthrough_key.eq_ignore_ascii_case(key)
+            } else {
+                passthrough_key == key
+            }
+        })
+    }
+
+    fn split(keys: &str) -> Vec<String> {
+        let mut split = vec![String::new()];
+        let mut chars = keys.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' if chars.as_str().starts_with(',') => {
+                    split.last_mut().unwrap().push(',');
+                    chars.next();
+                }
+                ',' => split.push(String::new()),
+                c => split.last_mut().unwrap().push(c),
+            }
+        }
+        split
+            .iter()
+            .map(|key| key.trim())
+            .filter(|key| !key.is_empty())
+            .map(str::to_string)
+            .collect()
+    }
+}
+
+impl ParseFromValue for PassthroughKeys {
+    fn parse_from_value(&mut self, value: Value) {
+        match value {
+            Value::String(keys) if keys.is_str() => {
+                *self = PassthroughKeys(Self::split(keys.as_str().unwrap()))
+            }
+            Value::Array(keys) if keys.iter().all(Value::is_str) => {
+                *self = PassthroughKeys(
+                    keys.iter()
+                        .map(|key| key.as_str().unwrap().to_string())
+                        .collect(),
+                )
+            }
+            value => error!(
+                "Setting PassthroughKeys expected a string or a list of strings, but received {:?}",
+                value
+            ),
+        }
+    }
+}
+
+impl From<PassthroughKeys> for Value {
+    fn from(keys: PassthroughKeys) -> Self {
+        Value::Array(keys.0.into_iter().map(Value::from).collect())
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[cfg(target_os = "macos")]
 pub enum OptionAsMeta {
@@ -117,3 +319,49 @@ impl From<OptionAsMeta> for Value {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn visual_bell_only_flashes() {
+        assert_eq!(BellStyle::None.actions(true), (false, false));
+        assert_eq!(BellStyle::Beep.actions(true), (true, false));
+        assert_eq!(BellStyle::Both.actions(true), (true, false));
+        assert_eq!(BellStyle::Beep.actions(false), (false, true));
+        assert_eq!(BellStyle::Both.actions(false), (true, true));
+    }
+
+    #[test]
+    fn passthrough_keys_are_matched_like_neovim_does() {
+        let mut passthrough_keys = PassthroughKeys::default();
+        assert!(!passthrough_keys.contains("<Esc>"));
+
+        passthrough_keys.parse_from_value(Value::from("<D-q>, <C-S-Tab>,x"));
+        assert!(passthrough_keys.contains("<D-q>"));
+        assert!(passthrough_keys.contains("<c-s-tab>"));
+        assert!(passthrough_keys.contains("x"));
+
+        assert!(!passthrough_keys.contains("X"));
+        assert!(!passthrough_keys.contains("<D-w>"));
+        assert!(!passthrough_keys.contains("q"));
+    }
+
+    #[test]
+    fn passthrough_keys_can_include_a_comma() {
+        let mut passthrough_keys = PassthroughKeys::default();
+        passthrough_keys.parse_from_value(Value::from("<D-q>,\\,"));
+        assert_eq!(passthrough_keys.0, vec!["<D-q>", ","]);
+
+        passthrough_keys
+            .parse_from_value(Value::Array(vec![Value::from(","), Value::from("<M-,>")]));
+        assert_eq!(passthrough_keys.0, vec![",", "<M-,>"]);
+        assert!(passthrough_keys.contains("<m-,>"));
+        assert!(!passthrough_keys.contains("<D-q>"));
+
+        // Anything else keeps the previous list
+        passthrough_keys.parse_from_value(Value::from(1));
+        assert_eq!(passthrough_keys.0, vec![",", "<M-,>"]);
+    }
+}
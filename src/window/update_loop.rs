@@ -9,7 +9,9 @@ use winit::{
 #[cfg(target_os = "macos")]
 use icrate::Foundation::MainThreadMarker;
 
-use super::{save_window_size, CmdLineSettings, UserEvent, WindowSettings, WinitWindowWrapper};
+use super::{
+    reduce_motion, save_window_size, CmdLineSettings, UserEvent, WindowSettings, WinitWindowWrapper,
+};
 use crate::{
     profiling::{tracy_plot, tracy_zone},
     renderer::DrawCommand,
@@ -77,6 +79,78 @@ impl ShouldRender {
 
 const MAX_ANIMATION_DT: f64 = 1.0 / 120.0;
 
+// How many strings are shaped ahead of time after the font changes, and how often, so that it
+// neither delays the frames nor keeps the loop busy
+const WARM_UP_BUDGET: usize = 32;
+const WARM_UP_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Splits the frame into equal steps of at most `MAX_ANIMATION_DT`, so that the animations end up
+/// in nearly the same state no matter how the time is split into frames, without lagging behind
+/// the rendered frame.
+fn animation_steps(dt: Duration) -> (u32, Duration) {
+    let num_steps = ((dt.as_secs_f64() / MAX_ANIMATION_DT).ceil() as u32).max(1);
+    (num_steps, dt / num_steps)
+}
+
+/// When the loop has to wake up without any events. While idle and nothing is animating, only the
+/// cursor blink and held keys need it, instead of waking up at the refresh rate to check for
+/// changes.
+pub fn event_deadline(
+    should_render: &ShouldRender,
+    idle_allowed: bool,
+    frame_deadline: Instant,
+    wake_time: Option<Instant>,
+) -> Option<Instant> {
+    let deadline = match should_render {
+        ShouldRender::Immediately => return Some(Instant::now()),
+        ShouldRender::Deadline(deadline) => Some((*deadline).min(frame_deadline)),
+        ShouldRender::Wait if idle_allowed => None,
+        ShouldRender::Wait => Some(frame_deadline),
+    };
+    // Short blink intervals would otherwise be rounded up to the frame rate, which is low
+    // while idle
+    match (deadline, wake_time) {
+        (Some(deadline), Some(wake_time)) => Some(deadline.min(wake_time)),
+        (deadline, wake_time) => deadline.or(wake_time),
+    }
+}
+
+/// Neovim updates arrive as user events through the proxy, and wake the loop by themselves, so
+/// without a deadline it waits for the next event.
+pub fn control_flow(deadline: Option<Instant>) -> ControlFlow {
+    match deadline {
+        Some(deadline) => ControlFlow::WaitUntil(deadline),
+        None => ControlFlow::Wait,
+    }
+}
+
+/// Whether the window can be seen. Nothing is rendered while it's minimized or fully covered by
+/// other windows, but the Neovim events are still processed, so it's up to date when restored.
+#[derive(Default)]
+struct Visibility {
+    occluded: bool,
+    minimized: bool,
+}
+
+impl Visibility {
+    /// Returns whether the window was hidden, and is visible again.
+    fn handle_event(&mut self, event: &WindowEvent) -> bool {
+        let was_hidden = self.is_hidden();
+        match event {
+            WindowEvent::Occluded(occluded) => self.occluded = *occluded,
+            // Some platforms don't report the occlusion, but resize the window to nothing when
+            // minimizing it
+            WindowEvent::Resized(size) => self.minimized = size.width == 0 || size.height == 0,
+            _ => {}
+        }
+        was_hidden && !self.is_hidden()
+    }
+
+    fn is_hidden(&self) -> bool {
+        self.occluded || self.minimized
+    }
+}
+
 pub struct UpdateLoop {
     idle: bool,
     previous_frame_start: Instant,
@@ -84,6 +158,7 @@ pub struct UpdateLoop {
     should_render: ShouldRender,
     num_consecutive_rendered: u32,
     focused: FocusedState,
+    visibility: Visibility,
     pending_render: bool, // We should render as soon as the compositor/vsync allows
     pending_draw_commands: Vec<Vec<DrawCommand>>,
     animation_start: Instant, // When the last animation started (went from idle to animating)
@@ -121,6 +196,7 @@ impl UpdateLoop {
 
         let cmd_line_settings = SETTINGS.get::<CmdLineSettings>();
         let idle = cmd_line_settings.idle;
+        reduce_motion::update_system_preference();
 
         let window_wrapper = WinitWindowWrapper::new(initial_window_size, initial_font_settings);
 
@@ -131,6 +207,7 @@ impl UpdateLoop {
             should_render,
             num_consecutive_rendered,
             focused,
+            visibility: Visibility::default(),
             pending_render,
             pending_draw_commands,
             animation_start,
@@ -145,7 +222,17 @@ impl UpdateLoop {
         }
     }
 
+    // Idling can be disabled either with `--no-idle` or at runtime with `g:neovide_no_idle`.
+    fn is_idle_allowed(&self) -> bool {
+        self.idle && !SETTINGS.get::<WindowSettings>().no_idle
+    }
+
     fn get_refresh_rate(&self) -> f32 {
+        if !self.is_idle_allowed() {
+            // Keep the full refresh rate when rendering continuously, even when unfocused, so
+            // that frames aren't dropped while screen recording
+            return (SETTINGS.get::<WindowSettings>().refresh_rate as f32).max(1.0);
+        }
         match self.focused {
             // NOTE: Always wait for the idle refresh rate when winit throttling is used to avoid waking up too early
             // The winit redraw request will likely happen much before that and wake it up anyway
@@ -163,17 +250,29 @@ impl UpdateLoop {
         self.previous_frame_start + expected_frame_duration
     }
 
-    fn get_event_deadline(&self) -> Instant {
-        // When there's a pending render we don't need to wait for anything else than the render event
-        if self.pending_render {
-            return self.animation_start + self.animation_time;
+    /// Returns None when nothing needs to be drawn, the loop then sleeps until the next event.
+    fn get_event_deadline(&self) -> Option<Instant> {
+        if self.visibility.is_hidden() {
+            return None;
         }
-
-        match self.should_render {
-            ShouldRender::Immediately => Instant::now(),
-            ShouldRender::Deadline(old_deadline) => old_deadline.min(self.get_frame_deadline()),
-            _ => self.get_frame_deadline(),
+        // When there's a pending render we don't need to wait for anything else than the render event
+        let deadline = if self.pending_render {
+            Some(self.animation_start + self.animation_time)
+        } else {
+            event_deadline(
+                &self.should_render,
+                self.is_idle_allowed(),
+                self.get_frame_deadline(),
+                self.window_wrapper.next_wake_time(),
+            )
+        };
+        // Wake up for the next part of the warm-up, without rendering anything for it
+        let shaper = &self.window_wrapper.renderer.grid_renderer.shaper;
+        if shaper.is_warming_up() {
+            let warm_up = Instant::now() + WARM_UP_INTERVAL;
+            return Some(deadline.map_or(warm_up, |deadline| deadline.min(warm_up)));
         }
+        deadline
     }
 
     fn schedule_next_event(&mut self, event_loop: &ActiveEventLoop) {
@@ -183,7 +282,7 @@ impl UpdateLoop {
             self.window_wrapper
                 .try_create_window(event_loop, &self.proxy);
         }
-        event_loop.set_control_flow(ControlFlow::WaitUntil(self.get_event_deadline()));
+        event_loop.set_control_flow(control_flow(self.get_event_deadline()));
     }
 
     fn animate(&mut self) {
@@ -216,8 +315,7 @@ impl UpdateLoop {
         tracy_plot!("Simulation dt", dt.as_secs_f64());
         self.animation_time += dt;
 
-        let num_steps = (dt.as_secs_f64() / MAX_ANIMATION_DT).ceil() as u32;
-        let step = dt / num_steps;
+        let (num_steps, step) = animation_steps(dt);
         for _ in 0..num_steps {
             if self.window_wrapper.animate_frame(step.as_secs_f32()) {
                 self.should_render = ShouldRender::Immediately;
@@ -284,6 +382,9 @@ impl UpdateLoop {
     }
 
     fn prepare_and_animate(&mut self) {
+        if self.visibility.is_hidden() {
+            return;
+        }
         // We will also animate, but not render when frames are skipped or a bit late, to reduce visual artifacts
         let skipped_frame =
             self.pending_render && Instant::now() > (self.animation_start + self.animation_time);
@@ -300,8 +401,9 @@ impl UpdateLoop {
         let res = self.window_wrapper.prepare_frame();
         self.should_render.update(res);
 
-        let should_animate =
-            self.should_render == ShouldRender::Immediately || !self.idle || skipped_frame;
+        let should_animate = self.should_render == ShouldRender::Immediately
+            || !self.is_idle_allowed()
+            || skipped_frame;
 
         if should_animate {
             self.reset_animation_period();
@@ -318,6 +420,20 @@ impl UpdateLoop {
         }
     }
 
+    fn handle_visibility_event(&mut self, event: &WindowEvent) {
+        if self.visibility.handle_event(event) {
+            // Start the animations over, instead of catching up on the time spent hidden
+            self.num_consecutive_rendered = 0;
+            self.reset_animation_period();
+            self.should_render = ShouldRender::Immediately;
+        } else if self.visibility.is_hidden() && self.pending_render {
+            // The redraw might never arrive while hidden, and the draw commands would wait for it
+            self.pending_render = false;
+            tracy_plot!("pending_render", self.pending_render as u8 as f64);
+            self.process_buffered_draw_commands();
+        }
+    }
+
     fn redraw_requested(&mut self) {
         if self.pending_render {
             tracy_zone!("render (redraw requested)");
@@ -345,6 +461,9 @@ impl ApplicationHandler<UserEvent> for UpdateLoop {
                 self.redraw_requested();
             }
             WindowEvent::Focused(focused_event) => {
+                if focused_event {
+                    reduce_motion::update_system_preference();
+                }
                 self.focused = if focused_event {
                     FocusedState::Focused
                 } else {
@@ -353,6 +472,9 @@ impl ApplicationHandler<UserEvent> for UpdateLoop {
                 #[cfg(target_os = "macos")]
                 self.menu.ensure_menu_added();
             }
+            WindowEvent::Occluded(_) | WindowEvent::Resized(_) => {
+                self.handle_visibility_event(&event);
+            }
             _ => {}
         }
 
@@ -388,7 +510,13 @@ impl ApplicationHandler<UserEvent> for UpdateLoop {
 
     fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
         tracy_zone!("about_to_wait");
+        self.window_wrapper.repeat_held_key();
         self.prepare_and_animate();
+        self.window_wrapper
+            .renderer
+            .grid_renderer
+            .shaper
+            .warm_up(WARM_UP_BUDGET);
         self.schedule_next_event(event_loop);
     }
 
@@ -404,3 +532,84 @@ impl ApplicationHandler<UserEvent> for UpdateLoop {
         self.schedule_next_event(event_loop);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::animation_utils::CriticallyDampedSpringAnimation;
+    use winit::dpi::PhysicalSize;
+
+    fn simulate(frames: &[u64]) -> f32 {
+        let mut animation = CriticallyDampedSpringAnimation::new();
+        animation.position = 10.0;
+        for frame in frames {
+            let (steps, step) = animation_steps(Duration::from_micros(*frame));
+            for _ in 0..steps {
+                animation.update(step.as_secs_f32(), 0.3);
+            }
+        }
+        animation.position
+    }
+
+    #[test]
+    fn animations_barely_depend_on_the_frame_times() {
+        // 200ms, split as 60Hz, 144Hz and uneven frames
+        let even = simulate(&[16_000, 16_000, 16_000, 16_000, 16_000, 16_000, 104_000]);
+        let mut fast_frames = vec![6_944; 28];
+        fast_frames.push(5_568);
+        let fast = simulate(&fast_frames);
+        let uneven = simulate(&[1_000, 33_000, 12_500, 90_000, 500, 63_000]);
+
+        assert!((even - fast).abs() < 0.05, "{even} {fast}");
+        assert!((even - uneven).abs() < 0.05, "{even} {uneven}");
+        assert!(even > 0.0 && even < 10.0);
+    }
+
+    #[test]
+    fn rendering_pauses_while_the_window_is_hidden() {
+        let mut visibility = Visibility::default();
+        assert!(!visibility.is_hidden());
+
+        assert!(!visibility.handle_event(&WindowEvent::Occluded(true)));
+        assert!(visibility.is_hidden());
+        assert!(visibility.handle_event(&WindowEvent::Occluded(false)));
+        assert!(!visibility.is_hidden());
+
+        let minimized = WindowEvent::Resized(PhysicalSize::new(0, 0));
+        assert!(!visibility.handle_event(&minimized));
+        assert!(visibility.is_hidden());
+        // Still hidden while both minimized and occluded
+        visibility.handle_event(&WindowEvent::Occluded(true));
+        assert!(!visibility.handle_event(&WindowEvent::Resized(PhysicalSize::new(800, 600))));
+        assert!(visibility.is_hidden());
+        assert!(visibility.handle_event(&WindowEvent::Occluded(false)));
+    }
+
+    #[test]
+    fn idle_loop_sleeps_until_the_next_event() {
+        let now = Instant::now();
+        let frame_deadline = now + Duration::from_millis(16);
+        let blink = now + Duration::from_millis(500);
+
+        assert_eq!(
+            event_deadline(&ShouldRender::Wait, true, frame_deadline, None),
+            None
+        );
+        assert_eq!(
+            event_deadline(&ShouldRender::Wait, true, frame_deadline, Some(blink)),
+            Some(blink)
+        );
+
+        // Without idling the loop keeps the frame rate
+        assert_eq!(
+            event_deadline(&ShouldRender::Wait, false, frame_deadline, Some(blink)),
+            Some(frame_deadline)
+        );
+
+        let animation = ShouldRender::Deadline(now + Duration::from_millis(5));
+        assert_eq!(
+            event_deadline(&animation, true, frame_deadline, Some(blink)),
+            Some(now + Duration::from_millis(5))
+        );
+    }
+}
@@ -1,5 +1,8 @@
 use super::{
-    KeyboardManager, MouseManager, UserEvent, WindowCommand, WindowSettings, WindowSettingsChanged,
+    diagnostics::{command_line_settings, Diagnostics},
+    fullscreen_transition::FullscreenTransition,
+    is_motion_reduced, KeyboardManager, MouseManager, UserEvent, WindowCommand, WindowSettings,
+    WindowSettingsChanged,
 };
 
 #[cfg(target_os = "macos")]
@@ -14,13 +17,14 @@ use crate::{
     bridge::{send_ui, ParallelCommand, SerialCommand},
     profiling::{tracy_frame, tracy_gpu_collect, tracy_gpu_zone, tracy_plot, tracy_zone},
     renderer::{
-        create_skia_renderer, DrawCommand, Renderer, RendererSettingsChanged, SkiaRenderer, VSync,
+        create_skia_renderer, selected_backend, DrawCommand, GridRenderer, Renderer,
+        RendererSettingsChanged, SkiaRenderer, VSync,
     },
     settings::{
         clamped_grid_size, FontSettings, HotReloadConfigs, SettingsChanged, DEFAULT_GRID_SIZE,
-        MIN_GRID_SIZE, SETTINGS,
+        MAX_DRAWN_GRID_SIZE, MIN_GRID_SIZE, SETTINGS,
     },
-    units::{GridRect, GridSize, PixelPos, PixelSize},
+    units::{GridPos, GridRect, GridScale, GridSize, PixelPos, PixelSize},
     window::{create_window, PhysicalSize, ShouldRender, WindowSize},
     CmdLineSettings,
 };
@@ -31,15 +35,123 @@ use super::macos::MacosWindowFeature;
 #[cfg(target_os = "macos")]
 use icrate::Foundation::MainThreadMarker;
 
+use std::{
+    cmp::Ordering,
+    time::{Duration, Instant},
+};
+
 use log::trace;
 use raw_window_handle::{HasWindowHandle, RawWindowHandle};
+use skia_safe::{Canvas, ISize};
 use winit::{
     dpi,
     event::{Ime, WindowEvent},
     event_loop::{ActiveEventLoop, EventLoopProxy},
-    window::{Fullscreen, Theme},
+    window::{Fullscreen, Theme, Window},
 };
 
+const MIN_ZOOM: f64 = 0.25;
+const MAX_ZOOM: f64 = 4.0;
+const ZOOM_STEP: f64 = 1.1;
+// How long to keep the window hidden while waiting for the default colors, after the first flush
+const DEFAULT_COLORS_TIMEOUT: Duration = Duration::from_millis(500);
+// How long Neovim can take to draw anything before the window is shown with a loading indicator
+const LOADING_INDICATOR_DELAY: Duration = Duration::from_millis(500);
+const LOADING_TEXT: &str = "Loading Neovim…";
+
+/// Applies the given number of zoom steps to the scale factor, positive steps zoom in. Only the
+/// limit in the direction of the zoom applies, so a scale factor that's already past it is kept.
+fn zoomed_scale_factor(scale_factor: f64, steps: i32) -> f64 {
+    let zoomed = scale_factor * ZOOM_STEP.powi(steps);
+    match steps.cmp(&0) {
+        Ordering::Greater => zoomed.min(MAX_ZOOM.max(scale_factor)),
+        Ordering::Less => zoomed.max(MIN_ZOOM.min(scale_factor)),
+        Ordering::Equal => scale_factor,
+    }
+}
+
+/// Plays the alert sound of the OS.
+#[cfg(target_os = "windows")]
+fn system_beep(_window: &Window) {
+    use windows::Win32::{System::Diagnostics::Debug::MessageBeep, UI::WindowsAndMessaging::MB_OK};
+    let _ = unsafe { MessageBeep(MB_OK) };
+}
+
+/// Plays the alert sound of the OS.
+#[cfg(target_os = "macos")]
+fn system_beep(_window: &Window) {
+    unsafe { icrate::AppKit::NSBeep() };
+}
+
+/// There's no common alert sound on Linux, so the window asks for attention instead, which most
+/// window managers handle like a terminal bell.
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn system_beep(window: &Window) {
+    window.request_user_attention(Some(winit::window::UserAttentionType::Informational));
+}
+
+/// Decides whether the window can be shown once Neovim has drawn its first frame. Showing it before
+/// the default colors are known flashes a black or white grid before the colorscheme loads.
+fn ready_to_show(
+    wait_for_default_colors: bool,
+    has_default_colors: bool,
+    waited: Duration,
+) -> bool {
+    !wait_for_default_colors || has_default_colors || waited >= DEFAULT_COLORS_TIMEOUT
+}
+
+/// Decides whether to show the window with a loading indicator, because Neovim takes a while to
+/// start. A fast startup never gets to see it.
+fn loading_indicator_due(enabled: bool, has_flushed: bool, waited: Duration) -> bool {
+    enabled && !has_flushed && waited >= LOADING_INDICATOR_DELAY
+}
+
+/// Draws the loading text centered in the window, the shaper falls back to another font for the
+/// characters the configured one doesn't have.
+fn draw_loading_indicator(grid_renderer: &mut GridRenderer, canvas: &Canvas, size: PixelSize<f32>) {
+    let columns = LOADING_TEXT.chars().count() as i32;
+    let text_size = GridSize::new(columns, 1).cast() * grid_renderer.grid_scale;
+    let offset = (size - text_size) / 2.0;
+    canvas.save();
+    canvas.translate((
+        offset.width.max(0.0).round(),
+        offset.height.max(0.0).round(),
+    ));
+    grid_renderer.draw_foreground(canvas, LOADING_TEXT, GridPos::origin(), columns, &None);
+    canvas.restore();
+}
+
+fn grid_size_from_content_size(
+    content_size: PixelSize<u32>,
+    grid_scale: GridScale,
+    min: GridSize<u32>,
+) -> GridSize<u32> {
+    let grid_size = (content_size / grid_scale).floor().try_cast().unwrap();
+
+    // The rest of the window stays empty, Neovim is told the capped size
+    grid_size.max(min).min(MAX_DRAWN_GRID_SIZE)
+}
+
+/// The part of the content area that is too small to fit another whole cell
+fn partial_cell_remainder(content_size: PixelSize<u32>, grid_scale: GridScale) -> PixelSize<u32> {
+    let grid_size = grid_size_from_content_size(content_size, grid_scale, GridSize::default());
+    // Round up, so that the snapped size still fits the same number of cells
+    let used_size: PixelSize<u32> = (grid_size * grid_scale).ceil().try_cast().unwrap();
+    content_size - used_size
+}
+
+/// The width of the reserved gutter on the left, rounded up to whole pixels so that it never
+/// overlaps the first column.
+fn gutter_width(columns: u32, grid_scale: GridScale) -> u32 {
+    (columns as f32 * grid_scale.width()).ceil() as u32
+}
+
+/// Where the grid starts, in cells. Neovim's windows are positioned relative to this, so the mouse
+/// is mapped through it as well.
+fn grid_origin(padding: &WindowPadding, grid_scale: GridScale) -> GridPos<f32> {
+    PixelPos::new(padding.left, padding.top).cast() / grid_scale
+}
+
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub struct WindowPadding {
     pub top: u32,
@@ -74,9 +186,15 @@ pub struct WinitWindowWrapper {
     requested_columns: Option<u32>,
     requested_lines: Option<u32>,
     ui_state: UIState,
+    start_time: Instant,
+    /// The window was shown before Neovim drew anything, and shows the loading indicator
+    loading: bool,
+    first_flush_time: Option<Instant>,
+    has_default_colors: bool,
     window_padding: WindowPadding,
     initial_window_size: WindowSize,
     is_minimized: bool,
+    fullscreen_transition: Option<FullscreenTransition>,
     ime_enabled: bool,
     ime_area: (dpi::PhysicalPosition<u32>, dpi::PhysicalSize<u32>),
     pub vsync: Option<VSync>,
@@ -104,6 +222,10 @@ impl WinitWindowWrapper {
             requested_columns: None,
             requested_lines: None,
             ui_state: UIState::Initing,
+            start_time: Instant::now(),
+            loading: false,
+            first_flush_time: None,
+            has_default_colors: false,
             window_padding: WindowPadding {
                 left: 0,
                 right: 0,
@@ -112,6 +234,7 @@ impl WinitWindowWrapper {
             },
             initial_window_size,
             is_minimized: false,
+            fullscreen_transition: None,
             vsync: None,
             ime_enabled: false,
             ime_area: Default::default(),
@@ -128,6 +251,18 @@ impl WinitWindowWrapper {
     pub fn set_fullscreen(&mut self, fullscreen: bool) {
         if let Some(skia_renderer) = &self.skia_renderer {
             let window = skia_renderer.window();
+            let settings = SETTINGS.get::<WindowSettings>();
+            if settings.fullscreen_animation
+                && settings.fullscreen_animation_length > 0.0
+                && !is_motion_reduced()
+                && self.ui_state >= UIState::Showing
+            {
+                let size = self.saved_inner_size;
+                self.fullscreen_transition = Some(FullscreenTransition::new(
+                    PixelSize::new(size.width, size.height).cast(),
+                    settings.fullscreen_animation_length,
+                ));
+            }
             if fullscreen {
                 let handle = window.current_monitor();
                 window.set_fullscreen(Some(Fullscreen::Borderless(handle)));
@@ -162,6 +297,18 @@ impl WinitWindowWrapper {
         }
     }
 
+    fn ring_bell(&mut self, visual: bool) {
+        let (flash, beep) = SETTINGS.get::<WindowSettings>().bell.actions(visual);
+        if flash {
+            self.renderer.ring_visual_bell();
+        }
+        if beep {
+            if let Some(skia_renderer) = &self.skia_renderer {
+                system_beep(skia_renderer.window());
+            }
+        }
+    }
+
     pub fn set_ime(&mut self, ime_enabled: bool) {
         if let Some(skia_renderer) = &self.skia_renderer {
             skia_renderer.window().set_ime_allowed(ime_enabled);
@@ -173,9 +320,21 @@ impl WinitWindowWrapper {
         match command {
             WindowCommand::TitleChanged(new_title) => self.handle_title_changed(new_title),
             WindowCommand::SetMouseEnabled(mouse_enabled) => {
-                self.mouse_manager.enabled = mouse_enabled
+                self.mouse_manager.set_enabled(mouse_enabled)
             }
             WindowCommand::ListAvailableFonts => self.send_font_names(),
+            WindowCommand::ShowDiagnostics(path) => self.send_diagnostics(path),
+            WindowCommand::Screenshot(path) => self.save_screenshot(&path),
+            WindowCommand::ReloadFont(guifont) => {
+                self.renderer.grid_renderer.reload_font(&guifont);
+                self.font_changed_last_frame = true;
+            }
+            WindowCommand::SetSecondaryCursors(positions) => {
+                self.renderer.set_secondary_cursors(positions);
+            }
+            WindowCommand::SetCursorTabSpan(tab_span) => {
+                self.renderer.set_cursor_tab_span(tab_span);
+            }
             WindowCommand::FocusWindow => {
                 if let Some(skia_renderer) = &self.skia_renderer {
                     skia_renderer.window().focus_window();
@@ -185,6 +344,15 @@ impl WinitWindowWrapper {
                 self.minimize_window();
                 self.is_minimized = true;
             }
+            WindowCommand::ToggleFullscreen => {
+                if let Some(skia_renderer) = &self.skia_renderer {
+                    let fullscreen = skia_renderer.window().fullscreen().is_none();
+                    self.set_fullscreen(fullscreen);
+                }
+            }
+            WindowCommand::Zoom(steps) => self.zoom(steps),
+            WindowCommand::ResetZoom => self.reset_zoom(),
+            WindowCommand::Bell { visual } => self.ring_bell(visual),
             WindowCommand::ThemeChanged(new_theme) => {
                 self.handle_theme_changed(new_theme);
             }
@@ -209,16 +377,14 @@ impl WinitWindowWrapper {
             WindowSettingsChanged::Fullscreen(fullscreen) => {
                 self.set_fullscreen(fullscreen);
             }
+            WindowSettingsChanged::SnapWindowToGrid(..) => {
+                self.snap_window_to_grid();
+            }
             WindowSettingsChanged::InputIme(ime_enabled) => {
                 self.set_ime(ime_enabled);
             }
             WindowSettingsChanged::ScaleFactor(user_scale_factor) => {
-                let renderer = &mut self.renderer;
-                renderer.user_scale_factor = user_scale_factor.into();
-                renderer.grid_renderer.handle_scale_factor_update(
-                    renderer.os_scale_factor * renderer.user_scale_factor,
-                );
-                self.font_changed_last_frame = true;
+                self.set_user_scale_factor(user_scale_factor.into());
             }
             WindowSettingsChanged::WindowBlurred(blur) => {
                 if let Some(skia_renderer) = &self.skia_renderer {
@@ -257,10 +423,55 @@ impl WinitWindowWrapper {
                 }
                 self.font_changed_last_frame = true;
             }
+            RendererSettingsChanged::Grayscale(..)
+            | RendererSettingsChanged::GrayscaleThreshold(..) => {
+                self.renderer.update_color_mode();
+                // Redraw the cached lines with the new colors
+                self.font_changed_last_frame = true;
+            }
+            RendererSettingsChanged::FontKerning(..)
+            | RendererSettingsChanged::FontLigatures(..) => {
+                self.renderer.update_shaping_features();
+                self.font_changed_last_frame = true;
+            }
+            RendererSettingsChanged::ShapingCacheSize(..)
+            | RendererSettingsChanged::ShapingCacheMemory(..) => {
+                self.renderer.update_shaping_cache_limit();
+            }
+            RendererSettingsChanged::ShapingWarmUp(..) => {
+                self.renderer.update_shaping_warm_up();
+            }
+            RendererSettingsChanged::GlyphAlignment(..) => {
+                self.renderer.update_glyph_alignment();
+                self.font_changed_last_frame = true;
+            }
             _ => {}
         }
     }
 
+    fn set_user_scale_factor(&mut self, user_scale_factor: f64) {
+        let renderer = &mut self.renderer;
+        renderer.user_scale_factor = user_scale_factor;
+        renderer
+            .grid_renderer
+            .handle_scale_factor_update(renderer.os_scale_factor * renderer.user_scale_factor);
+        // This makes the next frame re-report the grid size to Neovim
+        self.font_changed_last_frame = true;
+    }
+
+    fn zoom(&mut self, steps: i32) {
+        let user_scale_factor = zoomed_scale_factor(self.renderer.user_scale_factor, steps);
+        log::info!("Zooming to scale factor {user_scale_factor:.2}");
+        self.set_user_scale_factor(user_scale_factor);
+    }
+
+    /// Goes back to the configured `g:neovide_scale_factor`, undoing all the zoom steps at once.
+    fn reset_zoom(&mut self) {
+        let user_scale_factor = SETTINGS.get::<WindowSettings>().scale_factor.into();
+        log::info!("Resetting the zoom to scale factor {user_scale_factor:.2}");
+        self.set_user_scale_factor(user_scale_factor);
+    }
+
     pub fn handle_title_changed(&mut self, new_title: String) {
         self.title = new_title;
         if let Some(skia_renderer) = &self.skia_renderer {
@@ -279,6 +490,38 @@ impl WinitWindowWrapper {
         send_ui(ParallelCommand::DisplayAvailableFonts(font_names));
     }
 
+    fn send_diagnostics(&mut self, path: Option<String>) {
+        let renderer = &mut self.renderer;
+        let grid_scale = renderer.grid_renderer.grid_scale;
+        let backend = selected_backend();
+        let diagnostics = Diagnostics {
+            backend,
+            renderer: self
+                .skia_renderer
+                .as_ref()
+                .map_or_else(|| (&backend).into(), |skia_renderer| skia_renderer.name()),
+            scale_factor: renderer.os_scale_factor * renderer.user_scale_factor,
+            cell_size: PixelSize::new(grid_scale.width(), grid_scale.height()),
+            fonts: renderer.grid_renderer.shaper.loaded_font_names(),
+            settings: SETTINGS.current_values(),
+            command_line: command_line_settings(&SETTINGS.get::<CmdLineSettings>()),
+        };
+        send_ui(ParallelCommand::ShowDiagnostics(diagnostics.report(), path));
+    }
+
+    fn save_screenshot(&mut self, path: &str) {
+        let result = match self.renderer.screenshot() {
+            Some(png) => std::fs::write(path, png.as_bytes()).map_err(|error| error.to_string()),
+            None => Err("nothing has been drawn yet".to_string()),
+        };
+        if let Err(error) = result {
+            log::error!("Cannot save the screenshot to {path}: {error}");
+            send_ui(ParallelCommand::ShowError {
+                lines: vec![format!("Cannot save the screenshot to {path}: {error}")],
+            });
+        }
+    }
+
     pub fn handle_quit(&mut self) {
         send_ui(ParallelCommand::Quit);
     }
@@ -300,6 +543,21 @@ impl WinitWindowWrapper {
 
     pub fn handle_window_event(&mut self, event: WindowEvent) -> bool {
         // The renderer and vsync should always be created when a window event is received
+        if SETTINGS.get::<WindowSettings>().zoom_bindings {
+            let zoom_steps = self.keyboard_manager.zoom_steps(&event).or_else(|| {
+                self.mouse_manager
+                    .zoom_steps(&event, &self.keyboard_manager)
+            });
+            if let Some(steps) = zoom_steps {
+                self.zoom(steps);
+                return self.ui_state >= UIState::FirstFrame;
+            }
+            if self.keyboard_manager.resets_zoom(&event) {
+                self.reset_zoom();
+                return self.ui_state >= UIState::FirstFrame;
+            }
+        }
+
         let skia_renderer = self.skia_renderer.as_mut().unwrap();
         let vsync = self.vsync.as_mut().unwrap();
 
@@ -405,7 +663,29 @@ impl WinitWindowWrapper {
             self.font_changed_last_frame = false;
             self.renderer.prepare_lines(true);
         }
-        self.renderer.draw_frame(skia_renderer.canvas(), dt);
+        let size = skia_renderer.window().inner_size();
+        let canvas = skia_renderer.canvas();
+        if let Some(transition) = &self.fullscreen_transition {
+            let origin = transition.content_rect().min;
+            let (scale_x, scale_y) = transition.content_scale();
+            let content_size = transition.content_size().cast::<i32>();
+            canvas.save();
+            canvas.translate((origin.x, origin.y));
+            canvas.scale((scale_x, scale_y));
+            self.renderer.draw_frame(
+                canvas,
+                ISize::new(content_size.width, content_size.height),
+                dt,
+            );
+            canvas.restore();
+        } else {
+            self.renderer
+                .draw_frame(canvas, canvas.base_layer_size(), dt);
+        }
+        if self.loading {
+            let size = PixelSize::new(size.width, size.height).cast();
+            draw_loading_indicator(&mut self.renderer.grid_renderer, canvas, size);
+        }
         skia_renderer.flush();
         {
             tracy_gpu_zone!("wait for vsync");
@@ -423,15 +703,44 @@ impl WinitWindowWrapper {
     pub fn animate_frame(&mut self, dt: f32) -> bool {
         tracy_zone!("animate_frame", 0);
 
+        // The grid gets resized by prepare_frame once the transition is over, and the transition
+        // is only removed there when the resized grid has arrived. Until then the old grid is
+        // still drawn scaled to the window, so the frames have to be prepared again.
+        if let Some(transition) = &mut self.fullscreen_transition {
+            transition.animate(dt);
+        }
+        let transitioning = self.fullscreen_transition.is_some();
+
         let res = self
             .renderer
-            .animate_frame(&self.get_grid_rect_from_window(GridSize::default()), dt);
+            .animate_frame(&self.get_grid_rect_from_window(GridSize::default()), dt)
+            || transitioning;
         tracy_plot!("animate_frame", res as u8 as f64);
         self.renderer.prepare_lines(false);
         #[allow(clippy::let_and_return)]
         res
     }
 
+    pub fn repeat_held_key(&mut self) {
+        self.keyboard_manager.repeat_held_key();
+    }
+
+    /// When the loop has to wake up without any events, for the cursor blink or the key repeat.
+    pub fn next_wake_time(&self) -> Option<Instant> {
+        let loading_indicator_time = (self.ui_state == UIState::Initing
+            && self.first_flush_time.is_none()
+            && SETTINGS.get::<WindowSettings>().loading_indicator)
+            .then(|| self.start_time + LOADING_INDICATOR_DELAY);
+        [
+            self.renderer.next_wake_time(),
+            self.keyboard_manager.next_key_repeat(),
+            loading_indicator_time,
+        ]
+        .into_iter()
+        .flatten()
+        .min()
+    }
+
     pub fn try_create_window(
         &mut self,
         event_loop: &ActiveEventLoop,
@@ -580,11 +889,47 @@ impl WinitWindowWrapper {
         let handle_draw_commands_result = self.renderer.handle_draw_commands(batch);
 
         self.font_changed_last_frame |= handle_draw_commands_result.font_changed;
+        self.has_default_colors |= handle_draw_commands_result.default_style_changed;
 
-        if self.ui_state == UIState::Initing && handle_draw_commands_result.should_show {
-            log::info!("Showing the Window");
+        if handle_draw_commands_result.should_show && self.first_flush_time.is_none() {
+            self.first_flush_time = Some(Instant::now());
+            // The content takes the place of the loading indicator
+            self.loading = false;
+        }
+        self.show_window_when_ready();
+    }
+
+    fn show_window_when_ready(&mut self) {
+        if self.ui_state != UIState::Initing {
+            return;
+        }
+        let window_settings = SETTINGS.get::<WindowSettings>();
+        if loading_indicator_due(
+            window_settings.loading_indicator,
+            self.first_flush_time.is_some(),
+            self.start_time.elapsed(),
+        ) {
+            log::info!("Showing the Window with a loading indicator");
+            self.loading = true;
             self.ui_state = UIState::WaitingForWindowCreate;
+            return;
+        }
+        let Some(first_flush_time) = self.first_flush_time else {
+            return;
         };
+
+        let wait_for_default_colors = window_settings.wait_for_default_colors;
+        if ready_to_show(
+            wait_for_default_colors,
+            self.has_default_colors,
+            first_flush_time.elapsed(),
+        ) {
+            if !self.has_default_colors && wait_for_default_colors {
+                log::info!("Timed out waiting for the default colors");
+            }
+            log::info!("Showing the Window");
+            self.ui_state = UIState::WaitingForWindowCreate;
+        }
     }
 
     fn handle_config_changed(&mut self, config: HotReloadConfigs) {
@@ -607,11 +952,80 @@ impl WinitWindowWrapper {
             padding_top
         };
 
-        WindowPadding {
+        // The gutter is cell sized, but doesn't map to any Neovim cells
+        let gutter = gutter_width(
+            window_settings.gutter_columns,
+            self.renderer.grid_renderer.grid_scale,
+        );
+
+        let padding = WindowPadding {
             top: window_padding_top,
-            left: window_settings.padding_left,
+            left: window_settings.padding_left + gutter,
             right: window_settings.padding_right,
             bottom: window_settings.padding_bottom,
+        };
+
+        // A fullscreen or maximized window can't be snapped, so center the grid in it instead
+        if window_settings.snap_window_to_grid && !self.is_window_resizable() {
+            let padding_size =
+                PixelSize::new(padding.left + padding.right, padding.top + padding.bottom);
+            let inner_size =
+                PixelSize::new(self.saved_inner_size.width, self.saved_inner_size.height);
+            if padding_size.width <= inner_size.width && padding_size.height <= inner_size.height {
+                let remainder = partial_cell_remainder(
+                    inner_size - padding_size,
+                    self.renderer.grid_renderer.grid_scale,
+                );
+                return WindowPadding {
+                    top: padding.top + remainder.height / 2,
+                    left: padding.left + remainder.width / 2,
+                    right: padding.right + remainder.width - remainder.width / 2,
+                    bottom: padding.bottom + remainder.height - remainder.height / 2,
+                };
+            }
+        }
+
+        padding
+    }
+
+    fn is_window_resizable(&self) -> bool {
+        let Some(skia_renderer) = &self.skia_renderer else {
+            return true;
+        };
+        let window = skia_renderer.window();
+        window.fullscreen().is_none() && !window.is_maximized()
+    }
+
+    /// Shrinks the window to a whole number of cells, so that there are no partial cells at the
+    /// edges.
+    fn snap_window_to_grid(&self) {
+        if !SETTINGS.get::<WindowSettings>().snap_window_to_grid || !self.is_window_resizable() {
+            return;
+        }
+        let Some(skia_renderer) = &self.skia_renderer else {
+            return;
+        };
+
+        let padding = self.window_padding;
+        let padding_size =
+            PixelSize::new(padding.left + padding.right, padding.top + padding.bottom);
+        let inner_size = PixelSize::new(self.saved_inner_size.width, self.saved_inner_size.height);
+        if padding_size.width > inner_size.width || padding_size.height > inner_size.height {
+            return;
+        }
+
+        let remainder = partial_cell_remainder(
+            inner_size - padding_size,
+            self.renderer.grid_renderer.grid_scale,
+        );
+        if remainder != PixelSize::zero() {
+            let snapped_size = inner_size - remainder;
+            let _ = skia_renderer
+                .window()
+                .request_inner_size(winit::dpi::PhysicalSize {
+                    width: snapped_size.width,
+                    height: snapped_size.height,
+                });
         }
     }
 
@@ -623,6 +1037,7 @@ impl WinitWindowWrapper {
         let padding_changed = window_padding != self.window_padding;
 
         // Don't render until the UI is fully entered and the window is shown
+        self.show_window_when_ready();
         if self.ui_state < UIState::FirstFrame {
             return ShouldRender::Wait;
         } else if self.ui_state == UIState::FirstFrame {
@@ -642,12 +1057,36 @@ impl WinitWindowWrapper {
             // NOTE: Only actually resize the grid when the window is not minimized
             // Some platforms return a zero size when that is the case, so we should not try to resize to that.
             let new_size = skia_renderer.window().inner_size();
-            if self.saved_inner_size != new_size || self.font_changed_last_frame || padding_changed
+            if let Some(transition) = &mut self.fullscreen_transition {
+                transition.set_target(PixelSize::new(new_size.width, new_size.height).cast());
+                should_render = ShouldRender::Immediately;
+            }
+            // Keep the grid at its old size until the transition ends, so that Neovim only gets
+            // the final size
+            let transitioning = self
+                .fullscreen_transition
+                .as_ref()
+                .is_some_and(|transition| !transition.is_finished());
+            if !transitioning
+                && (self.saved_inner_size != new_size
+                    || self.font_changed_last_frame
+                    || padding_changed)
             {
                 self.window_padding = window_padding;
                 self.saved_inner_size = new_size;
 
                 self.update_grid_size_from_window();
+                self.snap_window_to_grid();
+                should_render = ShouldRender::Immediately;
+            }
+        }
+
+        // The transition only ends once Neovim has sent the grid at the new size, otherwise the
+        // old grid would be drawn unscaled for a few frames
+        if let Some(transition) = &self.fullscreen_transition {
+            let resized = self.saved_grid_size == Some(self.get_grid_size());
+            if transition.is_finished() && (resized || transition.is_stale()) {
+                self.fullscreen_transition = None;
                 should_render = ShouldRender::Immediately;
             }
         }
@@ -718,18 +1157,12 @@ impl WinitWindowWrapper {
             PixelSize::new(self.saved_inner_size.width, self.saved_inner_size.height)
                 - window_padding_size;
 
-        let grid_size = (content_size / self.renderer.grid_renderer.grid_scale)
-            .floor()
-            .try_cast()
-            .unwrap();
-
-        grid_size.max(min)
+        grid_size_from_content_size(content_size, self.renderer.grid_renderer.grid_scale, min)
     }
 
     fn get_grid_rect_from_window(&self, min: GridSize<u32>) -> GridRect<f32> {
         let size = self.get_grid_size_from_window(min).try_cast().unwrap();
-        let pos = PixelPos::new(self.window_padding.left, self.window_padding.top).cast()
-            / self.renderer.grid_renderer.grid_scale;
+        let pos = grid_origin(&self.window_padding, self.renderer.grid_renderer.grid_scale);
         GridRect::<f32>::from_origin_and_size(pos, size)
     }
 
@@ -792,3 +1225,137 @@ impl WinitWindowWrapper {
         skia_renderer.resize();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::GridRenderer;
+
+    #[test]
+    fn window_waits_for_the_default_colors() {
+        let short_wait = Duration::from_millis(10);
+        assert!(!ready_to_show(true, false, short_wait));
+        assert!(ready_to_show(true, true, short_wait));
+        assert!(ready_to_show(false, false, short_wait));
+        // Don't wait forever if they never arrive
+        assert!(ready_to_show(true, false, DEFAULT_COLORS_TIMEOUT));
+    }
+
+    #[test]
+    fn loading_indicator_only_shows_on_slow_startups() {
+        let delay = LOADING_INDICATOR_DELAY;
+        assert!(!loading_indicator_due(true, false, delay / 2));
+        assert!(loading_indicator_due(true, false, delay));
+        // Neovim drew something in time, or it's turned off
+        assert!(!loading_indicator_due(true, true, delay * 2));
+        assert!(!loading_indicator_due(false, false, delay * 2));
+    }
+
+    #[test]
+    fn zoom_is_clamped() {
+        assert!(zoomed_scale_factor(1.0, 1) > 1.0);
+        assert!(zoomed_scale_factor(1.0, -1) < 1.0);
+        assert_eq!(zoomed_scale_factor(MAX_ZOOM, 1), MAX_ZOOM);
+        assert_eq!(zoomed_scale_factor(MIN_ZOOM, -1), MIN_ZOOM);
+        assert_eq!(zoomed_scale_factor(1.0, 100), MAX_ZOOM);
+        // A scale factor set past the limits isn't pulled back in by zooming further
+        assert_eq!(zoomed_scale_factor(5.0, 1), 5.0);
+        assert!(zoomed_scale_factor(5.0, -1) < 5.0);
+        assert_eq!(zoomed_scale_factor(0.1, -1), 0.1);
+        assert!(zoomed_scale_factor(0.1, 1) > 0.1);
+    }
+
+    #[test]
+    fn zooming_changes_the_grid_size() {
+        let mut grid_renderer = GridRenderer::new(1.0);
+        let content_size = PixelSize::new(1600, 1200);
+        let min = GridSize::new(1, 1);
+
+        let initial = grid_size_from_content_size(content_size, grid_renderer.grid_scale, min);
+
+        grid_renderer.handle_scale_factor_update(zoomed_scale_factor(1.0, 3));
+        let zoomed_in = grid_size_from_content_size(content_size, grid_renderer.grid_scale, min);
+        assert!(zoomed_in.width < initial.width);
+        assert!(zoomed_in.height < initial.height);
+
+        grid_renderer.handle_scale_factor_update(zoomed_scale_factor(1.0, -3));
+        let zoomed_out = grid_size_from_content_size(content_size, grid_renderer.grid_scale, min);
+        assert!(zoomed_out.width > initial.width);
+        assert!(zoomed_out.height > initial.height);
+    }
+
+    #[test]
+    fn resetting_the_zoom_restores_the_grid_size() {
+        SETTINGS.set(&WindowSettings::default());
+        SETTINGS.set(&crate::renderer::RendererSettings::default());
+        let mut window_wrapper = WinitWindowWrapper::new(WindowSize::NeovimGrid, None);
+        window_wrapper.saved_inner_size = PhysicalSize::new(1600, 1200);
+        let initial = window_wrapper.get_grid_size_from_window(MIN_GRID_SIZE);
+
+        window_wrapper.zoom(5);
+        let zoomed = window_wrapper.get_grid_size_from_window(MIN_GRID_SIZE);
+        assert!(zoomed.width < initial.width && zoomed.height < initial.height);
+        // The grid is sent to Neovim again on the next frame
+        assert!(window_wrapper.font_changed_last_frame);
+
+        window_wrapper.reset_zoom();
+        assert_eq!(
+            window_wrapper.renderer.user_scale_factor,
+            f64::from(WindowSettings::default().scale_factor)
+        );
+        assert_eq!(
+            window_wrapper.get_grid_size_from_window(MIN_GRID_SIZE),
+            initial
+        );
+    }
+
+    #[test]
+    fn window_is_snapped_to_whole_cells() {
+        let grid_scale = GridScale::new(PixelSize::new(8.5, 17.0));
+
+        let remainder = partial_cell_remainder(PixelSize::new(855, 180), grid_scale);
+        // 100 columns need 850 pixels and 10 lines 170 pixels
+        assert_eq!(remainder, PixelSize::new(5, 10));
+
+        // Fractional cell widths are rounded up so that the snapped size keeps all the cells
+        let remainder = partial_cell_remainder(PixelSize::new(30, 17), grid_scale);
+        assert_eq!(remainder, PixelSize::new(4, 0));
+        let snapped_size = PixelSize::new(30, 17) - remainder;
+        assert_eq!(
+            grid_size_from_content_size(snapped_size, grid_scale, GridSize::default()),
+            GridSize::new(3, 1)
+        );
+    }
+
+    #[test]
+    fn gutter_offsets_the_grid_by_whole_columns() {
+        let grid_scale = GridScale::new(PixelSize::new(8.4, 17.0));
+        let padding = WindowPadding {
+            top: 0,
+            left: gutter_width(3, grid_scale),
+            right: 0,
+            bottom: 0,
+        };
+        let origin = grid_origin(&padding, grid_scale);
+        assert_eq!(origin.x.floor(), 3.0);
+        assert_eq!(origin.y, 0.0);
+
+        // The gutter takes columns away from Neovim
+        let content_size = PixelSize::new(840 - padding.left, 170);
+        let grid_size = grid_size_from_content_size(content_size, grid_scale, GridSize::new(1, 1));
+        assert_eq!(grid_size, GridSize::new(96, 10));
+    }
+
+    #[test]
+    fn huge_windows_are_capped_to_the_max_grid_size() {
+        // A fullscreen 5K display with a font of a couple of pixels
+        let grid_scale = GridScale::new(PixelSize::new(2.0, 4.0));
+        let content_size = PixelSize::new(5120, 2880);
+        let grid_size = grid_size_from_content_size(content_size, grid_scale, MIN_GRID_SIZE);
+        assert_eq!(grid_size, MAX_DRAWN_GRID_SIZE);
+
+        let content_size = PixelSize::new(5120, 400);
+        let grid_size = grid_size_from_content_size(content_size, grid_scale, MIN_GRID_SIZE);
+        assert_eq!(grid_size, GridSize::new(MAX_DRAWN_GRID_SIZE.width, 100));
+    }
+}